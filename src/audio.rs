@@ -0,0 +1,275 @@
+// Audio monitoring: plays one channel through the default output device. Only
+// built with the `audio` feature.
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use std::collections::VecDeque;
+use std::f64::consts::TAU;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+const TONE_FREQUENCY: f64 = 440.0; // Carrier frequency in tone mode
+const MAX_QUEUED_SECONDS: f64 = 0.5; // Older input is dropped to keep the latency bounded
+pub const DEFAULT_PITCH_RANGE: (f32, f32) = (220.0, 880.0); // Hz at the bottom and top of the signal range
+pub const PITCH_LIMITS: std::ops::RangeInclusive<f32> = 20.0..=20000.0;
+const BEEP_FREQUENCY: f64 = 880.0;
+const BEEP_DURATION: std::time::Duration = std::time::Duration::from_millis(150);
+
+#[derive(Clone, Copy, PartialEq, Default)]
+pub enum AudioMode {
+    /// Play the (resampled) signal itself.
+    #[default]
+    Signal,
+    /// Play a fixed-pitch tone whose loudness follows the signal amplitude.
+    Tone,
+    /// Play a tone whose pitch follows the signal within the pitch range.
+    Pitch,
+}
+
+impl AudioMode {
+    pub const ALL: [AudioMode; 3] = [AudioMode::Signal, AudioMode::Tone, AudioMode::Pitch];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            AudioMode::Signal => "Signal",
+            AudioMode::Tone => "Tone",
+            AudioMode::Pitch => "Pitch",
+        }
+    }
+}
+
+/// Errors from the output threads and stream callbacks, collected for the UI to
+/// show since nothing on those threads can report back directly.
+pub struct AudioErrors {
+    sender: crossbeam_channel::Sender<String>,
+    receiver: crossbeam_channel::Receiver<String>,
+}
+
+impl Default for AudioErrors {
+    fn default() -> Self {
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        Self { sender, receiver }
+    }
+}
+
+impl AudioErrors {
+    pub fn sender(&self) -> crossbeam_channel::Sender<String> {
+        self.sender.clone()
+    }
+
+    /// Messages reported since the last call.
+    pub fn drain(&self) -> impl Iterator<Item = String> + '_ {
+        self.receiver.try_iter()
+    }
+}
+
+struct Shared {
+    queue: VecDeque<f32>,
+    volume: f32,
+    mode: AudioMode,
+    pitch_range: (f32, f32),
+}
+
+/// Handle to a running audio output. Dropping it stops playback.
+pub struct AudioMonitor {
+    shared: Arc<Mutex<Shared>>,
+    source_rate: f64,
+    // the output stream lives on its own thread until this sender is dropped
+    _stop: crossbeam_channel::Sender<()>,
+}
+
+impl AudioMonitor {
+    /// Open the default output device and start playing samples that arrive at `source_rate` Hz.
+    pub fn start(
+        source_rate: f64,
+        volume: f32,
+        mode: AudioMode,
+        pitch_range: (f32, f32),
+        errors: crossbeam_channel::Sender<String>,
+    ) -> Result<Self, String> {
+        if source_rate <= 0.0 {
+            return Err("audio monitoring needs a regular sample rate".to_string());
+        }
+
+        let shared = Arc::new(Mutex::new(Shared {
+            queue: VecDeque::new(),
+            volume,
+            mode,
+            pitch_range,
+        }));
+        let (stop_tx, stop_rx) = crossbeam_channel::bounded::<()>(0);
+        let (ready_tx, ready_rx) = crossbeam_channel::bounded::<Result<(), String>>(1);
+
+        // cpal streams can't be moved between threads, so the stream is created,
+        // played and dropped on a dedicated thread
+        let thread_shared = shared.clone();
+        thread::spawn(
+            move || match open_output(thread_shared, source_rate, errors) {
+                Ok(_stream) => {
+                    let _ = ready_tx.send(Ok(()));
+                    // blocks until the monitor is dropped, then the stream goes out of scope
+                    let _ = stop_rx.recv();
+                }
+                Err(e) => {
+                    let _ = ready_tx.send(Err(e));
+                }
+            },
+        );
+
+        match ready_rx.recv() {
+            Ok(Ok(())) => Ok(Self {
+                shared,
+                source_rate,
+                _stop: stop_tx,
+            }),
+            Ok(Err(e)) => Err(e),
+            Err(_) => Err("audio thread exited unexpectedly".to_string()),
+        }
+    }
+
+    /// Queue one input sample, already normalized to roughly -1.0..=1.0.
+    pub fn push(&self, value: f32) {
+        let max_queued = (self.source_rate * MAX_QUEUED_SECONDS).ceil() as usize;
+        let mut shared = self.shared.lock().unwrap();
+        shared.queue.push_back(if value.is_finite() {
+            value.clamp(-1.0, 1.0)
+        } else {
+            0.0
+        });
+        while shared.queue.len() > max_queued {
+            shared.queue.pop_front();
+        }
+    }
+
+    pub fn set_volume(&self, volume: f32) {
+        self.shared.lock().unwrap().volume = volume;
+    }
+
+    pub fn set_mode(&self, mode: AudioMode) {
+        self.shared.lock().unwrap().mode = mode;
+    }
+
+    /// Frequencies in Hz that a signal of -1.0 and 1.0 map to in pitch mode.
+    pub fn set_pitch_range(&self, pitch_range: (f32, f32)) {
+        self.shared.lock().unwrap().pitch_range = pitch_range;
+    }
+}
+
+fn open_output(
+    shared: Arc<Mutex<Shared>>,
+    source_rate: f64,
+    errors: crossbeam_channel::Sender<String>,
+) -> Result<cpal::Stream, String> {
+    let device = cpal::default_host()
+        .default_output_device()
+        .ok_or_else(|| "no audio output device available".to_string())?;
+    let config = device
+        .default_output_config()
+        .map_err(|e| format!("failed to query audio output: {}", e))?;
+    let device_rate = config.sample_rate().0 as f64;
+    let output_channels = config.channels() as usize;
+
+    // linear-interpolating resampler from the stream rate to the device rate
+    let step = source_rate / device_rate;
+    let mut position = 0.0;
+    let mut previous = 0.0f32;
+    let mut current = 0.0f32;
+    let mut envelope = 0.0f32;
+    let mut pitch = 0.0f32;
+    let mut phase = 0.0f64;
+
+    let stream = device
+        .build_output_stream(
+            &config.into(),
+            move |data: &mut [f32], _: &cpal::OutputCallbackInfo| {
+                let mut shared = shared.lock().unwrap();
+                for frame in data.chunks_mut(output_channels) {
+                    position += step;
+                    while position >= 1.0 {
+                        position -= 1.0;
+                        previous = current;
+                        // hold the last value if the stream stalls
+                        if let Some(next) = shared.queue.pop_front() {
+                            current = next;
+                        }
+                    }
+                    let signal = previous + (current - previous) * position as f32;
+
+                    let out = match shared.mode {
+                        AudioMode::Signal => signal,
+                        AudioMode::Tone => {
+                            // smooth the rectified signal so the tone doesn't crackle
+                            envelope += (signal.abs() - envelope) * 0.001;
+                            phase = (phase + TONE_FREQUENCY / device_rate) % 1.0;
+                            envelope * (phase * TAU).sin() as f32
+                        }
+                        AudioMode::Pitch => {
+                            // smoothed like the envelope, so the pitch glides instead of stepping
+                            pitch += (signal - pitch) * 0.001;
+                            let (low, high) = shared.pitch_range;
+                            let frequency = low + (pitch + 1.0) / 2.0 * (high - low);
+                            phase = (phase + frequency as f64 / device_rate) % 1.0;
+                            0.5 * (phase * TAU).sin() as f32
+                        }
+                    } * shared.volume;
+
+                    for sample in frame.iter_mut() {
+                        *sample = out;
+                    }
+                }
+            },
+            move |e| {
+                let _ = errors.send(format!("Error: audio output: {}", e));
+            },
+            None,
+        )
+        .map_err(|e| format!("failed to open audio output: {}", e))?;
+    stream
+        .play()
+        .map_err(|e| format!("failed to start audio output: {}", e))?;
+    Ok(stream)
+}
+
+/// Play a short beep on the default output device, without blocking. Failures
+/// are only reported as warnings, a missing beep isn't worth interrupting anything for.
+pub fn beep(errors: crossbeam_channel::Sender<String>) {
+    thread::spawn(move || match open_beep(errors.clone()) {
+        Ok(_stream) => thread::sleep(BEEP_DURATION),
+        Err(e) => {
+            let _ = errors.send(format!("Warning: beep failed: {}", e));
+        }
+    });
+}
+
+fn open_beep(errors: crossbeam_channel::Sender<String>) -> Result<cpal::Stream, String> {
+    let device = cpal::default_host()
+        .default_output_device()
+        .ok_or_else(|| "no audio output device available".to_string())?;
+    let config = device
+        .default_output_config()
+        .map_err(|e| format!("failed to query audio output: {}", e))?;
+    let device_rate = config.sample_rate().0 as f64;
+    let output_channels = config.channels() as usize;
+    let mut phase = 0.0f64;
+
+    let stream = device
+        .build_output_stream(
+            &config.into(),
+            move |data: &mut [f32], _: &cpal::OutputCallbackInfo| {
+                for frame in data.chunks_mut(output_channels) {
+                    phase = (phase + BEEP_FREQUENCY / device_rate) % 1.0;
+                    let out = 0.3 * (phase * TAU).sin() as f32;
+                    for sample in frame.iter_mut() {
+                        *sample = out;
+                    }
+                }
+            },
+            move |e| {
+                let _ = errors.send(format!("Warning: beep failed: {}", e));
+            },
+            None,
+        )
+        .map_err(|e| format!("failed to open audio output: {}", e))?;
+    stream
+        .play()
+        .map_err(|e| format!("failed to start audio output: {}", e))?;
+    Ok(stream)
+}
@@ -0,0 +1,73 @@
+// Virtual channels computed from the stream's own channels. They are appended
+// after the stream's channels in every sample, so they plot, scale and filter
+// like real ones.
+
+#[derive(Clone, PartialEq)]
+pub enum DerivedChannel {
+    Difference(usize, usize), // First minus second, e.g. a bipolar derivation
+    Mean(Vec<usize>),         // Average of the listed channels
+}
+
+impl DerivedChannel {
+    /// Display name, built from the names of the source channels.
+    pub fn name(&self, names: &[String]) -> String {
+        let name = |ch: &usize| names.get(*ch).map_or("?", |n| n.as_str());
+        match self {
+            DerivedChannel::Difference(a, b) => format!("{}−{}", name(a), name(b)),
+            DerivedChannel::Mean(channels) => format!("Mean of {}", channels.len()),
+        }
+    }
+
+    /// The channels it is computed from.
+    pub fn sources(&self) -> Vec<usize> {
+        match self {
+            DerivedChannel::Difference(a, b) => vec![*a, *b],
+            DerivedChannel::Mean(channels) => channels.clone(),
+        }
+    }
+
+    /// The source channels with the weight each contributes; `compute` is their weighted sum.
+    pub fn weights(&self) -> Vec<(usize, f64)> {
+        match self {
+            DerivedChannel::Difference(a, b) => vec![(*a, 1.0), (*b, -1.0)],
+            DerivedChannel::Mean(channels) => {
+                let weight = 1.0 / channels.len() as f64;
+                channels.iter().map(|&ch| (ch, weight)).collect()
+            }
+        }
+    }
+
+    /// Whether every source channel exists in a stream of `channel_count` channels.
+    pub fn fits(&self, channel_count: usize) -> bool {
+        self.sources().iter().all(|&ch| ch < channel_count)
+    }
+
+    pub fn compute(&self, values: &[f32]) -> f32 {
+        let value = |ch: usize| values.get(ch).copied().unwrap_or(0.0);
+        match self {
+            DerivedChannel::Difference(a, b) => value(*a) - value(*b),
+            DerivedChannel::Mean(channels) if channels.is_empty() => 0.0,
+            DerivedChannel::Mean(channels) => {
+                channels.iter().map(|&ch| value(ch)).sum::<f32>() / channels.len() as f32
+            }
+        }
+    }
+}
+
+/// Subtract the mean of the `included` channels from every channel.
+pub fn common_average_reference(values: &mut [f32], included: &[bool]) {
+    let (sum, count) = values
+        .iter()
+        .zip(included)
+        .filter(|(value, included)| **included && value.is_finite())
+        .fold((0.0, 0), |(sum, count), (value, _)| {
+            (sum + value, count + 1)
+        });
+    if count == 0 {
+        return;
+    }
+    let mean = sum / count as f32;
+    for value in values.iter_mut() {
+        *value -= mean;
+    }
+}
@@ -0,0 +1,99 @@
+// Decimated, baseline-corrected points of each channel, kept between plot rebuilds
+// so only the samples that arrived since the last rebuild need transforming.
+use crate::filter::Biquad;
+use crate::{DecimationMethod, decimate};
+use std::collections::VecDeque;
+
+/// What the cached points depend on; any change rebuilds them from the buffer.
+#[derive(Clone, Copy, PartialEq)]
+pub struct CacheKey {
+    pub baseline: f64,
+    pub reference_channel: Option<usize>, // Channels of equal baselines can't tell apart
+    pub reference_baseline: Option<f64>,  // Set if a reference channel is subtracted
+    pub factor: usize,
+    pub method: DecimationMethod,
+    pub anti_alias: Option<(f64, f64)>, // Sample rate and cutoff of a low-pass applied before decimating
+}
+
+#[derive(Default)]
+pub struct ChannelCache {
+    key: Option<CacheKey>,
+    points: VecDeque<(f64, f64)>, // Decimated (timestamp, deflection) of every complete group
+    covered_until: Option<f64>,   // Timestamp of the last sample in `points`
+    anti_alias: Option<Biquad>,   // Filter state after the last sample in `points`
+}
+
+impl ChannelCache {
+    /// The decimated deflections of the whole buffer. Only complete groups of
+    /// new samples are added to the cache; the newest, incomplete group is
+    /// decimated on every call until it fills up.
+    pub fn points(
+        &mut self,
+        key: CacheKey,
+        values: &VecDeque<f32>,
+        timestamps: &VecDeque<f64>,
+        reference: Option<&VecDeque<f32>>,
+    ) -> Vec<(f64, f64)> {
+        let len = values.len().min(timestamps.len());
+        // a cleared or reset buffer starts over with older timestamps
+        let went_back = timestamps
+            .back()
+            .zip(self.covered_until)
+            .is_none_or(|(&newest, covered)| newest < covered);
+        if self.key != Some(key) || went_back {
+            self.key = Some(key);
+            self.points.clear();
+            self.covered_until = None;
+            self.anti_alias = key
+                .anti_alias
+                .map(|(sample_rate, cutoff)| Biquad::lowpass(sample_rate, cutoff));
+        }
+        if let Some(&oldest) = timestamps.front() {
+            while self.points.front().is_some_and(|&(t, _)| t < oldest) {
+                self.points.pop_front();
+            }
+        }
+
+        let factor = key.factor.max(1);
+        let deflections = |from: usize, to: usize| -> Vec<(f64, f64)> {
+            (from..to)
+                .map(|i| {
+                    let v = values[i] as f64 - key.baseline;
+                    let v = match (reference, key.reference_baseline) {
+                        (Some(reference), Some(ref_baseline)) => {
+                            v - (reference.get(i).copied().unwrap_or(0.0) as f64 - ref_baseline)
+                        }
+                        _ => v,
+                    };
+                    (timestamps[i], v)
+                })
+                .collect()
+        };
+        let start = match self.covered_until {
+            Some(covered) => timestamps.partition_point(|&t| t <= covered).min(len),
+            None => 0,
+        };
+        let filtered = |filter: Option<&mut Biquad>, mut points: Vec<(f64, f64)>| {
+            if let Some(filter) = filter {
+                // a non-finite value would stay in the filter state for good
+                for (_, v) in points.iter_mut().filter(|(_, v)| v.is_finite()) {
+                    *v = filter.process_f64(*v);
+                }
+            }
+            points
+        };
+        let complete = start + (len - start) / factor * factor;
+        if complete > start {
+            let group = filtered(self.anti_alias.as_mut(), deflections(start, complete));
+            self.points.extend(decimate(&group, factor, key.method));
+            self.covered_until = Some(timestamps[complete - 1]);
+        }
+
+        // the incomplete group is filtered on a copy, the state only moves on with complete ones
+        let mut tail_filter = self.anti_alias.clone();
+        let tail = filtered(tail_filter.as_mut(), deflections(complete, len));
+        let mut points: Vec<(f64, f64)> = self.points.iter().copied().collect();
+        points.extend(decimate(&tail, factor, key.method));
+        points
+    }
+}
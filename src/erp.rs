@@ -0,0 +1,137 @@
+// Event-related potentials: epochs around marker events, averaged over trials.
+use std::collections::VecDeque;
+
+pub const DEFAULT_PRE_SECONDS: f64 = 0.1;
+pub const DEFAULT_POST_SECONDS: f64 = 0.5;
+
+/// Running average of the epochs around every marker, per channel. Each epoch is
+/// baseline-corrected to the mean of its pre-event part before it is added.
+/// Non-finite samples are left out, so each point averages the trials that had one.
+pub struct ErpAverager {
+    pre_seconds: f64,
+    post_seconds: f64,
+    sample_rate: f64,
+    pending: VecDeque<f64>, // Marker timestamps whose post-event data hasn't arrived yet
+    sums: Vec<Vec<f64>>,
+    counts: Vec<Vec<usize>>, // Trials with a finite value, per channel and sample
+    pub trials: usize,
+    pub skipped: usize, // Events whose pre-event data was no longer buffered
+}
+
+impl ErpAverager {
+    pub fn new(
+        channel_count: usize,
+        sample_rate: f64,
+        pre_seconds: f64,
+        post_seconds: f64,
+    ) -> Self {
+        let mut averager = Self {
+            pre_seconds,
+            post_seconds,
+            sample_rate,
+            pending: VecDeque::new(),
+            sums: Vec::new(),
+            counts: Vec::new(),
+            trials: 0,
+            skipped: 0,
+        };
+        averager.sums = vec![vec![0.0; averager.epoch_length()]; channel_count];
+        averager.counts = vec![vec![0; averager.epoch_length()]; channel_count];
+        averager
+    }
+
+    fn pre_length(&self) -> usize {
+        (self.pre_seconds * self.sample_rate).round() as usize
+    }
+
+    fn epoch_length(&self) -> usize {
+        ((self.pre_seconds + self.post_seconds) * self.sample_rate).round() as usize
+    }
+
+    /// Make room for one more channel, which joins the average from the next epoch on.
+    pub fn add_channel(&mut self) {
+        self.sums.push(vec![0.0; self.epoch_length()]);
+        self.counts.push(vec![0; self.epoch_length()]);
+    }
+
+    pub fn add_event(&mut self, timestamp: f64) {
+        self.pending.push_back(timestamp);
+    }
+
+    /// Cut out every pending epoch the buffer now fully contains. Epochs whose
+    /// post-event data is still missing stay pending for a later call.
+    pub fn update(&mut self, timestamps: &VecDeque<f64>, data: &[VecDeque<f32>]) {
+        let epoch_length = self.epoch_length();
+        if epoch_length == 0 {
+            self.pending.clear();
+            return;
+        }
+        let pre_length = self.pre_length();
+        let Some(&latest) = timestamps.back() else {
+            return;
+        };
+        while let Some(&event) = self.pending.front() {
+            if event + self.post_seconds > latest {
+                break;
+            }
+            self.pending.pop_front();
+
+            let start = timestamps.partition_point(|&t| t < event - self.pre_seconds);
+            let sample_interval = 1.0 / self.sample_rate;
+            let pruned = start == 0 && timestamps[0] > event - self.pre_seconds + sample_interval;
+            if pruned || start + epoch_length > timestamps.len() {
+                self.skipped += 1;
+                continue;
+            }
+            for ((sum, count), channel_data) in self
+                .sums
+                .iter_mut()
+                .zip(self.counts.iter_mut())
+                .zip(data.iter())
+            {
+                if channel_data.len() < start + epoch_length {
+                    continue;
+                }
+                let epoch: Vec<f64> = channel_data
+                    .range(start..start + epoch_length)
+                    .map(|&v| v as f64)
+                    .collect();
+                let pre: Vec<f64> = epoch[..pre_length]
+                    .iter()
+                    .copied()
+                    .filter(|v| v.is_finite())
+                    .collect();
+                let baseline = if pre.is_empty() {
+                    0.0
+                } else {
+                    pre.iter().sum::<f64>() / pre.len() as f64
+                };
+                for ((s, c), v) in sum.iter_mut().zip(count.iter_mut()).zip(epoch) {
+                    if v.is_finite() {
+                        *s += v - baseline;
+                        *c += 1;
+                    }
+                }
+            }
+            self.trials += 1;
+        }
+    }
+
+    /// The average of a channel as `[ms relative to the event, value]` points,
+    /// empty before the first trial. Samples no trial had a finite value for are left out.
+    pub fn average(&self, channel: usize) -> Vec<[f64; 2]> {
+        let (Some(sum), Some(count)) = (self.sums.get(channel), self.counts.get(channel)) else {
+            return Vec::new();
+        };
+        let pre_length = self.pre_length() as f64;
+        sum.iter()
+            .zip(count)
+            .enumerate()
+            .filter(|(_, (_, c))| **c > 0)
+            .map(|(i, (s, &c))| {
+                let ms = (i as f64 - pre_length) / self.sample_rate * 1000.0;
+                [ms, s / c as f64]
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,86 @@
+// History of status messages and connection events, so what flashed by in the
+// status bar can be looked at after the fact.
+use std::collections::VecDeque;
+
+const MAX_ENTRIES: usize = 1000; // The oldest entries are dropped beyond this
+
+#[derive(Clone, Copy, PartialEq, PartialOrd, Default)]
+pub enum Severity {
+    #[default]
+    Info,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    pub const ALL: [Severity; 3] = [Severity::Info, Severity::Warning, Severity::Error];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Severity::Info => "Info",
+            Severity::Warning => "Warning",
+            Severity::Error => "Error",
+        }
+    }
+
+    /// The severity of a status message, from its "Error:" or "Warning:" prefix.
+    pub fn of(message: &str) -> Self {
+        if message.starts_with("Error") {
+            Severity::Error
+        } else if message.starts_with("Warning") {
+            Severity::Warning
+        } else {
+            Severity::Info
+        }
+    }
+}
+
+pub struct LogEntry {
+    pub time: f64, // Unix time in seconds
+    pub severity: Severity,
+    pub message: String,
+    pub repeats: usize, // Identical messages that followed, folded into this entry
+}
+
+#[derive(Default)]
+pub struct EventLog {
+    entries: VecDeque<LogEntry>,
+}
+
+impl EventLog {
+    /// Add a message; one that repeats the newest entry only counts up its repeats.
+    pub fn push(&mut self, severity: Severity, message: String) {
+        let time = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0.0, |t| t.as_secs_f64());
+        if let Some(last) = self
+            .entries
+            .back_mut()
+            .filter(|last| last.severity == severity && last.message == message)
+        {
+            last.repeats += 1;
+            last.time = time;
+            return;
+        }
+        self.entries.push_back(LogEntry {
+            time,
+            severity,
+            message,
+            repeats: 0,
+        });
+        if self.entries.len() > MAX_ENTRIES {
+            self.entries.pop_front();
+        }
+    }
+
+    /// Entries of at least `min_severity`, oldest first.
+    pub fn entries(&self, min_severity: Severity) -> impl Iterator<Item = &LogEntry> {
+        self.entries
+            .iter()
+            .filter(move |entry| entry.severity >= min_severity)
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
@@ -0,0 +1,182 @@
+// Writing buffered data to disk on a background thread.
+use crossbeam_channel::Receiver;
+use lsl_viewer::recording::csv_field;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+
+/// How a group of samples is reduced to one row when exporting downsampled data.
+#[derive(Clone, Copy, PartialEq, Default)]
+pub enum Aggregation {
+    #[default]
+    First,
+    Last,
+    Mean,
+    Min,
+    Max,
+}
+
+impl Aggregation {
+    pub const ALL: [Aggregation; 5] = [
+        Aggregation::First,
+        Aggregation::Last,
+        Aggregation::Mean,
+        Aggregation::Min,
+        Aggregation::Max,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Aggregation::First => "First",
+            Aggregation::Last => "Last",
+            Aggregation::Mean => "Mean",
+            Aggregation::Min => "Min",
+            Aggregation::Max => "Max",
+        }
+    }
+
+    fn reduce(self, values: &[f32]) -> f32 {
+        match self {
+            Aggregation::First => values[0],
+            Aggregation::Last => values[values.len() - 1],
+            Aggregation::Mean => values.iter().sum::<f32>() / values.len() as f32,
+            Aggregation::Min => values.iter().copied().fold(f32::INFINITY, f32::min),
+            Aggregation::Max => values.iter().copied().fold(f32::NEG_INFINITY, f32::max),
+        }
+    }
+}
+
+/// Reduce every `factor` consecutive samples to one, stamped with the time of
+/// the first sample in the group.
+fn downsample(
+    timestamps: &[f64],
+    values: &[f32],
+    factor: usize,
+    aggregation: Aggregation,
+) -> (Vec<f64>, Vec<f32>) {
+    let factor = factor.max(1);
+    timestamps
+        .chunks(factor)
+        .zip(values.chunks(factor))
+        .map(|(t, v)| (t[0], aggregation.reduce(v)))
+        .unzip()
+}
+
+pub enum ExportProgress {
+    /// Number of files written so far.
+    Wrote(usize),
+    /// All files written, or the first error.
+    Finished(Result<usize, String>),
+}
+
+/// A running export. Poll it once per frame.
+pub struct ExportJob {
+    pub total: usize,
+    pub written: usize,
+    progress: Receiver<ExportProgress>,
+}
+
+impl ExportJob {
+    /// Drain progress messages, returning the final result once the export is done.
+    pub fn poll(&mut self) -> Option<Result<usize, String>> {
+        for progress in self.progress.try_iter() {
+            match progress {
+                ExportProgress::Wrote(n) => self.written = n,
+                ExportProgress::Finished(result) => return Some(result),
+            }
+        }
+        None
+    }
+}
+
+/// Write one `timestamp,value` CSV per channel into `dir`, named after the channel labels.
+/// With a `downsample_factor` above one, groups of samples are reduced with `aggregation`.
+/// Annotations, if any, go to `annotations.csv` next to them.
+pub fn spawn_per_channel_export(
+    dir: PathBuf,
+    timestamps: Vec<f64>,
+    channels: Vec<(String, Vec<f32>)>,
+    annotations: Vec<(f64, String)>,
+    downsample_factor: usize,
+    aggregation: Aggregation,
+) -> ExportJob {
+    let (tx, rx) = crossbeam_channel::unbounded();
+    let total = channels.len();
+    thread::spawn(move || {
+        let mut used_names = HashSet::new();
+        if !annotations.is_empty() {
+            // a channel with this label gets a suffix instead
+            used_names.insert("annotations".to_string());
+        }
+        let mut result = Ok(total);
+        for (i, (label, values)) in channels.iter().enumerate() {
+            let mut name = sanitize_filename(label);
+            // labels can collide after sanitizing, or be duplicated in the metadata
+            if !used_names.insert(name.clone()) {
+                name = format!("{}_{}", name, i);
+                used_names.insert(name.clone());
+            }
+            let path = dir.join(format!("{}.csv", name));
+            let (timestamps, values) =
+                downsample(&timestamps, values, downsample_factor, aggregation);
+            if let Err(e) = write_channel_csv(&path, &timestamps, &values) {
+                result = Err(format!("failed to write {}: {}", path.display(), e));
+                break;
+            }
+            let _ = tx.send(ExportProgress::Wrote(i + 1));
+        }
+        if result.is_ok() && !annotations.is_empty() {
+            let path = dir.join("annotations.csv");
+            if let Err(e) = write_annotations_csv(&path, &annotations) {
+                result = Err(format!("failed to write {}: {}", path.display(), e));
+            }
+        }
+        let _ = tx.send(ExportProgress::Finished(result));
+    });
+    ExportJob {
+        total,
+        written: 0,
+        progress: rx,
+    }
+}
+
+fn write_channel_csv(path: &Path, timestamps: &[f64], values: &[f32]) -> std::io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writeln!(writer, "timestamp,value")?;
+    for (timestamp, value) in timestamps.iter().zip(values) {
+        writeln!(writer, "{},{}", timestamp, value)?;
+    }
+    writer.flush()
+}
+
+fn write_annotations_csv(path: &Path, annotations: &[(f64, String)]) -> std::io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writeln!(writer, "timestamp,text")?;
+    for (timestamp, text) in annotations {
+        writeln!(writer, "{},{}", timestamp, csv_field(text))?;
+    }
+    writer.flush()
+}
+
+/// Turn a channel label into something safe to use as a file name on any platform.
+pub fn sanitize_filename(label: &str) -> String {
+    let name: String = label
+        .trim()
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '_' || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let name = name.trim_matches('.');
+    if name.is_empty() {
+        "channel".to_string()
+    } else {
+        name.to_string()
+    }
+}
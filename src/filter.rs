@@ -0,0 +1,349 @@
+// IIR filters applied to incoming samples before they are buffered.
+use lsl_viewer::DataSample;
+use lsl_viewer::processing::SampleProcessor;
+use std::f64::consts::{FRAC_1_SQRT_2, PI};
+
+/// Second-order IIR section (RBJ cookbook coefficients, transposed direct form II).
+#[derive(Clone)]
+pub struct Biquad {
+    b0: f64,
+    b1: f64,
+    b2: f64,
+    a1: f64,
+    a2: f64,
+    z1: f64,
+    z2: f64,
+}
+
+impl Biquad {
+    fn from_coefficients(b: [f64; 3], a: [f64; 3]) -> Self {
+        Self {
+            b0: b[0] / a[0],
+            b1: b[1] / a[0],
+            b2: b[2] / a[0],
+            a1: a[1] / a[0],
+            a2: a[2] / a[0],
+            z1: 0.0,
+            z2: 0.0,
+        }
+    }
+
+    /// Butterworth low-pass at `cutoff` Hz.
+    pub fn lowpass(sample_rate: f64, cutoff: f64) -> Self {
+        let w0 = 2.0 * PI * cutoff / sample_rate;
+        let alpha = w0.sin() / (2.0 * FRAC_1_SQRT_2);
+        let cos = w0.cos();
+        Self::from_coefficients(
+            [(1.0 - cos) / 2.0, 1.0 - cos, (1.0 - cos) / 2.0],
+            [1.0 + alpha, -2.0 * cos, 1.0 - alpha],
+        )
+    }
+
+    /// Butterworth high-pass at `cutoff` Hz.
+    pub fn highpass(sample_rate: f64, cutoff: f64) -> Self {
+        let w0 = 2.0 * PI * cutoff / sample_rate;
+        let alpha = w0.sin() / (2.0 * FRAC_1_SQRT_2);
+        let cos = w0.cos();
+        Self::from_coefficients(
+            [(1.0 + cos) / 2.0, -(1.0 + cos), (1.0 + cos) / 2.0],
+            [1.0 + alpha, -2.0 * cos, 1.0 - alpha],
+        )
+    }
+
+    /// Narrow band-stop around `center` Hz; `q` sets how narrow.
+    pub fn notch(sample_rate: f64, center: f64, q: f64) -> Self {
+        let w0 = 2.0 * PI * center / sample_rate;
+        let alpha = w0.sin() / (2.0 * q);
+        let cos = w0.cos();
+        Self::from_coefficients(
+            [1.0, -2.0 * cos, 1.0],
+            [1.0 + alpha, -2.0 * cos, 1.0 - alpha],
+        )
+    }
+
+    pub fn process(&mut self, x: f32) -> f32 {
+        self.process_f64(x as f64) as f32
+    }
+
+    pub fn process_f64(&mut self, x: f64) -> f64 {
+        // a single NaN would otherwise stick in the state forever
+        if !x.is_finite() {
+            return x;
+        }
+        let y = self.b0 * x + self.z1;
+        self.z1 = self.b1 * x - self.a1 * y + self.z2;
+        self.z2 = self.b2 * x - self.a2 * y;
+        y
+    }
+}
+
+/// One-pole high-pass that removes the DC offset and slow drift, like the AC
+/// coupling of a scope. Starts from the first sample so there is no step at the start.
+#[derive(Clone)]
+pub struct DcBlocker {
+    coefficient: f64,
+    previous_input: Option<f64>,
+    previous_output: f64,
+}
+
+impl DcBlocker {
+    pub fn new(sample_rate: f64, cutoff: f64) -> Self {
+        let rc = 1.0 / (2.0 * PI * cutoff);
+        let dt = 1.0 / sample_rate;
+        Self {
+            coefficient: rc / (rc + dt),
+            previous_input: None,
+            previous_output: 0.0,
+        }
+    }
+
+    pub fn process(&mut self, x: f32) -> f32 {
+        if !x.is_finite() {
+            return x;
+        }
+        let x = x as f64;
+        let y = match self.previous_input {
+            Some(previous) => self.coefficient * (self.previous_output + x - previous),
+            None => 0.0,
+        };
+        self.previous_input = Some(x);
+        self.previous_output = y;
+        y as f32
+    }
+}
+
+/// AC coupling settings as chosen in the UI.
+#[derive(Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct AcCouplingSettings {
+    pub enabled: bool,
+    pub cutoff: f64,
+}
+
+impl Default for AcCouplingSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            cutoff: 0.1,
+        }
+    }
+}
+
+/// Band-pass settings as chosen in the UI.
+#[derive(Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct BandpassSettings {
+    pub enabled: bool,
+    pub low_cut: f64,
+    pub high_cut: f64,
+}
+
+impl Default for BandpassSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            low_cut: 1.0,
+            high_cut: 40.0,
+        }
+    }
+}
+
+impl BandpassSettings {
+    /// Why the band can't be used at this sample rate, if it can't.
+    pub fn problem(&self, sample_rate: f64) -> Option<&'static str> {
+        if sample_rate <= 0.0 {
+            Some("Filtering needs a regular sample rate")
+        } else if self.low_cut >= self.high_cut {
+            Some("Low cut must be below high cut")
+        } else if self.high_cut >= sample_rate / 2.0 {
+            Some("High cut must be below the Nyquist frequency")
+        } else {
+            None
+        }
+    }
+}
+
+const NOTCH_Q: f64 = 30.0; // About 1.7 Hz wide at 50 Hz
+
+/// Line-noise notch choice.
+#[derive(Clone, Copy, PartialEq, Default, serde::Serialize, serde::Deserialize)]
+pub enum NotchFrequency {
+    #[default]
+    Off,
+    Hz50,
+    Hz60,
+}
+
+impl NotchFrequency {
+    pub const ALL: [NotchFrequency; 3] = [
+        NotchFrequency::Off,
+        NotchFrequency::Hz50,
+        NotchFrequency::Hz60,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            NotchFrequency::Off => "Off",
+            NotchFrequency::Hz50 => "50 Hz",
+            NotchFrequency::Hz60 => "60 Hz",
+        }
+    }
+
+    fn hz(self) -> Option<f64> {
+        match self {
+            NotchFrequency::Off => None,
+            NotchFrequency::Hz50 => Some(50.0),
+            NotchFrequency::Hz60 => Some(60.0),
+        }
+    }
+
+    /// Why the notch can't be used at this sample rate, if it can't.
+    pub fn problem(self, sample_rate: f64) -> Option<&'static str> {
+        match self.hz() {
+            Some(_) if sample_rate <= 0.0 => Some("Filtering needs a regular sample rate"),
+            Some(hz) if hz >= sample_rate / 2.0 => Some("Notch is above the Nyquist frequency"),
+            _ => None,
+        }
+    }
+}
+
+/// Everything that makes up the filter chain.
+#[derive(Clone, Copy, PartialEq, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct FilterSettings {
+    pub bandpass: BandpassSettings,
+    pub notch: NotchFrequency,
+    pub ac_coupling: AcCouplingSettings,
+}
+
+/// The stages that run on one channel.
+#[derive(Clone, Copy, PartialEq, Default)]
+pub struct AppliedFilters {
+    pub bandpass: bool,
+    pub notch: bool,
+    pub ac_coupling: bool,
+}
+
+impl AppliedFilters {
+    /// Short labels of the stages that run, e.g. "BP N", for the channel list.
+    pub fn badges(self) -> String {
+        [
+            (self.bandpass, "BP"),
+            (self.notch, "N"),
+            (self.ac_coupling, "AC"),
+        ]
+        .iter()
+        .filter(|(applied, _)| *applied)
+        .map(|(_, badge)| *badge)
+        .collect::<Vec<_>>()
+        .join(" ")
+    }
+}
+
+/// A chain of biquads per channel, with independent state for every channel.
+#[derive(Default)]
+pub struct ChannelFilters {
+    channels: Vec<Vec<Biquad>>,
+    dc_blockers: Vec<Option<DcBlocker>>, // None unless AC coupling is on for the channel
+    applied: Vec<AppliedFilters>,
+}
+
+impl ChannelFilters {
+    /// Filters for `channel_count` channels; those marked in `bypassed` are left alone.
+    pub fn new(
+        channel_count: usize,
+        sample_rate: f64,
+        settings: FilterSettings,
+        bypassed: &[bool],
+    ) -> Self {
+        let mut chain = Vec::new();
+        let mut applied = AppliedFilters::default();
+        let bandpass = settings.bandpass;
+        if bandpass.enabled && bandpass.problem(sample_rate).is_none() {
+            chain.push(Biquad::highpass(sample_rate, bandpass.low_cut));
+            chain.push(Biquad::lowpass(sample_rate, bandpass.high_cut));
+            applied.bandpass = true;
+        }
+        if let (Some(hz), None) = (settings.notch.hz(), settings.notch.problem(sample_rate)) {
+            chain.push(Biquad::notch(sample_rate, hz, NOTCH_Q));
+            applied.notch = true;
+        }
+        let ac_coupling = settings.ac_coupling;
+        let dc_blocker = (ac_coupling.enabled && sample_rate > 0.0 && ac_coupling.cutoff > 0.0)
+            .then(|| DcBlocker::new(sample_rate, ac_coupling.cutoff));
+        applied.ac_coupling = dc_blocker.is_some();
+
+        let mut filters = Self::default();
+        for ch in 0..channel_count {
+            if bypassed.get(ch).copied().unwrap_or(false) {
+                filters.channels.push(Vec::new());
+                filters.dc_blockers.push(None);
+                filters.applied.push(AppliedFilters::default());
+            } else {
+                filters.channels.push(chain.clone());
+                filters.dc_blockers.push(dc_blocker.clone());
+                filters.applied.push(applied);
+            }
+        }
+        filters
+    }
+
+    /// Add a channel carrying the weighted sum of the `sources` channels, e.g. a
+    /// derived one. The filters are linear, so starting from the same weighted sum
+    /// of the sources' states it goes on as if it had been filtered all along.
+    /// Sources filtered differently, e.g. bypassed ones, leave it a fresh state.
+    pub fn push_combined(
+        &mut self,
+        sources: &[(usize, f64)],
+        sample_rate: f64,
+        settings: FilterSettings,
+    ) {
+        let fresh = Self::new(1, sample_rate, settings, &[false]);
+        let mut chain = fresh.channels[0].clone();
+        let mut dc_blocker = fresh.dc_blockers[0].clone();
+        let applied = fresh.applied[0];
+        let continues = sources
+            .iter()
+            .all(|&(ch, _)| self.applied.get(ch) == Some(&applied));
+        if continues {
+            let sum = |state: &dyn Fn(usize) -> f64| -> f64 {
+                sources.iter().map(|&(ch, weight)| weight * state(ch)).sum()
+            };
+            for (stage, biquad) in chain.iter_mut().enumerate() {
+                biquad.z1 = sum(&|ch| self.channels[ch][stage].z1);
+                biquad.z2 = sum(&|ch| self.channels[ch][stage].z2);
+            }
+            if let Some(blocker) = dc_blocker.as_mut() {
+                let source = |ch: usize| self.dc_blockers[ch].as_ref();
+                blocker.previous_input = sources
+                    .iter()
+                    .all(|&(ch, _)| source(ch).is_some_and(|b| b.previous_input.is_some()))
+                    .then(|| sum(&|ch| source(ch).and_then(|b| b.previous_input).unwrap_or(0.0)));
+                blocker.previous_output = sum(&|ch| source(ch).map_or(0.0, |b| b.previous_output));
+            }
+        }
+        self.channels.push(chain);
+        self.dc_blockers.push(dc_blocker);
+        self.applied.push(applied);
+    }
+
+    /// The stages that run on channel `ch`.
+    pub fn applied(&self, ch: usize) -> AppliedFilters {
+        self.applied.get(ch).copied().unwrap_or_default()
+    }
+}
+
+impl SampleProcessor for ChannelFilters {
+    fn process(&mut self, sample: &mut DataSample) {
+        for (value, chain) in sample.values.iter_mut().zip(self.channels.iter_mut()) {
+            for biquad in chain.iter_mut() {
+                *value = biquad.process(*value);
+            }
+        }
+        for (value, blocker) in sample.values.iter_mut().zip(self.dc_blockers.iter_mut()) {
+            if let Some(blocker) = blocker {
+                *value = blocker.process(*value);
+            }
+        }
+    }
+}
@@ -0,0 +1,146 @@
+// Recording without a window: `--headless --connect NAME --out FILE [--duration SECONDS]`.
+// The LSL thread and CSV recorder are the same ones the viewer drives.
+use crossbeam_channel::RecvTimeoutError;
+use lsl_viewer::{
+    COMMAND_POLL_INTERVAL, ConnectOptions, LslCommand, LslResponse, lsl_handler_thread,
+};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub struct HeadlessArgs {
+    pub stream: String,
+    pub out: PathBuf,
+    pub duration: Option<Duration>, // Record until Ctrl-C if not given
+}
+
+/// The value of `--name VALUE` or `--name=VALUE`, if given.
+pub fn option_value(args: &[String], name: &str) -> Option<String> {
+    let flag = format!("--{}", name);
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if *arg == flag {
+            return args.next().cloned();
+        }
+        if let Some(value) = arg.strip_prefix(&flag).and_then(|a| a.strip_prefix('=')) {
+            return Some(value.to_string());
+        }
+    }
+    None
+}
+
+/// The headless options, or `None` if `--headless` wasn't given.
+pub fn parse_args(args: &[String]) -> Option<Result<HeadlessArgs, String>> {
+    if !args.iter().any(|a| a == "--headless") {
+        return None;
+    }
+    let parsed = (|| {
+        let stream = option_value(args, "connect").ok_or("--headless needs --connect NAME")?;
+        let out = PathBuf::from(option_value(args, "out").ok_or("--headless needs --out FILE")?);
+        // the recorder only writes CSV, whatever the file is called
+        let is_csv = out
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("csv"));
+        if !is_csv {
+            return Err(format!(
+                "--out {} must be a .csv file, headless recording only writes CSV",
+                out.display()
+            ));
+        }
+        let duration = match option_value(args, "duration") {
+            Some(seconds) => match seconds.parse::<f64>() {
+                Ok(seconds) if seconds > 0.0 && seconds.is_finite() => {
+                    Some(Duration::from_secs_f64(seconds))
+                }
+                _ => return Err(format!("Invalid --duration '{}'", seconds)),
+            },
+            None => None,
+        };
+        Ok(HeadlessArgs {
+            stream,
+            out,
+            duration,
+        })
+    })();
+    Some(parsed)
+}
+
+/// Connect, record until the duration has passed or Ctrl-C is pressed, and
+/// return the number of samples written. Progress goes to stderr.
+pub fn run(args: HeadlessArgs) -> Result<usize, String> {
+    let interrupted = Arc::new(AtomicBool::new(false));
+    let flag = interrupted.clone();
+    ctrlc::set_handler(move || flag.store(true, Ordering::SeqCst))
+        .map_err(|e| format!("Failed to install the Ctrl-C handler: {}", e))?;
+
+    let (cmd_tx, cmd_rx) = crossbeam_channel::unbounded::<LslCommand>();
+    let (resp_tx, resp_rx) = crossbeam_channel::unbounded::<LslResponse>();
+    let handler = thread::spawn(move || lsl_handler_thread(cmd_rx, resp_tx));
+
+    eprintln!("Resolving '{}'...", args.stream);
+    let _ = cmd_tx.send(LslCommand::ConnectByName(
+        args.stream.clone(),
+        ConnectOptions::default(),
+    ));
+
+    let mut recording_since: Option<Instant> = None;
+    let mut stopping = false;
+    let result = loop {
+        let due = recording_since
+            .zip(args.duration)
+            .is_some_and(|(since, duration)| since.elapsed() >= duration);
+        if !stopping && (due || interrupted.load(Ordering::SeqCst)) {
+            if recording_since.is_none() {
+                break Err("Interrupted before recording started".to_string());
+            }
+            stopping = true;
+            let _ = cmd_tx.send(LslCommand::StopRecording);
+        }
+
+        match resp_rx.recv_timeout(COMMAND_POLL_INTERVAL) {
+            Ok(LslResponse::Connected { channel_names, .. }) => {
+                eprintln!("Connected, {} channels", channel_names.len());
+                if recording_since.is_none() {
+                    let _ = cmd_tx.send(LslCommand::StartRecording(args.out.clone()));
+                }
+            }
+            Ok(LslResponse::RecordingStarted(path)) => {
+                recording_since = Some(Instant::now());
+                eprintln!("Recording to {}", path.display());
+            }
+            Ok(LslResponse::RecordingProgress(samples_written)) => {
+                eprintln!("{} samples written", samples_written);
+            }
+            Ok(LslResponse::RecordingStopped(samples_written)) => {
+                if stopping {
+                    break Ok(samples_written);
+                }
+                // the connection was lost; recording can't resume into the same file
+                break Err(format!(
+                    "Recording stopped after {} samples",
+                    samples_written
+                ));
+            }
+            Ok(LslResponse::Reconnecting { name, attempt }) => {
+                eprintln!("Lost '{}', reconnecting (attempt {})", name, attempt);
+            }
+            Ok(LslResponse::Error(message)) => {
+                if recording_since.is_none() || stopping {
+                    break Err(message);
+                }
+                eprintln!("Error: {}", message);
+            }
+            Ok(_) => {}
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => {
+                break Err("The LSL thread stopped unexpectedly".to_string());
+            }
+        }
+    };
+
+    drop(cmd_tx);
+    let _ = handler.join();
+    result
+}
@@ -0,0 +1,1374 @@
+// The LSL acquisition engine behind the viewer: a thread that resolves streams,
+// pulls their samples and records them, driven by `LslCommand`s and reporting
+// through `LslResponse`s. It has no UI of its own, so other front-ends and the
+// headless recorder can use it as is.
+pub mod processing;
+pub mod recording;
+
+use crossbeam_channel::{Receiver, RecvTimeoutError, Sender, TryRecvError};
+use lsl::{Pullable, StreamInfo, StreamInlet};
+use recording::Recorder;
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const BUFFER_SIZE: i32 = 360; // Inlet buffer of the marker stream, in seconds
+const DEFAULT_INLET_BUFFER_SECONDS: i32 = 360; // Data the inlet holds before LSL drops the oldest
+const DEFAULT_INFO_TIMEOUT_SECONDS: f64 = 5.0; // Timeout for a single metadata fetch
+const DEFAULT_INFO_RETRIES: u32 = 2; // Extra metadata fetch attempts before falling back
+const DEFAULT_CONNECT_TIMEOUT_SECONDS: f64 = 30.0; // Longest a connect may take, metadata retries included
+const RE_RESOLVE_TIMEOUT_SECONDS: f64 = 1.0; // How long to look for a listed stream before connecting
+const CONNECT_BY_NAME_TIMEOUT_SECONDS: f64 = 5.0; // How long --connect waits for its stream to appear
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(2); // Time between attempts to find a lost stream
+const TIME_CORRECTION_INTERVAL: Duration = Duration::from_secs(5); // How often the clock offset is queried
+const TIME_CORRECTION_TIMEOUT_SECONDS: f64 = 0.001; // liblsl measures in the background, a query only picks up the result
+const TIME_CORRECTION_RETRY: Duration = Duration::from_millis(200); // Wait before asking again while the offset isn't known yet
+const STREAM_WATCH_INTERVAL: Duration = Duration::from_secs(1); // How often the live stream list is checked
+const STREAM_FORGET_SECONDS: f64 = 5.0; // A stream not heard from for this long leaves the live list
+const PULL_STATS_INTERVAL: Duration = Duration::from_secs(1); // How often chunk statistics are reported
+pub const COMMAND_POLL_INTERVAL: Duration = Duration::from_millis(10); // Longest a command waits while idle
+const DEFAULT_IDLE_WAIT_MS: u64 = 10; // Wait between pulls once the inlet has run dry
+pub const BLOCKING_PULL_MAX_RATE: f64 = 50.0; // Hz; automatic pulling takes slower streams one sample at a time
+pub const BACKLOG_FLUSH_SECONDS: f64 = 2.0; // Inlet backlog beyond which it is dropped to get back to live data
+
+#[derive(Clone)]
+pub struct StreamData {
+    pub name: String,
+    pub stream_type: String,
+    pub source_id: String,
+    pub hostname: String,
+    pub channel_count: usize,
+    pub sample_rate: f64,
+    pub channel_format: String, // As declared, e.g. "float32" or "int16"
+}
+
+impl StreamData {
+    /// Identity used to recognize a stream across refreshes.
+    pub fn key(&self) -> &str {
+        if self.source_id.is_empty() {
+            &self.name
+        } else {
+            &self.source_id
+        }
+    }
+
+    /// Formats whose values can't all be held exactly by the f32 display buffers.
+    pub fn loses_precision(&self) -> bool {
+        matches!(self.channel_format.as_str(), "double64" | "int32" | "int64")
+    }
+
+    /// The nominal rate for display; irregular streams have none.
+    pub fn rate_label(&self) -> String {
+        if self.sample_rate > 0.0 {
+            format!("{} Hz", self.sample_rate)
+        } else {
+            "irregular".to_string()
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct DataSample {
+    pub timestamp: f64,
+    pub values: Vec<f32>,
+}
+
+/// LSL inlet postprocessing options, mirrored so they can be stored and compared.
+#[derive(Clone, Copy, PartialEq)]
+pub enum PostprocessingFlag {
+    ClockSync,
+    Dejitter,
+    Monotonize,
+    Threadsafe,
+}
+
+impl PostprocessingFlag {
+    pub const ALL: [PostprocessingFlag; 4] = [
+        PostprocessingFlag::ClockSync,
+        PostprocessingFlag::Dejitter,
+        PostprocessingFlag::Monotonize,
+        PostprocessingFlag::Threadsafe,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            PostprocessingFlag::ClockSync => "ClockSync",
+            PostprocessingFlag::Dejitter => "Dejitter",
+            PostprocessingFlag::Monotonize => "Monotonize",
+            PostprocessingFlag::Threadsafe => "Threadsafe",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            PostprocessingFlag::ClockSync => "Map timestamps to this computer's clock",
+            PostprocessingFlag::Dejitter => "Smooth the timestamps of regularly sampled streams",
+            PostprocessingFlag::Monotonize => "Never let timestamps go backwards",
+            PostprocessingFlag::Threadsafe => "Make the other options safe to change while pulling",
+        }
+    }
+
+    pub fn to_lsl(self) -> lsl::ProcessingOption {
+        match self {
+            PostprocessingFlag::ClockSync => lsl::ProcessingOption::ClockSync,
+            PostprocessingFlag::Dejitter => lsl::ProcessingOption::Dejitter,
+            PostprocessingFlag::Monotonize => lsl::ProcessingOption::Monotonize,
+            PostprocessingFlag::Threadsafe => lsl::ProcessingOption::Threadsafe,
+        }
+    }
+}
+
+const DEFAULT_POSTPROCESSING: [PostprocessingFlag; 2] =
+    [PostprocessingFlag::ClockSync, PostprocessingFlag::Dejitter];
+
+/// How the LSL thread takes data out of the inlet.
+#[derive(Clone, Copy, PartialEq, Default)]
+pub enum PullMode {
+    /// `Blocking` for streams up to `BLOCKING_PULL_MAX_RATE`, `Chunk` for the rest.
+    #[default]
+    Auto,
+    /// Everything available in one `pull_chunk` call.
+    Chunk,
+    /// `pull_sample` in a loop until the inlet is empty.
+    Sample,
+    /// `pull_sample` waiting for the next sample, which is forwarded on its own
+    /// as soon as it arrives; smooth for slow streams.
+    Blocking,
+}
+
+impl PullMode {
+    pub const ALL: [PullMode; 4] = [
+        PullMode::Auto,
+        PullMode::Chunk,
+        PullMode::Sample,
+        PullMode::Blocking,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            PullMode::Auto => "Automatic",
+            PullMode::Chunk => "pull_chunk",
+            PullMode::Sample => "pull_sample loop",
+            PullMode::Blocking => "pull_sample, one by one",
+        }
+    }
+
+    /// The mode to pull a stream of this nominal rate with.
+    pub fn resolve(self, sample_rate: f64) -> PullMode {
+        match self {
+            PullMode::Auto if sample_rate > 0.0 && sample_rate <= BLOCKING_PULL_MAX_RATE => {
+                PullMode::Blocking
+            }
+            PullMode::Auto => PullMode::Chunk,
+            mode => mode,
+        }
+    }
+}
+
+/// Settings that control how the LSL thread sets up a new inlet.
+#[derive(Clone)]
+pub struct ConnectOptions {
+    pub info_timeout: f64,
+    pub info_retries: u32,
+    pub connect_timeout: f64, // Seconds from resolving the stream to having its metadata
+    pub buffer_seconds: i32, // max_buflen of the inlet; hundreds of samples for irregular-rate streams
+    pub pull_mode: PullMode,
+    pub idle_wait_ms: u64, // Wait after an empty pull; while data flows the inlet is pulled back to back
+    pub recover: bool,     // Let liblsl transparently recover the inlet after an interruption
+    pub auto_reconnect: bool, // Re-resolve by source_id and rebind when the inlet fails anyway
+    pub postprocessing: Vec<PostprocessingFlag>, // Requested from the inlet; empty for raw timestamps
+    pub skip_backlog: bool, // Drop queued-up samples on resume or after a stall, except while recording
+}
+
+impl Default for ConnectOptions {
+    fn default() -> Self {
+        Self {
+            info_timeout: DEFAULT_INFO_TIMEOUT_SECONDS,
+            info_retries: DEFAULT_INFO_RETRIES,
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT_SECONDS,
+            buffer_seconds: DEFAULT_INLET_BUFFER_SECONDS,
+            pull_mode: PullMode::default(),
+            idle_wait_ms: DEFAULT_IDLE_WAIT_MS,
+            recover: true,
+            auto_reconnect: true,
+            postprocessing: DEFAULT_POSTPROCESSING.to_vec(),
+            skip_backlog: true,
+        }
+    }
+}
+
+/// Steps of the connect sequence, reported so the UI can show progress.
+#[derive(Clone, Copy, PartialEq)]
+pub enum ConnectStage {
+    Resolving,
+    OpeningInlet,
+    SettingPostprocessing,
+    FetchingMetadata,
+}
+
+impl ConnectStage {
+    pub fn label(self) -> &'static str {
+        match self {
+            ConnectStage::Resolving => "Resolving stream…",
+            ConnectStage::OpeningInlet => "Opening inlet…",
+            ConnectStage::SettingPostprocessing => "Setting postprocessing…",
+            ConnectStage::FetchingMetadata => "Fetching metadata…",
+        }
+    }
+}
+
+pub enum LslCommand {
+    RefreshStreams,
+    RefreshStreamsFiltered(String), // List only the streams matching an LSL predicate
+    Connect(String, ConnectOptions), // Key of the listed stream to connect to, see `StreamData::key`
+    ConnectByName(String, ConnectOptions), // Resolve a stream by exact name and connect to it
+    CancelConnect, // Give up on the connect in progress; the current stream, if any, stays
+    Disconnect,
+    ConnectMarkers(String), // Key of a listed marker stream to show alongside the data
+    DisconnectMarkers,
+    StartRecording(PathBuf), // Write every sample of the connected stream to this CSV file
+    StopRecording,
+    Pause,       // Stop pulling but keep the inlet open
+    Resume,      // Pull again; the backlog is dropped if the connection skips it
+    SkipBacklog, // Drop what has queued up in the inlet to get back to live data, unless recording
+    // Only meaningful while a file is played back
+    SetPlaybackPaused(bool),
+    SetPlaybackSpeed(f64), // Multiple of real time
+    SeekPlayback(f64),     // Seconds from the start of the file
+}
+
+pub enum LslResponse {
+    StreamsFound(Vec<StreamData>),
+    Connected {
+        stream: StreamData, // As it was resolved for this connection
+        channel_names: Vec<String>,
+        channel_units: Vec<String>, // Declared unit per channel, empty if unknown
+        channel_types: Vec<String>, // Declared type per channel, empty if unknown
+        metadata_available: bool,   // False if the metadata fetch timed out
+    },
+    ConnectCancelled,
+    Disconnected,
+    Error(String),
+    Data(DataSample),
+    StringData(f64, Vec<String>), // Timestamp and one value per channel, from string-format streams
+    ConnectStage(ConnectStage),
+    PostprocessingApplied {
+        requested: Vec<PostprocessingFlag>,
+        applied: Vec<PostprocessingFlag>,
+    },
+    Reconnecting {
+        name: String,
+        attempt: u32,
+    },
+    Reconnected,
+    BacklogSkipped(u32), // Queued-up samples dropped to get back to live data
+    TimeCorrection(f64), // Seconds to add to the source's timestamps to get local LSL time
+    PullStats {
+        chunks_per_sec: f64, // Pulls that returned data
+        avg_chunk_size: f64,
+        max_chunk_size: usize,
+    },
+    InletDiag {
+        buffered: usize, // Samples waiting in the inlet right after a pull
+    },
+    StreamXml(String), // Full stream info of the connected stream, sent after `Connected`
+    MarkersConnected(String),
+    MarkersDisconnected,
+    Marker(f64, String), // Timestamp and event text
+    RecordingStarted(PathBuf),
+    RecordingProgress(usize), // Samples written so far, sent on every flush
+    RecordingStopped(usize),  // Total samples written
+    PlaybackSeeked,           // Data sent after this starts at the new position
+    PlaybackProgress {
+        position: f64, // Seconds from the start of the file
+        duration: f64,
+    },
+}
+
+/// Apply the requested postprocessing options to an inlet and report which ones
+/// are actually active. If the inlet rejects the full set, the options are tried
+/// one at a time so that every option it does accept stays enabled.
+fn apply_postprocessing(
+    inlet: &StreamInlet,
+    requested: &[PostprocessingFlag],
+) -> Vec<PostprocessingFlag> {
+    let to_lsl = |flags: &[PostprocessingFlag]| -> Vec<lsl::ProcessingOption> {
+        flags.iter().map(|f| f.to_lsl()).collect()
+    };
+
+    if inlet.set_postprocessing(&to_lsl(requested)).is_ok() {
+        return requested.to_vec();
+    }
+
+    let mut applied: Vec<PostprocessingFlag> = Vec::new();
+    for &flag in requested {
+        let mut candidate = applied.clone();
+        candidate.push(flag);
+        if inlet.set_postprocessing(&to_lsl(&candidate)).is_ok() {
+            applied = candidate;
+        }
+    }
+    // make sure the inlet ends up with exactly the accepted set
+    if inlet.set_postprocessing(&to_lsl(&applied)).is_err() {
+        applied.clear();
+    }
+    applied
+}
+
+/// Labels, units and types of the channels. Generated names and empty units and
+/// types if the metadata doesn't describe exactly `expected_count` channels.
+fn extract_channel_info(
+    info: &mut StreamInfo,
+    expected_count: usize,
+) -> (Vec<String>, Vec<String>, Vec<String>) {
+    let mut channel_names = vec![];
+    let mut channel_units = vec![];
+    let mut channel_types = vec![];
+
+    let mut cursor = info.desc().child("channels").child("channel");
+    while cursor.is_valid() {
+        channel_names.push(cursor.child_value_named("label"));
+        channel_units.push(cursor.child_value_named("unit").trim().to_string());
+        channel_types.push(cursor.child_value_named("type").trim().to_string());
+        cursor = cursor.next_sibling();
+    }
+
+    if channel_names.len() != expected_count {
+        (
+            default_channel_names(expected_count),
+            vec![String::new(); expected_count],
+            vec![String::new(); expected_count],
+        )
+    } else {
+        (channel_names, channel_units, channel_types)
+    }
+}
+
+pub fn default_channel_names(count: usize) -> Vec<String> {
+    (0..count)
+        .map(|i| "Ch ".to_string() + &i.to_string())
+        .collect()
+}
+
+/// The listed stream with this key, see `StreamData::key`. Keys stay the same
+/// while the list is refreshed, unlike positions in it.
+fn listed_stream<'a>(available_streams: &'a [StreamInfo], key: &str) -> Option<&'a StreamInfo> {
+    available_streams.iter().find(|s| {
+        let source_id = s.source_id();
+        if source_id.is_empty() {
+            s.stream_name() == key
+        } else {
+            source_id == key
+        }
+    })
+}
+
+/// Find a listed stream on the network again, matching by source_id when it has
+/// one and by name otherwise. Returns `None` if the stream has gone away.
+fn re_resolve_stream(listed: &StreamInfo) -> Option<StreamInfo> {
+    let source_id = listed.source_id();
+    let predicate = if source_id.is_empty() {
+        format!("name='{}'", listed.stream_name())
+    } else {
+        format!("source_id='{}'", source_id)
+    };
+    // XPath 1.0 literals can't contain the quote character they are delimited by
+    if predicate.matches('\'').count() != 2 {
+        return Some(listed.clone());
+    }
+    lsl::resolve_bypred(&predicate, 1, RE_RESOLVE_TIMEOUT_SECONDS)
+        .ok()?
+        .into_iter()
+        .find(|s| s.stream_name() == listed.stream_name())
+}
+
+/// Find a stream on the network by its exact name.
+fn resolve_stream_by_name(name: &str) -> Result<StreamInfo, String> {
+    let predicate = format!("name='{}'", name);
+    // XPath 1.0 literals can't contain the quote character they are delimited by
+    if predicate.matches('\'').count() != 2 {
+        return Err(format!(
+            "Can't look up a stream name containing a quote: {}",
+            name
+        ));
+    }
+    lsl::resolve_bypred(&predicate, 1, CONNECT_BY_NAME_TIMEOUT_SECONDS)
+        .map_err(|e| format!("Failed to look for stream '{}': {}", name, e))?
+        .into_iter()
+        .next()
+        .ok_or_else(|| {
+            format!(
+                "No stream named '{}' found within {} s",
+                name, CONNECT_BY_NAME_TIMEOUT_SECONDS
+            )
+        })
+}
+
+/// Fetch the full stream info, retrying a few times for slow outlets.
+fn fetch_stream_info(inlet: &StreamInlet, options: &ConnectOptions) -> Option<StreamInfo> {
+    (0..=options.info_retries).find_map(|_| inlet.info(options.info_timeout).ok())
+}
+
+/// A data inlet opened on a connect thread, ready for the LSL thread to take over.
+struct OpenedInlet {
+    inlet: StreamInlet,
+    info: StreamInfo,
+    channel_names: Vec<String>,
+    channel_units: Vec<String>,
+    channel_types: Vec<String>,
+    xml: Option<String>, // Full stream info, if the metadata could be fetched
+    metadata_available: bool, // False if the metadata fetch timed out
+    applied_postprocessing: Vec<PostprocessingFlag>,
+}
+
+/// What a connect thread reports back to the LSL thread.
+enum ConnectProgress {
+    Stage(ConnectStage),
+    Opened(Box<OpenedInlet>),
+    Failed(String),
+}
+
+/// A connect running on its own thread, so the LSL thread keeps handling
+/// commands meanwhile. Dropping it abandons the attempt.
+struct PendingConnect {
+    name: String,
+    options: ConnectOptions,
+    progress: Receiver<ConnectProgress>,
+    deadline: Instant,
+}
+
+/// Resolve a stream with `resolve` and open an inlet on it, on a new thread.
+fn start_connect(
+    name: String,
+    resolve: impl FnOnce() -> Result<StreamInfo, String> + Send + 'static,
+    options: ConnectOptions,
+) -> PendingConnect {
+    let (sender, receiver) = crossbeam_channel::unbounded();
+    let thread_options = options.clone();
+    thread::spawn(move || {
+        let progress = match open_data_inlet(resolve, &thread_options, &sender) {
+            Ok(opened) => ConnectProgress::Opened(Box::new(opened)),
+            Err(msg) => ConnectProgress::Failed(msg),
+        };
+        let _ = sender.send(progress);
+    });
+    PendingConnect {
+        name,
+        deadline: Instant::now() + Duration::from_secs_f64(options.connect_timeout),
+        options,
+        progress: receiver,
+    }
+}
+
+/// Open an inlet on the stream `resolve` finds, reporting each stage. Stops
+/// between stages once nobody is waiting for the result anymore.
+fn open_data_inlet(
+    resolve: impl FnOnce() -> Result<StreamInfo, String>,
+    options: &ConnectOptions,
+    progress: &Sender<ConnectProgress>,
+) -> Result<OpenedInlet, String> {
+    let report = |stage| {
+        progress
+            .send(ConnectProgress::Stage(stage))
+            .map_err(|_| "Connect cancelled".to_string())
+    };
+    let stream_info = resolve()?;
+    let channel_count = stream_info.channel_count() as usize;
+    report(ConnectStage::OpeningInlet)?;
+    let inlet = StreamInlet::new(&stream_info, options.buffer_seconds, 0, options.recover)
+        .map_err(|e| format!("Failed to connect: {}", e))?;
+    report(ConnectStage::SettingPostprocessing)?;
+    let applied_postprocessing = apply_postprocessing(&inlet, &options.postprocessing);
+
+    // slow metadata shouldn't fail the whole connect, fall back to generated names
+    report(ConnectStage::FetchingMetadata)?;
+    let ((channel_names, channel_units, channel_types), xml, metadata_available) =
+        match fetch_stream_info(&inlet, options) {
+            Some(mut info) => (
+                extract_channel_info(&mut info, channel_count),
+                info.to_xml().ok(),
+                true,
+            ),
+            None => (
+                (
+                    default_channel_names(channel_count),
+                    vec![String::new(); channel_count],
+                    vec![String::new(); channel_count],
+                ),
+                None,
+                false,
+            ),
+        };
+    Ok(OpenedInlet {
+        inlet,
+        info: stream_info,
+        channel_names,
+        channel_units,
+        channel_types,
+        xml,
+        metadata_available,
+        applied_postprocessing,
+    })
+}
+
+/// Drop whatever has queued up in the inlet and report how much that was.
+fn skip_backlog(inlet: &StreamInlet, resp_tx: &Sender<LslResponse>) {
+    let dropped = inlet.flush();
+    if dropped > 0 {
+        let _ = resp_tx.send(LslResponse::BacklogSkipped(dropped));
+    }
+}
+
+/// Finish the active recording, if any, and report how much was written.
+pub fn stop_recording(recorder: &mut Option<Recorder>, resp_tx: &Sender<LslResponse>) {
+    if let Some(active) = recorder.take() {
+        let response = match active.finish() {
+            Ok(samples_written) => LslResponse::RecordingStopped(samples_written),
+            Err(e) => LslResponse::Error(format!("Failed to finish recording: {}", e)),
+        };
+        let _ = resp_tx.send(response);
+    }
+}
+
+pub fn lsl_handler_thread(cmd_rx: Receiver<LslCommand>, resp_tx: Sender<LslResponse>) {
+    let mut available_streams: Vec<StreamInfo> = Vec::new();
+    let mut inlet: Option<StreamInlet> = None;
+    let mut processor = processing::registered_processor();
+    let mut channel_names: Vec<String> = Vec::new();
+    let mut recorder: Option<Recorder> = None;
+    // The stream and options of the current connection, kept for reconnecting
+    let mut connection: Option<(StreamInfo, ConnectOptions)> = None;
+    let mut reconnect: Option<ReconnectState> = None;
+    let mut marker_inlet: Option<StreamInlet> = None;
+    // Whether the last pull returned data; if so, the next one follows without waiting
+    let mut data_flowing = false;
+    // String streams are forwarded as text instead of going through the numeric path
+    let mut string_stream = false;
+    let mut channel_format = lsl::ChannelFormat::Float32;
+    // When the clock offset of the connected stream was last queried
+    let mut last_time_correction: Option<Instant> = None;
+    let mut pull_counter = PullCounter::new();
+    // Result of a stream listing that resolves on its own thread, so pulling goes on meanwhile
+    let mut resolving: Option<Receiver<lsl::Result<Vec<StreamInfo>>>> = None;
+    // Keeps the list up to date between refreshes, with the filter of the last one
+    let mut watcher = StreamWatcher::new(None);
+    // Set by `Pause`: the inlet stays open but isn't pulled
+    let mut paused = false;
+    // A connect in progress; the current inlet keeps being pulled until it succeeds
+    let mut connecting: Option<PendingConnect> = None;
+
+    loop {
+        // Wait for commands between pulls, so a disconnect is handled within one interval.
+        // A blocking pull does the waiting itself, for no longer than that interval
+        let blocking =
+            inlet.is_some() && !paused && connected_pull_mode(&connection) == PullMode::Blocking;
+        let wait = if data_flowing || blocking {
+            Duration::ZERO
+        } else {
+            connection
+                .as_ref()
+                .map_or(COMMAND_POLL_INTERVAL, |(_, options)| {
+                    Duration::from_millis(options.idle_wait_ms)
+                })
+        };
+        match cmd_rx.recv_timeout(wait) {
+            // a refresh while one is running gets that one's result
+            Ok(LslCommand::RefreshStreams) => {
+                if watcher.predicate.is_some() {
+                    watcher = StreamWatcher::new(None);
+                }
+                if resolving.is_none() {
+                    resolving = Some(resolve_in_background(None));
+                }
+            }
+            Ok(LslCommand::RefreshStreamsFiltered(predicate)) => {
+                if watcher.predicate.as_ref() != Some(&predicate) {
+                    watcher = StreamWatcher::new(Some(predicate.clone()));
+                }
+                if resolving.is_none() {
+                    resolving = Some(resolve_in_background(Some(predicate)));
+                }
+            }
+            Ok(LslCommand::Connect(key, options)) => {
+                stop_recording(&mut recorder, &resp_tx);
+                paused = false;
+                reconnect = None;
+                // the list may be stale, so look the stream up again before connecting
+                let _ = resp_tx.send(LslResponse::ConnectStage(ConnectStage::Resolving));
+                let listed = listed_stream(&available_streams, &key).cloned();
+                let name = listed
+                    .as_ref()
+                    .map_or(String::new(), |s| s.stream_name().to_string());
+                let resolve = move || match listed {
+                    Some(listed) => re_resolve_stream(&listed).ok_or_else(|| {
+                        format!(
+                            "Stream '{}' is no longer available, please refresh the stream list",
+                            listed.stream_name()
+                        )
+                    }),
+                    None => Err(format!(
+                        "Stream '{}' is no longer listed, please refresh the stream list",
+                        key
+                    )),
+                };
+                connecting = Some(start_connect(name, resolve, options));
+            }
+            Ok(LslCommand::ConnectByName(name, options)) => {
+                stop_recording(&mut recorder, &resp_tx);
+                paused = false;
+                reconnect = None;
+                let _ = resp_tx.send(LslResponse::ConnectStage(ConnectStage::Resolving));
+                let target = name.clone();
+                connecting = Some(start_connect(
+                    name,
+                    move || resolve_stream_by_name(&target),
+                    options,
+                ));
+            }
+            Ok(LslCommand::CancelConnect) => {
+                if connecting.take().is_some() {
+                    let _ = resp_tx.send(LslResponse::ConnectCancelled);
+                }
+            }
+            Ok(LslCommand::ConnectMarkers(key)) => {
+                marker_inlet = None;
+                let opened = listed_stream(&available_streams, &key)
+                    .ok_or_else(|| {
+                        format!(
+                            "Marker stream '{}' is no longer listed, please refresh the stream list",
+                            key
+                        )
+                    })
+                    .and_then(|info| {
+                        StreamInlet::new(info, BUFFER_SIZE, 0, true)
+                            .map(|new_inlet| (info.stream_name().to_string(), new_inlet))
+                            .map_err(|e| format!("Failed to connect to marker stream: {}", e))
+                    });
+                match opened {
+                    Ok((name, new_inlet)) => {
+                        apply_postprocessing(&new_inlet, &DEFAULT_POSTPROCESSING);
+                        marker_inlet = Some(new_inlet);
+                        let _ = resp_tx.send(LslResponse::MarkersConnected(name));
+                    }
+                    Err(msg) => {
+                        let _ = resp_tx.send(LslResponse::Error(msg));
+                    }
+                }
+            }
+            Ok(LslCommand::DisconnectMarkers) => {
+                marker_inlet = None;
+                let _ = resp_tx.send(LslResponse::MarkersDisconnected);
+            }
+            Ok(LslCommand::Disconnect) => {
+                stop_recording(&mut recorder, &resp_tx);
+                paused = false;
+                connecting = None;
+                inlet = None;
+                marker_inlet = None;
+                connection = None;
+                reconnect = None;
+                if resp_tx.send(LslResponse::Disconnected).is_err() {
+                    break;
+                }
+            }
+            Ok(LslCommand::StartRecording(path)) => {
+                stop_recording(&mut recorder, &resp_tx);
+                if inlet.is_none() {
+                    let _ = resp_tx.send(LslResponse::Error(
+                        "Connect to a stream before recording".to_string(),
+                    ));
+                } else if string_stream {
+                    let _ = resp_tx.send(LslResponse::Error(
+                        "Only numeric streams can be recorded".to_string(),
+                    ));
+                } else {
+                    match Recorder::create(&path, &channel_names) {
+                        Ok(new_recorder) => {
+                            recorder = Some(new_recorder);
+                            let _ = resp_tx.send(LslResponse::RecordingStarted(path));
+                        }
+                        Err(e) => {
+                            let _ = resp_tx.send(LslResponse::Error(format!(
+                                "Failed to start recording: {}",
+                                e
+                            )));
+                        }
+                    }
+                }
+            }
+            Ok(LslCommand::StopRecording) => stop_recording(&mut recorder, &resp_tx),
+            Ok(LslCommand::Pause) => paused = true,
+            Ok(LslCommand::Resume) => {
+                paused = false;
+                // the backlog is stale by now, start again from the newest data
+                if let (Some(active_inlet), Some((_, options))) = (&inlet, &connection)
+                    && options.skip_backlog
+                    && recorder.is_none()
+                {
+                    skip_backlog(active_inlet, &resp_tx);
+                }
+            }
+            Ok(LslCommand::SkipBacklog) => {
+                if let Some(active_inlet) = inlet.as_ref().filter(|_| recorder.is_none()) {
+                    skip_backlog(active_inlet, &resp_tx);
+                }
+            }
+            Ok(
+                LslCommand::SetPlaybackPaused(_)
+                | LslCommand::SetPlaybackSpeed(_)
+                | LslCommand::SeekPlayback(_),
+            ) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+            Err(RecvTimeoutError::Timeout) => {}
+        }
+
+        if let Some(receiver) = &resolving {
+            match receiver.try_recv() {
+                Ok(resolved) => {
+                    list_streams(resolved, &mut available_streams, &resp_tx);
+                    resolving = None;
+                }
+                Err(TryRecvError::Empty) => {}
+                Err(TryRecvError::Disconnected) => resolving = None,
+            }
+        }
+        if let Some(streams) = watcher.poll() {
+            list_streams(Ok(streams), &mut available_streams, &resp_tx);
+        }
+
+        // Pass on the progress of a connect, and take over its inlet once it's open
+        let finished = connecting.as_ref().and_then(|pending| {
+            for progress in pending.progress.try_iter() {
+                match progress {
+                    ConnectProgress::Stage(stage) => {
+                        let _ = resp_tx.send(LslResponse::ConnectStage(stage));
+                    }
+                    done => return Some(done),
+                }
+            }
+            (Instant::now() >= pending.deadline).then(|| {
+                ConnectProgress::Failed(format!(
+                    "Connecting to '{}' timed out after {} s; the stream may be gone, try refreshing the list",
+                    pending.name, pending.options.connect_timeout
+                ))
+            })
+        });
+        if let Some((done, pending)) =
+            finished.and_then(|done| connecting.take().map(|pending| (done, pending)))
+        {
+            match done {
+                ConnectProgress::Opened(opened) => {
+                    let opened = *opened;
+                    if let Some(processor) = processor.as_mut() {
+                        processor.reset(opened.channel_names.len(), opened.info.nominal_srate());
+                    }
+                    let _ = resp_tx.send(LslResponse::Connected {
+                        stream: stream_data(&opened.info),
+                        channel_names: opened.channel_names.clone(),
+                        channel_units: opened.channel_units,
+                        channel_types: opened.channel_types,
+                        metadata_available: opened.metadata_available,
+                    });
+                    if let Some(xml) = opened.xml {
+                        let _ = resp_tx.send(LslResponse::StreamXml(xml));
+                    }
+                    let _ = resp_tx.send(LslResponse::PostprocessingApplied {
+                        requested: pending.options.postprocessing.clone(),
+                        applied: opened.applied_postprocessing,
+                    });
+                    channel_names = opened.channel_names;
+                    string_stream =
+                        matches!(opened.info.channel_format(), lsl::ChannelFormat::String);
+                    channel_format = opened.info.channel_format();
+                    last_time_correction = None;
+                    pull_counter = PullCounter::new();
+                    inlet = Some(opened.inlet);
+                    connection = Some((opened.info, pending.options));
+                }
+                ConnectProgress::Failed(msg) => {
+                    let _ = resp_tx.send(LslResponse::Error(msg));
+                }
+                ConnectProgress::Stage(_) => {}
+            }
+        }
+
+        // Pull data if connected
+        data_flowing = false;
+        if let Some(active_inlet) = inlet.as_ref().filter(|_| !paused) {
+            // after a stall, jump to live data instead of fast-forwarding through the backlog
+            if let Some((info, options)) = &connection {
+                let limit = info.nominal_srate() * BACKLOG_FLUSH_SECONDS;
+                if options.skip_backlog
+                    && recorder.is_none()
+                    && limit > 0.0
+                    && active_inlet.samples_available() as f64 > limit
+                {
+                    skip_backlog(active_inlet, &resp_tx);
+                }
+            }
+            let pull_mode = connected_pull_mode(&connection);
+            // the number of samples forwarded, or None once the UI has hung up
+            let pulled = if string_stream {
+                active_inlet.pull_chunk().map(
+                    |(chunk, timestamps): (Vec<Vec<String>>, Vec<f64>)| {
+                        let count = chunk.len();
+                        chunk
+                            .into_iter()
+                            .zip(timestamps)
+                            .all(|(values, timestamp)| {
+                                resp_tx
+                                    .send(LslResponse::StringData(timestamp, values))
+                                    .is_ok()
+                            })
+                            .then_some(count)
+                    },
+                )
+            } else {
+                pull_in_format(active_inlet, channel_format, pull_mode).map(
+                    |(chunk, timestamps)| {
+                        let count = chunk.len();
+                        forward_chunk(chunk, &timestamps, &mut processor, &mut recorder, &resp_tx)
+                            .then_some(count)
+                    },
+                )
+            };
+            match pulled {
+                Ok(Some(count)) => {
+                    data_flowing = count > 0;
+                    pull_counter.record(count);
+                    if let Some(stats) = pull_counter.take_if_due() {
+                        let _ = resp_tx.send(stats);
+                        let _ = resp_tx.send(LslResponse::InletDiag {
+                            buffered: active_inlet.samples_available() as usize,
+                        });
+                    }
+                }
+                // the UI is gone, nobody is listening anymore
+                Ok(None) => break,
+                Err(e) => {
+                    inlet = None;
+                    match &connection {
+                        // keep the display and any recording going while we look for it again
+                        Some((info, options))
+                            if options.auto_reconnect && !info.source_id().is_empty() =>
+                        {
+                            reconnect = Some(ReconnectState {
+                                attempt: 0,
+                                next_attempt: Instant::now(),
+                                running: None,
+                            });
+                        }
+                        // usually the source went away; go back to idle instead of taking the app down
+                        _ => {
+                            stop_recording(&mut recorder, &resp_tx);
+                            connection = None;
+                            let _ = resp_tx.send(LslResponse::Disconnected);
+                            let _ = resp_tx.send(LslResponse::Error(format!("Stream lost: {}", e)));
+                        }
+                    }
+                }
+            }
+        }
+
+        // Refresh the clock offset now and then. The query barely waits, so it can't stall
+        // pulling; until liblsl's measurement has a result it is asked again shortly
+        if let Some(active_inlet) = &inlet
+            && last_time_correction.is_none_or(|last| last.elapsed() >= TIME_CORRECTION_INTERVAL)
+        {
+            match active_inlet.time_correction(TIME_CORRECTION_TIMEOUT_SECONDS) {
+                Ok(offset) => {
+                    last_time_correction = Some(Instant::now());
+                    let _ = resp_tx.send(LslResponse::TimeCorrection(offset));
+                }
+                Err(_) => {
+                    last_time_correction = Instant::now().checked_sub(
+                        TIME_CORRECTION_INTERVAL.saturating_sub(TIME_CORRECTION_RETRY),
+                    );
+                }
+            }
+        }
+
+        // Markers arrive rarely, pull whatever is there
+        if let Some(active_inlet) = &marker_inlet {
+            let pulled: Result<(Vec<Vec<String>>, Vec<f64>), _> = active_inlet.pull_chunk();
+            match pulled {
+                Ok((chunk, timestamps)) => {
+                    for (values, timestamp) in chunk.into_iter().zip(timestamps) {
+                        let _ = resp_tx.send(LslResponse::Marker(timestamp, values.join(", ")));
+                    }
+                }
+                Err(e) => {
+                    marker_inlet = None;
+                    let _ = resp_tx.send(LslResponse::MarkersDisconnected);
+                    let _ = resp_tx.send(LslResponse::Error(format!("Marker stream lost: {}", e)));
+                }
+            }
+        }
+
+        // Look for a lost stream every few seconds and rebind to it
+        if let (Some(state), Some((info, options))) = (reconnect.as_mut(), &connection) {
+            match state.running.as_ref().map(|running| running.try_recv()) {
+                Some(Ok(Some((new_inlet, applied)))) => {
+                    inlet = Some(new_inlet);
+                    reconnect = None;
+                    last_time_correction = None;
+                    let _ = resp_tx.send(LslResponse::Reconnected);
+                    let _ = resp_tx.send(LslResponse::PostprocessingApplied {
+                        requested: options.postprocessing.clone(),
+                        applied,
+                    });
+                }
+                Some(Ok(None) | Err(TryRecvError::Disconnected)) => {
+                    state.running = None;
+                    state.next_attempt = Instant::now() + RECONNECT_INTERVAL;
+                }
+                Some(Err(TryRecvError::Empty)) => {}
+                None if Instant::now() >= state.next_attempt => {
+                    state.attempt += 1;
+                    let _ = resp_tx.send(LslResponse::Reconnecting {
+                        name: info.stream_name().to_string(),
+                        attempt: state.attempt,
+                    });
+                    state.running = Some(reconnect_in_background(info, options));
+                }
+                None => {}
+            }
+        }
+    }
+}
+
+/// Continuous resolution of the streams on the network, reporting the list
+/// whenever a stream appears or disappears.
+struct StreamWatcher {
+    resolver: Option<lsl::ContinuousResolver>, // None if LSL couldn't create one
+    predicate: Option<String>,
+    last_poll: Instant,
+    known: Vec<String>, // Sorted uids of the last reported list
+}
+
+impl StreamWatcher {
+    fn new(predicate: Option<String>) -> Self {
+        let resolver = match &predicate {
+            Some(predicate) => {
+                lsl::ContinuousResolver::new_with_pred(predicate, STREAM_FORGET_SECONDS)
+            }
+            None => lsl::ContinuousResolver::new(STREAM_FORGET_SECONDS),
+        };
+        Self {
+            resolver: resolver.ok(),
+            predicate,
+            last_poll: Instant::now(),
+            known: Vec::new(),
+        }
+    }
+
+    /// The current streams if the set changed since the last report.
+    fn poll(&mut self) -> Option<Vec<StreamInfo>> {
+        if self.last_poll.elapsed() < STREAM_WATCH_INTERVAL {
+            return None;
+        }
+        self.last_poll = Instant::now();
+        let streams = self.resolver.as_ref()?.results().ok()?;
+        let mut uids: Vec<String> = streams.iter().map(|s| s.uid()).collect();
+        uids.sort();
+        if uids == self.known {
+            return None;
+        }
+        self.known = uids;
+        Some(streams)
+    }
+}
+
+/// Resolve the streams on the network, all or those matching `predicate`, on a
+/// thread of its own; the result arrives on the returned channel.
+fn resolve_in_background(predicate: Option<String>) -> Receiver<lsl::Result<Vec<StreamInfo>>> {
+    let (sender, receiver) = crossbeam_channel::bounded(1);
+    thread::spawn(move || {
+        let resolved = match predicate {
+            // resolve as long as an unfiltered refresh does, rather than stopping at the first match
+            Some(predicate) => lsl::resolve_bypred(&predicate, i32::MAX, 3.0),
+            None => lsl::resolve_streams(3.0),
+        };
+        let _ = sender.send(resolved);
+    });
+    receiver
+}
+
+/// What the UI is told about a stream.
+fn stream_data(info: &StreamInfo) -> StreamData {
+    StreamData {
+        name: info.stream_name().to_string(),
+        stream_type: info.stream_type().to_string(),
+        source_id: info.source_id().to_string(),
+        hostname: info.hostname().to_string(),
+        channel_count: info.channel_count() as usize,
+        sample_rate: info.nominal_srate(),
+        channel_format: channel_format_label(info.channel_format()).to_string(),
+    }
+}
+
+/// Keep a fresh listing for later connects and send it to the UI.
+fn list_streams(
+    resolved: lsl::Result<Vec<StreamInfo>>,
+    available_streams: &mut Vec<StreamInfo>,
+    resp_tx: &Sender<LslResponse>,
+) {
+    match resolved {
+        Ok(streams) => {
+            *available_streams = streams;
+            let listing = available_streams.iter().map(stream_data).collect();
+            let _ = resp_tx.send(LslResponse::StreamsFound(listing));
+        }
+        Err(e) => {
+            let _ = resp_tx.send(LslResponse::Error(format!(
+                "Failed to refresh streams: {}",
+                e
+            )));
+        }
+    }
+}
+
+/// Name of a channel format as LSL spells it in stream metadata.
+fn channel_format_label(format: lsl::ChannelFormat) -> &'static str {
+    match format {
+        lsl::ChannelFormat::Float32 => "float32",
+        lsl::ChannelFormat::Double64 => "double64",
+        lsl::ChannelFormat::String => "string",
+        lsl::ChannelFormat::Int32 => "int32",
+        lsl::ChannelFormat::Int16 => "int16",
+        lsl::ChannelFormat::Int8 => "int8",
+        lsl::ChannelFormat::Int64 => "int64",
+        lsl::ChannelFormat::Undefined => "undefined",
+    }
+}
+
+/// Sample types an inlet can be pulled as; the display buffers hold f32.
+trait NumericSample: Copy {
+    fn to_f32(self) -> f32;
+}
+
+macro_rules! numeric_sample {
+    ($($t:ty),*) => {
+        $(impl NumericSample for $t {
+            fn to_f32(self) -> f32 {
+                self as f32
+            }
+        })*
+    };
+}
+numeric_sample!(f32, f64, i32, i16, i8);
+
+/// Pull what is available in the stream's own type `T`, so liblsl doesn't
+/// convert on the way out, and convert it for display.
+fn pull_numeric<T: NumericSample>(
+    inlet: &StreamInlet,
+    pull_mode: PullMode,
+) -> lsl::Result<(Vec<Vec<f32>>, Vec<f64>)>
+where
+    StreamInlet: Pullable<T>,
+{
+    let (chunk, timestamps): (Vec<Vec<T>>, Vec<f64>) = match pull_mode {
+        PullMode::Auto | PullMode::Chunk => inlet.pull_chunk()?,
+        PullMode::Sample => pull_available_samples(inlet)?,
+        PullMode::Blocking => pull_next_sample(inlet)?,
+    };
+    let chunk = chunk
+        .into_iter()
+        .map(|values| values.into_iter().map(T::to_f32).collect())
+        .collect();
+    Ok((chunk, timestamps))
+}
+
+/// Pull a numeric stream in its declared channel format.
+fn pull_in_format(
+    inlet: &StreamInlet,
+    format: lsl::ChannelFormat,
+    pull_mode: PullMode,
+) -> lsl::Result<(Vec<Vec<f32>>, Vec<f64>)> {
+    match format {
+        // int64 goes through f64, which holds it exactly up to 2^53
+        lsl::ChannelFormat::Double64 | lsl::ChannelFormat::Int64 => {
+            pull_numeric::<f64>(inlet, pull_mode)
+        }
+        lsl::ChannelFormat::Int32 => pull_numeric::<i32>(inlet, pull_mode),
+        lsl::ChannelFormat::Int16 => pull_numeric::<i16>(inlet, pull_mode),
+        lsl::ChannelFormat::Int8 => pull_numeric::<i8>(inlet, pull_mode),
+        _ => pull_numeric::<f32>(inlet, pull_mode),
+    }
+}
+
+/// The pull mode of the connected stream, with `Auto` resolved by its rate.
+fn connected_pull_mode(connection: &Option<(StreamInfo, ConnectOptions)>) -> PullMode {
+    connection
+        .as_ref()
+        .map_or(PullMode::Chunk, |(info, options)| {
+            options.pull_mode.resolve(info.nominal_srate())
+        })
+}
+
+/// Wait up to one command poll interval for the next sample, as a chunk of at most one.
+fn pull_next_sample<T>(inlet: &StreamInlet) -> lsl::Result<(Vec<Vec<T>>, Vec<f64>)>
+where
+    StreamInlet: Pullable<T>,
+{
+    let (values, timestamp): (Vec<T>, f64) =
+        inlet.pull_sample(COMMAND_POLL_INTERVAL.as_secs_f64())?;
+    // a zero timestamp means the wait ran out
+    if timestamp == 0.0 {
+        return Ok((Vec::new(), Vec::new()));
+    }
+    Ok((vec![values], vec![timestamp]))
+}
+
+/// Pull single samples until the inlet has nothing more, as a chunk.
+fn pull_available_samples<T>(inlet: &StreamInlet) -> lsl::Result<(Vec<Vec<T>>, Vec<f64>)>
+where
+    StreamInlet: Pullable<T>,
+{
+    let mut chunk = Vec::new();
+    let mut timestamps = Vec::new();
+    loop {
+        let (values, timestamp): (Vec<T>, f64) = inlet.pull_sample(0.0)?;
+        // a zero timestamp means no sample was available
+        if timestamp == 0.0 {
+            return Ok((chunk, timestamps));
+        }
+        chunk.push(values);
+        timestamps.push(timestamp);
+    }
+}
+
+/// Sizes of the chunks pulled since the last report.
+struct PullCounter {
+    since: Instant,
+    chunks: usize,
+    samples: usize,
+    max_chunk: usize,
+}
+
+impl PullCounter {
+    fn new() -> Self {
+        Self {
+            since: Instant::now(),
+            chunks: 0,
+            samples: 0,
+            max_chunk: 0,
+        }
+    }
+
+    /// Count a pull; empty ones only mean the inlet had nothing yet.
+    fn record(&mut self, chunk_size: usize) {
+        if chunk_size > 0 {
+            self.chunks += 1;
+            self.samples += chunk_size;
+            self.max_chunk = self.max_chunk.max(chunk_size);
+        }
+    }
+
+    /// The statistics of the last interval once it is over, starting the next one.
+    fn take_if_due(&mut self) -> Option<LslResponse> {
+        let elapsed = self.since.elapsed();
+        if elapsed < PULL_STATS_INTERVAL {
+            return None;
+        }
+        let counted = std::mem::replace(self, PullCounter::new());
+        Some(LslResponse::PullStats {
+            chunks_per_sec: counted.chunks as f64 / elapsed.as_secs_f64(),
+            avg_chunk_size: if counted.chunks > 0 {
+                counted.samples as f64 / counted.chunks as f64
+            } else {
+                0.0
+            },
+            max_chunk_size: counted.max_chunk,
+        })
+    }
+}
+
+/// A new inlet on a lost stream and the postprocessing it took, if it was found.
+type Rebound = Option<(StreamInlet, Vec<PostprocessingFlag>)>;
+
+/// Progress of the attempts to get a lost stream back.
+struct ReconnectState {
+    attempt: u32,
+    next_attempt: Instant,
+    running: Option<Receiver<Rebound>>, // Result of the attempt on its own thread
+}
+
+/// Run `try_reconnect` on a thread of its own, so the LSL thread keeps handling
+/// commands during the re-resolve; the result arrives on the returned channel.
+fn reconnect_in_background(lost: &StreamInfo, options: &ConnectOptions) -> Receiver<Rebound> {
+    let (sender, receiver) = crossbeam_channel::bounded(1);
+    let (lost, options) = (lost.clone(), options.clone());
+    thread::spawn(move || {
+        let _ = sender.send(try_reconnect(&lost, &options));
+    });
+    receiver
+}
+
+/// Find a lost stream by its source_id and open a new inlet for it. Only a stream
+/// with the same channel count is accepted, so the buffers stay valid.
+fn try_reconnect(lost: &StreamInfo, options: &ConnectOptions) -> Rebound {
+    let predicate = format!("source_id='{}'", lost.source_id());
+    if predicate.matches('\'').count() != 2 {
+        return None;
+    }
+    let found = lsl::resolve_bypred(&predicate, 1, RE_RESOLVE_TIMEOUT_SECONDS)
+        .ok()?
+        .into_iter()
+        .find(|s| s.channel_count() == lost.channel_count())?;
+    let inlet = StreamInlet::new(&found, options.buffer_seconds, 0, options.recover).ok()?;
+    let applied = apply_postprocessing(&inlet, &options.postprocessing);
+    Some((inlet, applied))
+}
+
+/// Process, record and send one pulled chunk. Returns false once the UI has hung up.
+fn forward_chunk(
+    chunk: Vec<Vec<f32>>,
+    timestamps: &[f64],
+    processor: &mut Option<Box<dyn processing::SampleProcessor>>,
+    recorder: &mut Option<Recorder>,
+    resp_tx: &Sender<LslResponse>,
+) -> bool {
+    for (values, &timestamp) in chunk.into_iter().zip(timestamps) {
+        let mut data = DataSample { timestamp, values };
+        if let Some(processor) = processor.as_mut() {
+            processor.process(&mut data);
+        }
+        // every channel is recorded, whatever is selected for display
+        if let Some(active) = recorder.as_mut()
+            && let Err(e) = active.write(&data)
+        {
+            let _ = resp_tx.send(LslResponse::Error(format!("Recording failed: {}", e)));
+            stop_recording(recorder, resp_tx);
+        }
+
+        if resp_tx.send(LslResponse::Data(data)).is_err() {
+            return false;
+        }
+    }
+
+    if let Some(active) = recorder.as_mut() {
+        match active.flush_if_due() {
+            Ok(true) => {
+                let _ = resp_tx.send(LslResponse::RecordingProgress(active.samples_written));
+            }
+            Ok(false) => {}
+            Err(e) => {
+                let _ = resp_tx.send(LslResponse::Error(format!("Recording failed: {}", e)));
+                stop_recording(recorder, resp_tx);
+            }
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn idle_disconnect_is_handled_promptly() {
+        let (cmd_tx, cmd_rx) = crossbeam_channel::unbounded();
+        let (resp_tx, resp_rx) = crossbeam_channel::unbounded();
+        let handler = thread::spawn(move || lsl_handler_thread(cmd_rx, resp_tx));
+
+        let sent = Instant::now();
+        cmd_tx.send(LslCommand::Disconnect).unwrap();
+        loop {
+            match resp_rx.recv_timeout(Duration::from_secs(5)) {
+                Ok(LslResponse::Disconnected) => break,
+                Ok(_) => {}
+                Err(_) => panic!("expected a Disconnected response"),
+            }
+        }
+        // one poll interval is the target, the bound leaves room for a loaded machine
+        assert!(sent.elapsed() < Duration::from_secs(1));
+
+        drop(cmd_tx);
+        handler.join().unwrap();
+    }
+
+    #[test]
+    #[ignore = "opens a real LSL outlet, which needs a working network stack"]
+    fn disconnect_is_handled_promptly() {
+        // an outlet that never pushes, so every blocking pull waits as long as it can
+        let info = StreamInfo::new(
+            "DisconnectTest",
+            "EEG",
+            2,
+            10.0,
+            lsl::ChannelFormat::Float32,
+            "disconnect-is-handled-promptly",
+        )
+        .unwrap();
+        let _outlet = lsl::StreamOutlet::new(&info, 0, 360).unwrap();
+        let options = ConnectOptions {
+            pull_mode: PullMode::Blocking,
+            ..ConnectOptions::default()
+        };
+
+        let (cmd_tx, cmd_rx) = crossbeam_channel::unbounded();
+        let (resp_tx, resp_rx) = crossbeam_channel::unbounded();
+        let handler = thread::spawn(move || lsl_handler_thread(cmd_rx, resp_tx));
+
+        for _ in 0..3 {
+            cmd_tx
+                .send(LslCommand::ConnectByName(
+                    "DisconnectTest".to_string(),
+                    options.clone(),
+                ))
+                .unwrap();
+            loop {
+                match resp_rx.recv_timeout(Duration::from_secs(10)) {
+                    Ok(LslResponse::Connected { .. }) => break,
+                    Ok(LslResponse::Error(e)) => panic!("{}", e),
+                    Ok(_) => {}
+                    Err(_) => panic!("expected a Connected response"),
+                }
+            }
+            // land somewhere in the middle of a pull_sample
+            thread::sleep(Duration::from_millis(37));
+            let sent = Instant::now();
+            cmd_tx.send(LslCommand::Disconnect).unwrap();
+            loop {
+                match resp_rx.recv_timeout(Duration::from_secs(1)) {
+                    Ok(LslResponse::Disconnected) => break,
+                    Ok(_) => {}
+                    Err(_) => panic!("expected a Disconnected response"),
+                }
+            }
+            assert!(sent.elapsed() < Duration::from_millis(500));
+        }
+
+        drop(cmd_tx);
+        handler.join().unwrap();
+    }
+
+    #[test]
+    fn lsl_thread_exits_when_ui_is_gone() {
+        let (resp_tx, resp_rx) = crossbeam_channel::unbounded();
+        drop(resp_rx);
+        let mut processor = None;
+        let mut recorder = None;
+        assert!(!forward_chunk(
+            vec![vec![1.0, 2.0], vec![3.0, 4.0]],
+            &[0.0, 0.1],
+            &mut processor,
+            &mut recorder,
+            &resp_tx,
+        ));
+
+        let (cmd_tx, cmd_rx) = crossbeam_channel::unbounded();
+        let (resp_tx, resp_rx) = crossbeam_channel::unbounded();
+        let handler = thread::spawn(move || lsl_handler_thread(cmd_rx, resp_tx));
+        drop(resp_rx);
+        cmd_tx.send(LslCommand::Disconnect).unwrap();
+        // the command sender is still alive, so only the failed send can end the thread
+        let started = Instant::now();
+        while !handler.is_finished() {
+            assert!(started.elapsed() < Duration::from_secs(1));
+            thread::sleep(Duration::from_millis(5));
+        }
+        assert!(handler.join().is_ok());
+    }
+}
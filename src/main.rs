@@ -1,399 +1,6443 @@
 // #![windows_subsystem = "windows"]
+#[cfg(feature = "audio")]
+mod audio;
+mod derived;
+mod display_cache;
+mod erp;
+mod event_log;
+mod export;
+mod filter;
+mod headless;
+mod peaks;
+mod playback;
+mod profile;
+mod settings;
+mod snapshot;
+mod spectrogram;
+mod spectrum;
+mod stats;
+#[cfg(feature = "test-source")]
+mod test_source;
+mod xdf;
+mod xml_tree;
+
+#[cfg(feature = "audio")]
+use audio::{AudioErrors, AudioMode, AudioMonitor};
+use crossbeam_channel::{Receiver, Sender};
+use derived::DerivedChannel;
+use display_cache::{CacheKey, ChannelCache};
 use eframe::egui;
 use egui::Stroke;
-use egui_plot::{AxisHints, GridInput, GridMark, Line, Plot, PlotPoints, Points, VLine};
-use lsl::{Pullable, StreamInfo, StreamInlet, XMLElement};
-use std::collections::VecDeque;
-use std::sync::mpsc::{self, Receiver, Sender};
-use std::time::Duration;
+use egui_plot::{
+    AxisHints, GridInput, GridMark, HLine, Line, LineStyle, MarkerShape, Plot, PlotImage,
+    PlotPoint, PlotPoints, Points, Polygon, Text, VLine,
+};
+use erp::ErpAverager;
+use event_log::{EventLog, Severity};
+use export::{Aggregation, ExportJob};
+use filter::{ChannelFilters, FilterSettings, NotchFrequency};
+use lsl_viewer::{
+    BACKLOG_FLUSH_SECONDS, BLOCKING_PULL_MAX_RATE, ConnectOptions, ConnectStage, DataSample,
+    LslCommand, LslResponse, PostprocessingFlag, PullMode, StreamData, default_channel_names,
+    lsl_handler_thread, processing,
+};
+use peaks::{PeakDetector, PeakSettings};
+use processing::SampleProcessor;
+use profile::Profile;
+use settings::{RecentStream, Settings, StreamProfile, Theme};
+use snapshot::Snapshot;
+use spectrogram::{Spectrogram, SpectrogramSettings};
+use stats::ChannelStats;
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
 use std::{f64, thread};
 
 const DEFAULT_TIME_WINDOW_SECONDS: f64 = 2.0; // Show last 10 seconds of data
-const BUFFER_SIZE: i32 = 360;
+const TIME_WINDOW_RANGE: std::ops::RangeInclusive<f64> = 0.25..=120.0; // Seconds; shorter windows make the sweep math degenerate
+const TIME_WINDOW_PRESETS: [f64; 6] = [1.0, 2.0, 5.0, 10.0, 30.0, 60.0]; // Seconds, also stepped through with Left/Right
+const SCALE_KEY_STEP: f64 = 1.25; // Gain factor per Up/Down key press
+const PLAYBACK_SPEEDS: [f64; 6] = [0.25, 0.5, 1.0, 2.0, 4.0, 8.0]; // Multiples of real time
+const UI_SCALE_RANGE: std::ops::RangeInclusive<f32> = 0.5..=3.0; // Zoom on top of the display's own scale factor
+
+/// Keys handled in `handle_shortcuts`, as listed in the help window.
+const SHORTCUTS: [(&str, &str); 9] = [
+    ("Up / Down", "Increase / decrease the gain"),
+    ("Left / Right", "Shorter / longer time window"),
+    ("Space", "Freeze / resume the display"),
+    ("B", "Baseline correction"),
+    ("H", "Hide / show the controls and status bar"),
+    ("A", "Annotate the newest sample with the typed note"),
+    (
+        "Scroll over plot",
+        "Gain of all channels, or of the channel under the pointer",
+    ),
+    ("Ctrl + scroll over plot", "Gain of all channels"),
+    ("F1", "Show this help"),
+];
+const LINE_HIT_DISTANCE: f32 = 6.0; // Points from a marker or annotation line within which the pointer hits it
+const MARKER_COLLAPSE_FRACTION: f64 = 0.01; // Identical markers closer than this part of the window share a label
+const MARKER_TICK_LENGTH: f32 = 8.0; // Points, of the ticks drawn in place of marker lines
 const DEFAULT_SCALE: f64 = 25.0; // Default scale for data visualization
+const DEFAULT_RECORDING_TEMPLATE: &str = "{stream}_{date}_{index}";
+const BAD_CHANNEL_DIMMING: f32 = 0.3; // Opacity of the traces of bad channels
+const SCALE_NORMALIZATION: f64 = 10000.0; // Plotted deflection = value * scale / SCALE_NORMALIZATION
+const SCALE_RANGE: std::ops::RangeInclusive<f64> = 1.0..=1000.0;
+const AMPLITUDE_PRESETS: [f64; 5] = [10.0, 25.0, 50.0, 100.0, 200.0]; // Full-scale (±) amplitudes per lane
 const DEFAULT_DOWN_SAMPLE_FACTOR: usize = 1; // Default downsample factor
+const MAX_DOWN_SAMPLE_FACTOR: usize = 100;
+const AUTO_WINDOW_MIN_SAMPLES: f64 = 100.0; // Slow streams get a window showing at least this many samples
+const AUTO_WINDOW_MAX_SAMPLES: f64 = 20_000.0; // Fast streams get one showing at most this many
+const AUTO_DOWNSAMPLE_RATE: f64 = 5_000.0; // Hz; faster streams are drawn downsampled to about this rate
 const DEFAULT_BASELINE_TIME_WINDOW: f32 = 0.100; // Default baseline time window in seconds
+const DEFAULT_ENVELOPE_WINDOW_MS: f64 = 100.0; // Window of the moving RMS envelope
+const DEFAULT_MOVING_AVERAGE_WINDOW: usize = 10; // Samples
+const MOVING_AVERAGE_WINDOW_RANGE: std::ops::RangeInclusive<usize> = 2..=10_000; // Samples
+const PIXEL_DECIMATION_THRESHOLD: f64 = 2.0; // Samples per pixel column above which traces are reduced to min/max
+const SMOOTHING_STEP_PIXELS: f64 = 3.0; // Horizontal pixels per interpolated point when smoothing traces
+const MAX_SMOOTHING_STEPS: usize = 32; // Most points drawn per segment between two samples
+const X_GRID_SPACING_RANGE: std::ops::RangeInclusive<f64> = 0.01..=60.0; // Seconds between labelled time gridlines
+const GRID_FADE_RANGE: std::ops::RangeInclusive<f32> = 8.0..=300.0; // Pixels between gridlines over which they fade in, as in egui_plot
+const LINE_WIDTH_RANGE: std::ops::RangeInclusive<f32> = 0.5..=4.0; // Trace widths in points
+const ROW_SPACING_RANGE: std::ops::RangeInclusive<f64> = 1.0..=4.0; // Distance between stacked lanes
+const TIME_CORRECTION_HISTORY: usize = 12; // Offsets kept to show how stable the estimate is
+const AUTO_SCALE_PERCENTILE: f64 = 0.95; // Robust amplitude used by the auto scale, ignores rare spikes
+const AUTO_SCALE_LANE_FRACTION: f64 = 0.4; // Fraction of a lane the robust amplitude is mapped to
+const AUTO_SCALE_INTERVAL: Duration = Duration::from_secs(1); // How often the continuous auto scale runs
+const DEFAULT_MAX_AUTO_SELECTED_CHANNELS: usize = 16; // Channels ticked automatically on connect
+const DEFAULT_MAX_DISPLAY_BACKLOG: usize = 5000; // Pending samples per frame before the drop policy kicks in
+const AUTO_BUFFER_WINDOWS: f64 = 2.0; // Automatic per-channel sample limit, in time windows at the nominal rate
+const IRREGULAR_BUFFER_LIMIT: usize = 1_000_000; // Automatic per-channel sample limit for irregular streams
+const HISTORY_RANGE: std::ops::RangeInclusive<f64> = 0.0..=3600.0; // Seconds kept for the overview strip
+const OVERVIEW_COLUMNS: usize = 400; // Min/max columns the buffer is reduced to for the overview strip
+const OVERVIEW_HEIGHT: f32 = 40.0;
+const DEFAULT_MAX_RESPONSES_PER_FRAME: usize = 50_000; // Messages handled per frame, the rest waits for the next one
+const RECENT_STREAMS_LIMIT: usize = 5;
+const RECENT_STREAM_MAX_AGE: Duration = Duration::from_secs(30 * 24 * 3600); // Unused longer than this, it's forgotten
+const NEW_STREAM_BADGE_SECONDS: f64 = 5.0; // How long newly appeared streams are highlighted
+const GAP_THRESHOLD: f64 = 1.5; // A step this many nominal sample intervals long counts as a gap
+const EFFECTIVE_RATE_WINDOW: Duration = Duration::from_secs(5); // Span the delivered sample rate is averaged over
+const RATE_DEVIATION_WARNING: f64 = 0.05; // Relative difference from nominal that is flagged
+const DATA_FRESH_AGE: Duration = Duration::from_millis(200); // Newest sample younger than this: data is flowing
+const DATA_STALE_AGE: Duration = Duration::from_secs(2); // Newest sample older than this: the source has stalled
+const DEFAULT_ALERT_THRESHOLD: f64 = 100.0; // Deflection from the baseline that raises an alert, in the display unit
+const DEFAULT_CLIP_LIMIT: f64 = 1000.0; // Symmetric rail until the amplifier's range is entered, in the display unit
+const DEFAULT_CLIP_RUN: usize = 3; // Consecutive samples at a rail before a channel counts as clipping
+const CLIP_TOLERANCE: f64 = 1e-6; // How close to a rail counts as on it, relative to the range
+const ALERT_HOLD: Duration = Duration::from_secs(1); // How long an unlatched alert stays visible
+const ALERT_BEEP_INTERVAL: Duration = Duration::from_secs(1); // Beeps are at most this frequent
+const TEXT_LOG_LIMIT: usize = 10_000; // Entries kept in the log of a string stream
+const DEFAULT_LATENCY_CAP_MS: f64 = 250.0; // Display latency above which the inlet backlog is dropped, if capped
+const LATENCY_FLUSH_INTERVAL: Duration = Duration::from_secs(1); // Catching up is asked for at most this often
+const STATS_UPDATE_INTERVAL: Duration = Duration::from_millis(250); // Refresh rate of the statistics table
+const DEFAULT_DISPLAY_UPDATE_HZ: f64 = 30.0; // Plot geometry rebuilds per second, independent of the repaint rate
+
+// use pastel colors for channels
+const CHANNEL_COLORS: [egui::Color32; 14] = [
+    egui::Color32::from_rgb(255, 105, 180), // Pink
+    egui::Color32::from_rgb(135, 206, 235), // Sky Blue
+    egui::Color32::from_rgb(255, 215, 0),   // Gold
+    egui::Color32::from_rgb(144, 238, 144), // Light Green
+    egui::Color32::from_rgb(255, 160, 122), // Light Salmon
+    egui::Color32::from_rgb(255, 182, 193), // Light Pink
+    egui::Color32::from_rgb(255, 228, 181), // Moccasin
+    egui::Color32::from_rgb(173, 216, 230), // Light Blue
+    egui::Color32::from_rgb(221, 160, 221), // Plum
+    egui::Color32::from_rgb(255, 99, 71),   // Tomato
+    egui::Color32::from_rgb(255, 140, 0),   // Dark Orange
+    egui::Color32::from_rgb(255, 250, 205), // Lemon Chiffon
+    egui::Color32::from_rgb(240, 230, 140), // Khaki
+    egui::Color32::from_rgb(255, 218, 185), // Peach Puff
+];
+// The pastels above wash out on a light background, these are used there instead
+const CHANNEL_COLORS_LIGHT: [egui::Color32; 14] = [
+    egui::Color32::from_rgb(199, 21, 133), // Medium Violet Red
+    egui::Color32::from_rgb(0, 119, 182),  // Cerulean
+    egui::Color32::from_rgb(184, 134, 11), // Dark Goldenrod
+    egui::Color32::from_rgb(34, 139, 34),  // Forest Green
+    egui::Color32::from_rgb(210, 105, 30), // Chocolate
+    egui::Color32::from_rgb(128, 0, 128),  // Purple
+    egui::Color32::from_rgb(0, 128, 128),  // Teal
+    egui::Color32::from_rgb(178, 34, 34),  // Firebrick
+    egui::Color32::from_rgb(70, 130, 180), // Steel Blue
+    egui::Color32::from_rgb(85, 107, 47),  // Dark Olive Green
+    egui::Color32::from_rgb(255, 69, 0),   // Orange Red
+    egui::Color32::from_rgb(72, 61, 139),  // Dark Slate Blue
+    egui::Color32::from_rgb(139, 69, 19),  // Saddle Brown
+    egui::Color32::from_rgb(0, 100, 0),    // Dark Green
+];
+
+/// Common unit conversions offered in the UI: (label, factor, resulting unit).
+const UNIT_PRESETS: [(&str, f64, &str); 5] = [
+    ("V → µV", 1e6, "µV"),
+    ("mV → µV", 1e3, "µV"),
+    ("V → mV", 1e3, "mV"),
+    ("nV → µV", 1e-3, "µV"),
+    ("µV → mV", 1e-3, "mV"),
+];
+
+/// Content types the stream list can be narrowed down to.
+#[derive(Clone, Copy, PartialEq, Default)]
+enum StreamTypeFilter {
+    #[default]
+    Any,
+    Eeg,
+    Markers,
+    Audio,
+}
+
+impl StreamTypeFilter {
+    const ALL: [StreamTypeFilter; 4] = [
+        StreamTypeFilter::Any,
+        StreamTypeFilter::Eeg,
+        StreamTypeFilter::Markers,
+        StreamTypeFilter::Audio,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            StreamTypeFilter::Any => "Any",
+            StreamTypeFilter::Eeg => "EEG",
+            StreamTypeFilter::Markers => "Markers",
+            StreamTypeFilter::Audio => "Audio",
+        }
+    }
+
+    fn matches(self, stream_type: &str) -> bool {
+        self == StreamTypeFilter::Any || stream_type.eq_ignore_ascii_case(self.label())
+    }
+}
+
+/// Order of the stream list, so entries don't jump around between refreshes.
+#[derive(Clone, Copy, PartialEq, Default)]
+enum StreamSort {
+    #[default]
+    Name,
+    Type,
+    ChannelCount,
+    SampleRate,
+}
+
+impl StreamSort {
+    const ALL: [StreamSort; 4] = [
+        StreamSort::Name,
+        StreamSort::Type,
+        StreamSort::ChannelCount,
+        StreamSort::SampleRate,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            StreamSort::Name => "By name",
+            StreamSort::Type => "By type",
+            StreamSort::ChannelCount => "By channels",
+            StreamSort::SampleRate => "By rate",
+        }
+    }
+
+    /// Ties keep resolution order, since the sort is stable.
+    fn compare(self, a: &StreamData, b: &StreamData) -> std::cmp::Ordering {
+        match self {
+            StreamSort::Name => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+            StreamSort::Type => a
+                .stream_type
+                .to_lowercase()
+                .cmp(&b.stream_type.to_lowercase()),
+            StreamSort::ChannelCount => a.channel_count.cmp(&b.channel_count),
+            StreamSort::SampleRate => a.sample_rate.total_cmp(&b.sample_rate),
+        }
+    }
+}
+
+/// What to do with a sample whose timestamp isn't after the newest buffered one.
+#[derive(Clone, Copy, PartialEq, Default)]
+enum TimestampRegressionPolicy {
+    #[default]
+    DropSample,
+    ResetBuffer,
+    /// Keep the sample, moved to one sample interval after the newest one.
+    Monotonize,
+}
+
+impl TimestampRegressionPolicy {
+    const ALL: [TimestampRegressionPolicy; 3] = [
+        TimestampRegressionPolicy::DropSample,
+        TimestampRegressionPolicy::ResetBuffer,
+        TimestampRegressionPolicy::Monotonize,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            TimestampRegressionPolicy::DropSample => "Drop sample",
+            TimestampRegressionPolicy::ResetBuffer => "Reset buffer",
+            TimestampRegressionPolicy::Monotonize => "Move after newest",
+        }
+    }
+}
+
+/// What to do with a sample whose channel count differs from the connected stream's.
+#[derive(Clone, Copy, PartialEq, Default)]
+enum ChannelCountChangePolicy {
+    /// Adopt the new channel count, clearing the buffered data.
+    #[default]
+    Resize,
+    /// Keep the current layout and drop the sample.
+    Reject,
+}
+
+impl ChannelCountChangePolicy {
+    fn label(self) -> &'static str {
+        match self {
+            ChannelCountChangePolicy::Resize => "Resize buffers",
+            ChannelCountChangePolicy::Reject => "Reject sample",
+        }
+    }
+}
+
+/// How channel gains are chosen in the stacked layout.
+#[derive(Clone, Copy, PartialEq, Default)]
+enum LaneScaling {
+    /// Every lane uses its channel's gain from `channel_scales`, so amplitudes are comparable.
+    #[default]
+    Shared,
+    /// Each lane is normalized so its trace fills the lane.
+    Independent,
+}
+
+/// How the traces of the selected channels are arranged vertically.
+#[derive(Clone, Copy, PartialEq, Default)]
+enum PlotLayout {
+    /// Each channel in its own lane, scaled by its gain.
+    #[default]
+    Stacked,
+    /// All channels on one y-axis in their real units.
+    Overlay,
+}
+
+/// How groups of samples are reduced to plot points when resampling the display.
+#[derive(Clone, Copy, PartialEq, Default)]
+enum DecimationMethod {
+    /// Keep the first sample of each group; cheap, but aliases fast content.
+    Step,
+    /// Average of each group.
+    #[default]
+    Mean,
+    /// Smallest and largest value of each group, so spikes stay visible.
+    MinMax,
+}
+
+impl DecimationMethod {
+    const ALL: [DecimationMethod; 3] = [
+        DecimationMethod::Step,
+        DecimationMethod::Mean,
+        DecimationMethod::MinMax,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            DecimationMethod::Step => "Step",
+            DecimationMethod::Mean => "Mean",
+            DecimationMethod::MinMax => "Min/max",
+        }
+    }
+}
+
+/// Reduce `(timestamp, value)` points by `factor` using `method`.
+fn decimate(points: &[(f64, f64)], factor: usize, method: DecimationMethod) -> Vec<(f64, f64)> {
+    if factor <= 1 {
+        return points.to_vec();
+    }
+    let groups = points.chunks(factor);
+    match method {
+        DecimationMethod::Step => groups.map(|group| group[0]).collect(),
+        DecimationMethod::Mean => groups
+            .map(|group| {
+                let n = group.len() as f64;
+                let (t, v) = group
+                    .iter()
+                    .fold((0.0, 0.0), |(t, v), &(ti, vi)| (t + ti, v + vi));
+                (t / n, v / n)
+            })
+            .collect(),
+        DecimationMethod::MinMax => {
+            let mut decimated = Vec::with_capacity(2 * points.len().div_ceil(factor));
+            for group in groups {
+                let mut min = 0;
+                let mut max = 0;
+                for (i, &(_, v)) in group.iter().enumerate() {
+                    if v < group[min].1 {
+                        min = i;
+                    }
+                    if v > group[max].1 {
+                        max = i;
+                    }
+                }
+                // both extremes at their own time, in order, so the line goes through them
+                decimated.push(group[min.min(max)]);
+                if min != max {
+                    decimated.push(group[min.max(max)]);
+                }
+            }
+            decimated
+        }
+    }
+}
+
+/// Moving RMS of each trace, the usual view of muscle activity.
+#[derive(Clone, Copy, PartialEq, Default)]
+enum EnvelopeMode {
+    #[default]
+    Off,
+    /// Envelope drawn over the raw trace.
+    Overlay,
+    /// Envelope in place of the raw trace.
+    Only,
+}
+
+impl EnvelopeMode {
+    const ALL: [EnvelopeMode; 3] = [EnvelopeMode::Off, EnvelopeMode::Overlay, EnvelopeMode::Only];
+
+    fn label(self) -> &'static str {
+        match self {
+            EnvelopeMode::Off => "No envelope",
+            EnvelopeMode::Overlay => "RMS envelope over trace",
+            EnvelopeMode::Only => "RMS envelope only",
+        }
+    }
+}
+
+/// Root mean square over the trailing `window` samples, at every sample.
+/// Non-finite samples are left out; where there are only those, the result is NaN.
+fn moving_rms(points: &[(f64, f64)], window: usize) -> Vec<(f64, f64)> {
+    moving_mean(points, window, |v| v * v)
+        .into_iter()
+        // rounding can leave a tiny negative sum after a loud stretch
+        .map(|(t, mean_square)| (t, mean_square.max(0.0).sqrt()))
+        .collect()
+}
+
+/// Mean over the trailing `window` samples, at every sample. Non-finite samples
+/// are left out; where there are only those, the result is NaN.
+fn moving_average(points: &[(f64, f64)], window: usize) -> Vec<(f64, f64)> {
+    moving_mean(points, window, |v| v)
+}
+
+/// Running mean of `f(value)` over the trailing `window` finite samples.
+fn moving_mean(points: &[(f64, f64)], window: usize, f: impl Fn(f64) -> f64) -> Vec<(f64, f64)> {
+    let window = window.max(1);
+    let mut sum = 0.0;
+    let mut count = 0usize;
+    points
+        .iter()
+        .enumerate()
+        .map(|(i, &(t, v))| {
+            if v.is_finite() {
+                sum += f(v);
+                count += 1;
+            }
+            let old = i.checked_sub(window).map(|j| points[j].1);
+            if let Some(old) = old.filter(|old| old.is_finite()) {
+                sum -= f(old);
+                count -= 1;
+            }
+            let mean = if count > 0 {
+                sum / count as f64
+            } else {
+                f64::NAN
+            };
+            (t, mean)
+        })
+        .collect()
+}
+
+/// The runs of finite points, drawn as separate lines so that non-finite
+/// values leave gaps in a trace.
+fn finite_runs(points: &[PlotPoint]) -> impl Iterator<Item = &[PlotPoint]> {
+    points
+        .split(|p| !p.y.is_finite())
+        .filter(|run| !run.is_empty())
+}
+
+/// Mapping of time onto the x-axis.
+#[derive(Clone, Copy, PartialEq, Default)]
+enum TimeAxisScale {
+    /// Oscilloscope-style sweep with evenly spaced time.
+    #[default]
+    Linear,
+    /// Log-spaced age, newest at the right, so recent data gets more width.
+    RecentEmphasis,
+}
+
+impl TimeAxisScale {
+    fn x_axis_label(self) -> &'static str {
+        match self {
+            TimeAxisScale::Linear => "Time (seconds)",
+            TimeAxisScale::RecentEmphasis => "Age (seconds, log-spaced)",
+        }
+    }
+}
+
+/// How durations are written in the stats line.
+#[derive(Clone, Copy, PartialEq, Default)]
+enum DurationStyle {
+    #[default]
+    Seconds,
+    MinutesSeconds,
+}
+
+impl DurationStyle {
+    const ALL: [DurationStyle; 2] = [DurationStyle::Seconds, DurationStyle::MinutesSeconds];
+
+    fn label(self) -> &'static str {
+        match self {
+            DurationStyle::Seconds => "Seconds",
+            DurationStyle::MinutesSeconds => "mm:ss",
+        }
+    }
+
+    fn format(self, seconds: f64) -> String {
+        match self {
+            DurationStyle::Seconds => format!("{:.2} s", seconds),
+            DurationStyle::MinutesSeconds => {
+                let total = seconds.max(0.0).round() as u64;
+                format!("{:02}:{:02}", total / 60, total % 60)
+            }
+        }
+    }
+}
+
+/// Unit the linear time axis of a sweep is labelled in, picked from the window
+/// length so the ticks stay readable.
+#[derive(Clone, Copy, PartialEq)]
+enum TimeUnit {
+    Milliseconds,
+    Seconds,
+    Minutes,
+}
+
+impl TimeUnit {
+    fn for_window(window: f64) -> Self {
+        if window <= 2.0 {
+            TimeUnit::Milliseconds
+        } else if window > 120.0 {
+            TimeUnit::Minutes
+        } else {
+            TimeUnit::Seconds
+        }
+    }
+
+    fn axis_label(self) -> &'static str {
+        match self {
+            TimeUnit::Milliseconds => "Time (ms)",
+            TimeUnit::Seconds => "Time (seconds)",
+            TimeUnit::Minutes => "Time (min:s)",
+        }
+    }
+
+    fn format(self, seconds: f64) -> String {
+        match self {
+            TimeUnit::Milliseconds => format!("{:.0}", seconds * 1000.0),
+            TimeUnit::Seconds => format!("{}", (seconds * 1000.0).round() / 1000.0),
+            TimeUnit::Minutes => {
+                let total = seconds.round() as i64;
+                format!("{}:{:02}", total / 60, total % 60)
+            }
+        }
+    }
+
+    /// Distance between labelled ticks for about eight of them across `window`.
+    fn tick_step(self, window: f64) -> f64 {
+        match self {
+            // whole minutes and their usual fractions
+            TimeUnit::Minutes => [10.0, 15.0, 30.0, 60.0, 120.0, 300.0, 600.0]
+                .into_iter()
+                .find(|&step| step >= window / 8.0)
+                .unwrap_or(600.0),
+            _ => round_tick_value(window / 8.0).max(0.001),
+        }
+    }
+}
+
+/// Labelled ticks every `step` seconds within `bounds`, with fainter ones at a fifth of that.
+/// They fall where `origin + value`, the time the axis shows, is a multiple of the step.
+fn time_grid_marks(bounds: (f64, f64), step: f64, origin: f64) -> Vec<GridMark> {
+    let minor = step / 5.0;
+    let (first, last) = (
+        ((bounds.0 + origin) / minor).ceil() as i64,
+        ((bounds.1 + origin) / minor).floor() as i64,
+    );
+    // a wildly zoomed-out frozen plot shouldn't produce millions of marks
+    if last - first > 10_000 {
+        return Vec::new();
+    }
+    (first..=last)
+        .map(|i| GridMark {
+            value: i as f64 * minor - origin,
+            step_size: if i % 5 == 0 { step } else { minor },
+        })
+        .collect()
+}
+
+/// A mark at the center of each of `lanes` stacked lanes, and with `lane_grid`
+/// fainter ones halfway to their edges.
+fn lane_grid_marks(lanes: usize, row_spacing: f64, lane_grid: bool) -> Vec<GridMark> {
+    let mut marks = Vec::new();
+    for i in 0..lanes {
+        let center = -row_spacing * i as f64;
+        marks.push(GridMark {
+            value: center,
+            step_size: row_spacing,
+        });
+        if lane_grid {
+            // a smaller step makes egui_plot draw these fainter
+            for sub in [-0.25, 0.25] {
+                marks.push(GridMark {
+                    value: center + sub * row_spacing,
+                    step_size: 0.25 * row_spacing,
+                });
+            }
+        }
+    }
+    marks
+}
+
+/// Marks at 0 and every power of ten within `bounds` of a signed-log axis.
+fn signed_log_grid_marks(bounds: (f64, f64)) -> Vec<GridMark> {
+    let (min, max) = bounds;
+    let mut marks = vec![GridMark {
+        value: 0.0,
+        step_size: 1.0,
+    }];
+    for decade in 0..=(min.abs().max(max.abs()).ceil() as i32).min(30) {
+        for sign in [1.0, -1.0] {
+            let value = signed_log_value(sign * 10f64.powi(decade));
+            if (min..=max).contains(&value) {
+                marks.push(GridMark {
+                    value,
+                    step_size: 1.0,
+                });
+            }
+        }
+    }
+    marks
+}
+
+/// How the time axis follows new data.
+#[derive(Clone, Copy, PartialEq, Default)]
+enum PlotScrollMode {
+    /// Oscilloscope-style: new data overwrites the previous sweep from the left.
+    #[default]
+    Sweep,
+    /// The newest sample stays at the right edge and older data moves left.
+    Scroll,
+}
 
-#[derive(Clone)]
-struct StreamData {
+/// What happens to the previous sweep when the sweep wraps at the right edge.
+#[derive(Clone, Copy, PartialEq, Default)]
+enum SweepBoundary {
+    /// The new sweep overwrites the old one from the left.
+    #[default]
+    Overwrite,
+    /// The old sweep is cleared and the plot starts empty.
+    Clear,
+}
+
+impl SweepBoundary {
+    const ALL: [SweepBoundary; 2] = [SweepBoundary::Overwrite, SweepBoundary::Clear];
+
+    fn label(self) -> &'static str {
+        match self {
+            SweepBoundary::Overwrite => "Overwrite",
+            SweepBoundary::Clear => "Clear",
+        }
+    }
+}
+
+/// Whether the sweep wrapped between two sweep positions.
+fn sweep_wrapped(previous_position: f64, position: f64) -> bool {
+    position < previous_position
+}
+
+/// What the central plot shows.
+#[derive(Clone, Copy, PartialEq, Default)]
+enum PlotMode {
+    #[default]
+    Time,
+    Spectrum,
+    Spectrogram,
+    Erp,
+    Digits,
+}
+
+/// Timestamp at x = 0 of the sweep that `latest` falls in: the last multiple of `window`.
+fn sweep_start(latest: f64, window: f64) -> f64 {
+    latest - latest.rem_euclid(window)
+}
+
+/// Sweep position of a sample, and whether it belongs to the current sweep (the
+/// one `latest` falls in) rather than the remainder of the previous one. A sample
+/// exactly at the sweep start is the first of the current sweep.
+fn map_to_window(timestamp: f64, latest: f64, window: f64) -> (f64, bool) {
+    let offset = timestamp - sweep_start(latest, window);
+    if offset >= 0.0 {
+        (offset, true)
+    } else {
+        (offset.rem_euclid(window), false)
+    }
+}
+
+/// Time constant of the recent-emphasis axis as a fraction of the window:
+/// ages below this are roughly linear, older ones are compressed.
+const RECENT_EMPHASIS_KNEE: f64 = 0.05;
+
+/// x-position (0..=window, newest at `window`) of a sample `age` seconds old.
+fn recent_emphasis_x(age: f64, window: f64) -> f64 {
+    let tau = window * RECENT_EMPHASIS_KNEE;
+    let span = (window / tau).ln_1p();
+    window * (1.0 - (age.max(0.0) / tau).ln_1p() / span)
+}
+
+/// Inverse of [`recent_emphasis_x`]: the age shown at x-position `x`.
+fn recent_emphasis_age(x: f64, window: f64) -> f64 {
+    let tau = window * RECENT_EMPHASIS_KNEE;
+    let span = (window / tau).ln_1p();
+    tau * (((1.0 - x / window) * span).exp() - 1.0)
+}
+
+/// What to do with pending samples when the UI falls behind the stream.
+///
+/// Only the display path is affected; the LSL thread still receives every sample.
+#[derive(Clone, Copy, PartialEq, Default)]
+enum SampleDropPolicy {
+    #[default]
+    DropOldest,
+    DropNewest,
+    Coalesce,
+}
+
+impl SampleDropPolicy {
+    const ALL: [SampleDropPolicy; 3] = [
+        SampleDropPolicy::DropOldest,
+        SampleDropPolicy::DropNewest,
+        SampleDropPolicy::Coalesce,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            SampleDropPolicy::DropOldest => "Drop oldest",
+            SampleDropPolicy::DropNewest => "Drop newest",
+            SampleDropPolicy::Coalesce => "Coalesce",
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            SampleDropPolicy::DropOldest => {
+                "Skip the oldest pending samples so the display jumps straight to live data. \
+                 Lowest latency, but short events in the skipped span are never shown."
+            }
+            SampleDropPolicy::DropNewest => {
+                "Show the pending samples in order and discard what doesn't fit this frame. \
+                 No gaps inside the shown span, but the display lags behind and loses recent data."
+            }
+            SampleDropPolicy::Coalesce => {
+                "Average groups of consecutive pending samples into one. \
+                 Covers the whole span without gaps, at the cost of smoothing out fast transients."
+            }
+        }
+    }
+}
+
+/// State of the file being played back, as seen by the UI.
+struct PlaybackStatus {
     name: String,
-    channel_count: usize,
-    sample_rate: f64,
+    position: f64,
+    duration: f64,
+    paused: bool,
+    speed: f64,
+    seeking: bool, // The position slider is being dragged
+}
+
+/// How event marker labels are drawn, for when they crowd each other.
+#[derive(Clone, Copy, PartialEq, Default)]
+enum MarkerLabels {
+    #[default]
+    Inline,
+    /// Only the label of the marker under the pointer.
+    Hover,
+    Rotated,
+    /// Short ticks numbered by text, with a legend of the numbers.
+    Legend,
+}
+
+impl MarkerLabels {
+    const ALL: [MarkerLabels; 4] = [
+        MarkerLabels::Inline,
+        MarkerLabels::Hover,
+        MarkerLabels::Rotated,
+        MarkerLabels::Legend,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            MarkerLabels::Inline => "Inline",
+            MarkerLabels::Hover => "On hover",
+            MarkerLabels::Rotated => "Rotated",
+            MarkerLabels::Legend => "Ticks + legend",
+        }
+    }
+}
+
+/// Timestamp and text of the marker or annotation line nearest to `pointer`, if
+/// it is within `LINE_HIT_DISTANCE` of it.
+fn line_at<'a>(
+    plot_ui: &egui_plot::PlotUi,
+    pointer: egui::Pos2,
+    lines: &'a [(f64, f64, String)],
+) -> Option<(f64, &'a String)> {
+    lines
+        .iter()
+        .map(|(x, timestamp, text)| {
+            let line_x = plot_ui.screen_from_plot(PlotPoint::new(*x, 0.0)).x;
+            ((line_x - pointer.x).abs(), *timestamp, text)
+        })
+        .filter(|(distance, ..)| *distance <= LINE_HIT_DISTANCE)
+        .min_by(|a, b| a.0.total_cmp(&b.0))
+        .map(|(_, timestamp, text)| (timestamp, text))
+}
+
+/// A marker label, counting the identical markers collapsed into it.
+fn marker_label(text: &str, count: usize) -> String {
+    if count > 1 {
+        format!("{} ×{}", text, count)
+    } else {
+        text.to_string()
+    }
+}
+
+/// State of an active recording, as seen by the UI.
+struct RecordingStatus {
+    path: PathBuf,
+    started: Instant,
+    samples_written: usize,
 }
 
-#[derive(Clone)]
-struct DataSample {
-    timestamp: f64,
-    values: Vec<f32>,
+/// Plot-space points of one channel's trace.
+struct TraceGeometry {
+    channel: usize,
+    lane_center: f64,
+    lane_height: f64,                 // Row spacing the lane was laid out with
+    current: Vec<PlotPoint>,          // Current sweep
+    previous: Vec<PlotPoint>,         // Remainder of the previous sweep, drawn greyed out
+    envelope_current: Vec<PlotPoint>, // Moving RMS, split into sweeps like the trace; empty when off
+    envelope_previous: Vec<PlotPoint>,
+    average_current: Vec<PlotPoint>, // Moving average, split into sweeps like the trace; empty when off
+    average_previous: Vec<PlotPoint>,
+    off_scale_up: bool,
+    off_scale_down: bool,
+    // What was applied to the values, so plot coordinates can be mapped back
+    baseline: f64,
+    scale: f64,
+    offset: f64,
+    signed_log: bool, // Overlay values were plotted as `signed_log_value(value)`
+    z_score: Option<(f64, f64)>, // Mean and standard deviation overlay values were normalized with
 }
 
-enum LslCommand {
-    RefreshStreams,
-    Connect(usize), // Index of stream to connect to
-    Disconnect,
+/// Everything the plot draws, rebuilt at the display update rate and reused in between.
+#[derive(Default)]
+struct PlotGeometry {
+    traces: Vec<TraceGeometry>,
+    t_last: f64,
+    sweep_start: f64,                     // Timestamp at x = 0 of the current sweep
+    latest_timestamp: f64,                // Timestamp of the newest sample
+    markers: Vec<(f64, f64, String)>,     // x-position, timestamp and text of each marker in view
+    annotations: Vec<(f64, f64, String)>, // x-position, timestamp and text of each annotation in view
+    gaps: Vec<(f64, f64)>,                // x-positions of the start and end of each gap in view
+    clips: Vec<(usize, f64, f64)>,        // Channel and x-positions of each clipped stretch in view
+    selection: Vec<(f64, f64)>, // x-positions of the start and end of the statistics range
+    peaks: Vec<PlotPoint>,      // Detected peaks in view, placed on their trace
+    overview: Overview,
+    marker_labels: Vec<(f64, String, usize)>, // x-position, text and marker count of each label
+    points_drawn: usize,
+    points_available: usize,
 }
 
-enum LslResponse {
-    StreamsFound(Vec<StreamData>),
-    Connected(String, Vec<String>), // Stream name and channel names
-    Disconnected,
-    Error(String),
-    Data(DataSample),
+/// Lowest and highest value in one column of the overview strip, scaled to 0..1;
+/// None for a column without samples.
+type OverviewColumn = Option<(f32, f32)>;
+
+/// The whole buffer in miniature, for the overview strip below the plot.
+#[derive(Default)]
+struct Overview {
+    first: f64,                                // Timestamp at the left edge
+    last: f64,                                 // Timestamp at the right edge
+    traces: Vec<(usize, Vec<OverviewColumn>)>, // Channel and its columns
 }
 
 #[derive(Default)]
 struct LslViewer {
     // Connection state
     available_streams: Vec<StreamData>,
-    selected_stream_index: Option<usize>,
+    streams_listed: bool, // At least one stream listing has arrived
+    new_streams: HashMap<String, Instant>, // Keys of streams that appeared since the previous listing
+    stream_name_filter: String,
+    stream_type_filter: StreamTypeFilter,
+    stream_sort: StreamSort,
+    // Restrict the listing to one machine or source; applied by LSL when resolving
+    hostname_filter: String,
+    source_id_filter: String,
+    selected_stream: Option<StreamData>, // Listing of the stream connected or being connected to
     is_connected: bool,
+    connect_stage: Option<ConnectStage>, // Set while a connect is in progress
+    reconnecting: Option<(String, u32)>, // Stream name and attempt while a lost stream is looked for
+    connect_options: ConnectOptions,
+    metadata_available: bool,
+    stream_xml: Option<(String, Option<xml_tree::XmlNode>)>, // Raw and parsed stream info
+    show_stream_xml: bool,
+    show_raw_xml: bool,
 
     // Channel data
     channel_names: Vec<String>,
+    channel_units: Vec<String>, // Per channel, as declared in the metadata
+    channel_types: Vec<String>, // Per channel, as declared in the metadata
+    declared_unit: String,      // Unit shared by all channels, empty if unknown or mixed
+    unit_factor: f64,           // Incoming values are multiplied by this before display and export
+    display_unit: String,       // Unit after conversion
 
     // Channel selection
     channel_count: usize,
     selected_channels: Vec<bool>,
+    // Temporary hiding on top of the selection, like a mixer's mute and solo
+    muted_channels: Vec<bool>,
+    solo_channel: Option<usize>,
+    // Still plotted, dimmed, but left out of the common average, auto-scale and statistics
+    bad_channels: Vec<bool>,
+    max_auto_selected_channels: usize,
+    auto_select_types: String, // Comma-separated channel types ticked on connect, empty for any
+    channel_name_filter: String, // Only channels whose name contains this are listed
 
     // Data visualization parameters
-    data_scale: f64,
+    data_scale: f64, // Master gain, applied to every channel while `link_scales` is on
+    channel_scales: Vec<f64>, // Gain of each channel
+    link_scales: bool,
+    auto_scale_continuous: bool,
+    last_auto_scale: Option<Instant>,
+    lane_scaling: LaneScaling,
     time_window_seconds: f64,
+    time_axis_scale: TimeAxisScale,
+    scroll_mode: PlotScrollMode,
+    plot_mode: PlotMode,
+    plot_layout: PlotLayout,
+    reverse_order: bool, // Stack the last channel at the top instead of the first
+    line_width: f32,
+    // How the remainder of the previous sweep and the sweep position are drawn
+    previous_sweep_in_channel_color: bool,
+    previous_sweep_color: egui::Color32,
+    auto_x_grid: bool,   // Time gridlines at a spacing that fits the window
+    x_grid_spacing: f64, // Seconds between labelled time gridlines otherwise
+    grid_in_theme_color: bool,
+    grid_color: egui::Color32, // Gridlines otherwise, the alpha sets their opacity
+    show_sweep_line: bool,
+    sweep_boundary: SweepBoundary,
+    baseline_on_wrap: bool, // Recompute the stacked baselines every time the sweep wraps
+    acquisition_paused: bool, // The LSL thread was asked to stop pulling
+    log_y: bool,            // Overlay layout on a signed log axis
+    z_score: bool,          // Overlay each channel normalized to zero mean and unit variance
+    show_scale_bar: bool,
+    row_spacing: f64, // Distance between the centres of stacked lanes
+    crosshair: bool,  // Read out every shown channel at the time under the pointer
+    lane_grid: bool,  // Gridlines, zero line and round-value ticks inside each stacked lane
     downsample_factor: usize,
+    decimation: DecimationMethod,
+    pixel_decimation: bool, // Reduce dense traces to min/max per pixel column
+    anti_alias: bool,       // Low-pass at the downsampled Nyquist frequency before decimating
+    smooth_traces: bool,    // Draw sparse traces as curves through the samples; display only
+    envelope_mode: EnvelopeMode,
+    envelope_window_ms: f64,
+    moving_average: bool, // Boxcar-smoothed trace over the raw one; display only
+    moving_average_window: usize,
     reference_channel: Option<usize>,
+    derived_channels: Vec<DerivedChannel>, // Appended after the stream's channels, in this order
+    difference_pick: (usize, usize),       // Channels chosen for the next difference channel
+    common_average: bool,                  // Subtract the mean of the selected channels on arrival
+    display_update_hz: f64,                // How often the plot geometry is rebuilt
+    show_y_axis_title: bool, // Off by default, the lanes are already labelled with channel names
+    y_axis_title: String,
+    plot_geometry: PlotGeometry,
+    frozen: bool, // Display stopped; incoming samples are discarded until unfrozen
+    snap_back_to_live: bool,
+    last_geometry_build: Option<Instant>,
+    geometry_build_time: Duration, // How long the last rebuild took, shown in the diagnostics
+    display_caches: Vec<ChannelCache>, // Per channel, indexed like data_buffer
+    reference_traces: Option<HashMap<usize, Vec<[f64; 2]>>>, // Held traces by channel: x and value
 
     // Data storage - now storing (timestamp, value) pairs
     //data_buffer: Vec<VecDeque<(f64, f32)>>,
     data_buffer: Vec<VecDeque<f32>>, // Buffer for each channel
     timestamp_buffer: VecDeque<f64>, // Separate buffer for timestamps
     channel_baselines: Vec<f64>,
+    filter_settings: FilterSettings,
+    filters: ChannelFilters, // Per-channel filter state, applied as samples arrive
+    filter_bypass: Vec<bool>, // Channels the filters leave alone
+    timestamp_regression_policy: TimestampRegressionPolicy,
+    timestamp_regressions: usize,
+    channel_count_policy: ChannelCountChangePolicy,
+    channel_count_changes: usize,
+    marker_buffer: VecDeque<(f64, String)>, // Events from the marker stream, pruned like the data
+    marker_stream: Option<String>,          // Name of the connected marker stream
+    marker_labels: MarkerLabels,
+    collapse_markers: bool, // Identical markers close together share one label
+    annotations: VecDeque<(f64, String)>, // Timestamp and text of each manual annotation, pruned like the data
+    annotation_note: String,              // Text of the next annotation
+    annotation_count: usize,              // Annotations made so far, numbering those without a note
+    editing_annotation: Option<(f64, String, String)>, // Timestamp, text and edited text of the annotation being edited
+    alerts_enabled: bool,
+    alert_threshold: f64,
+    alert_latch: bool, // Alerts stay until cleared instead of fading after ALERT_HOLD
+    alert_beep: bool,
+    channel_alerts: Vec<Option<Instant>>, // When each channel last exceeded the threshold
+    last_alert_beep: Option<Instant>,
+    clip_detection: bool,
+    clip_min: f64,
+    clip_max: f64,
+    clip_run: usize,                // Samples at a rail before it counts as clipping
+    clip_counts: Vec<(usize, f64)>, // Per channel, samples in the current run at a rail and when it began
+    clip_buffer: VecDeque<(usize, f64, f64)>, // Channel, start and end timestamps of clipping, pruned like the data
+    nonfinite_seen: Vec<Option<f64>>, // Per channel, timestamp of the newest NaN or infinite value
+    last_finite: Vec<f32>, // Per channel, what the filters get in place of a non-finite value
+    peak_channel: Option<usize>, // Channel searched for peaks, None when off
+    peak_settings: PeakSettings,
+    peak_detector: PeakDetector,
+    peak_buffer: VecDeque<(f64, f32)>, // Timestamp and value of each detected peak, pruned like the data
+    erp: Option<ErpAverager>,          // Epochs around markers; None without a regular sample rate
+    erp_pre_seconds: f64,
+    erp_post_seconds: f64,
+    spectrogram: Option<Spectrogram>, // Only while the spectrogram is shown, for a regular sample rate
+    spectrogram_settings: SpectrogramSettings,
+    digits_channel: usize,
+    digits_range: Option<(f64, f64)>, // Lowest and highest baseline-corrected value since the last reset
+    digits_sparkline: bool,
+    gap_buffer: VecDeque<(f64, f64)>, // Start and end timestamps of timing gaps, pruned like the data
+    gap_count: usize,
+    last_arrival_timestamp: Option<f64>, // Newest timestamp seen by the gap detection
+    arrivals: VecDeque<(Instant, usize)>, // Samples received per frame, for the effective rate
+    last_data_instant: Option<Instant>,  // When the most recent sample arrived
+    channel_offsets: Vec<f64>, // Vertical offset of each trace within its lane, in lane units
+    channel_order: Vec<usize>, // Channel index of each display row, rearranged in the channel list
+    dragged_channel: Option<usize>,
 
     // Communication channels
     command_sender: Option<Sender<LslCommand>>,
     response_receiver: Option<Receiver<LslResponse>>,
 
+    // Display load handling
+    drop_policy: SampleDropPolicy,
+    max_display_backlog: usize,
+    dropped_display_samples: usize,
+    max_responses_per_frame: usize,
+    carried_over_responses: usize, // Messages left in the queue by the per-frame cap
+    custom_buffer_limit: bool,
+    max_buffer_samples: usize, // Per-channel limit when `custom_buffer_limit` is on
+    history_seconds: f64,      // Kept beyond the time window for the overview strip
+    view_end: Option<f64>, // Timestamp at the end of the shown window while frozen, None for the newest
+
+    // Inlet postprocessing as requested and as actually accepted by the inlet
+    requested_postprocessing: Vec<PostprocessingFlag>,
+    applied_postprocessing: Vec<PostprocessingFlag>,
+    time_corrections: VecDeque<f64>, // Recent clock offsets of the source, newest last
+    pull_stats: Option<(f64, f64, usize)>, // Chunks per second, average and largest chunk size
+    inlet_backlog: Option<(usize, bool)>, // Samples waiting in the inlet, and whether that grew
+    latency_cap: bool, // Drop the inlet backlog when the display falls behind by more than latency_cap_ms
+    latency_cap_ms: f64,
+    last_latency_flush: Option<Instant>,
+
+    // Audio monitoring of a single channel
+    #[cfg(feature = "audio")]
+    audio_monitor: Option<AudioMonitor>,
+    #[cfg(feature = "audio")]
+    audio_channel: usize,
+    #[cfg(feature = "audio")]
+    audio_volume: f32,
+    #[cfg(feature = "audio")]
+    audio_mode: AudioMode,
+    #[cfg(feature = "audio")]
+    audio_pitch_range: (f32, f32), // Hz for the bottom and top of the signal in pitch mode
+    #[cfg(feature = "audio")]
+    audio_errors: AudioErrors, // Reported by the output threads, shown in the status bar and log
+
+    // Recording to disk, running on the LSL thread
+    recording: Option<RecordingStatus>,
+    recording_directory: Option<PathBuf>, // Pre-selected in the recording's file dialog
+    recording_template: String,           // File name of new recordings, see `recording_file_name`
+    playback: Option<PlaybackStatus>,
+    // The LSL thread's channels, set aside while a file is played back in its place
+    live_channels: Option<(Sender<LslCommand>, Receiver<LslResponse>)>,
+
+    // Background export
+    export_job: Option<ExportJob>,
+    export_downsample_factor: usize, // Independent of the display downsampling
+    export_aggregation: Aggregation,
+
+    // Snapshot gallery
+    snapshots: Vec<Snapshot>,
+    snapshot_requested: bool,
+    show_gallery: bool,
+    show_shortcuts: bool,
+    show_log: bool,
+    event_log: EventLog, // Every status message, plus events that don't replace it
+    log_min_severity: Severity,
+    plot_rect: Option<egui::Rect>,
+    plot_capture_rect: Option<egui::Rect>, // The plot with its axes, for saved images
+    image_save_requested: bool,
+    image_scale: u32, // Saved images are enlarged this many times
+
+    // Statistics of the buffered data, recomputed a few times per second
+    channel_stats: Vec<(usize, ChannelStats)>,
+    stats_range: Option<(f64, f64)>, // Start and end timestamps selected with Shift-drag on the plot
+    range_drag_start: Option<f64>,   // Timestamp where the Shift-drag began
+    last_stats_update: Option<Instant>,
+
+    // Diagnostics
+    sequence_channel: Option<usize>, // Channel carrying a per-sample counter
+    last_sequence_value: Option<f64>,
+    sequence_skipped: usize, // Samples missing according to the counter
+    sequence_resets: usize,  // Times the counter repeated or went backwards
+    clock_reference: Option<(f64, Instant)>, // local_clock() and monotonic OS time at startup
+    irregular: bool, // The stream has no nominal rate: scrolling axis, points, no filters or decimation
+    text_stream: bool, // The stream carries strings, shown as a log instead of the plot
+    text_log: VecDeque<(f64, String)>,
+    wall_clock_offset: Option<f64>, // Unix time minus local_clock(), taken at connect
+    show_wall_clock: bool,          // Show timestamps as time of day instead of LSL seconds
+    show_source_clock: bool, // Undo ClockSync when showing timestamps, using the latest clock offset
+    timestamp_decimals: usize, // Digits after the point of LSL timestamps in the stats line
+    duration_style: DurationStyle,
+
     // UI state
     status_message: String,
     auto_refresh: bool,
+    last_stream: Option<String>,
+    auto_connect_last_stream: bool,
+    adapt_to_rate: bool, // Pick the time window and downsampling from the sample rate on connect
+    recent_streams: Vec<RecentStream>, // Most recently used first
+    stream_profiles: HashMap<String, StreamProfile>, // Display setup of each stream seen, by name
+    auto_connect_pending: bool, // Waiting for the first stream listing to auto-connect
+    disconnected_stream: Option<String>, // Stream whose last data stays on screen after a disconnect
     last_t: f64,
     channel_colors: Vec<egui::Color32>,
+    color_overrides: HashMap<String, egui::Color32>, // Picked colors by channel name, kept across connections
+    light_palette: bool, // Default colors come from CHANNEL_COLORS_LIGHT, for light visuals
+    theme: Theme,
+    applied_theme: Option<Theme>, // Last theme handed to egui, so it is only set on change
+    plot_popped_out: bool,        // The central view is drawn in its own window
+    presentation_mode: bool,      // Controls and status bar hidden, the plot gets the whole window
+    profile: Option<Profile>,     // Given with --profile, applied whenever its stream connects
 }
 
 impl LslViewer {
-    fn new() -> Self {
-        let (cmd_tx, cmd_rx) = mpsc::channel::<LslCommand>();
-        let (resp_tx, resp_rx) = mpsc::channel::<LslResponse>();
+    /// `connect_to` names a stream to connect to right away, as given with `--connect`;
+    /// otherwise the profile's stream is, if it names one.
+    fn new(settings: Settings, connect_to: Option<String>, profile: Option<Profile>) -> Self {
+        let (cmd_tx, cmd_rx) = crossbeam_channel::unbounded::<LslCommand>();
+        let (resp_tx, resp_rx) = crossbeam_channel::unbounded::<LslResponse>();
 
         // Spawn LSL handler thread
         thread::spawn(move || {
+            #[cfg(feature = "test-source")]
+            if std::env::var_os("LSL_VIEWER_TEST_SOURCE").is_some() {
+                test_source::test_source_thread(
+                    cmd_rx,
+                    resp_tx,
+                    test_source::TestSourceConfig::default(),
+                );
+                return;
+            }
             lsl_handler_thread(cmd_rx, resp_tx);
         });
 
-        let o = Self {
+        let mut o = Self {
             command_sender: Some(cmd_tx),
             response_receiver: Some(resp_rx),
-            auto_refresh: true,
-            data_scale: DEFAULT_SCALE,
-            time_window_seconds: DEFAULT_TIME_WINDOW_SECONDS,
+            auto_refresh: settings.auto_refresh,
+            data_scale: settings.data_scale,
+            link_scales: true,
+            time_window_seconds: settings
+                .time_window_seconds
+                .clamp(*TIME_WINDOW_RANGE.start(), *TIME_WINDOW_RANGE.end()),
             last_t: 0.0,
-            downsample_factor: DEFAULT_DOWN_SAMPLE_FACTOR,
+            downsample_factor: settings.downsample_factor.clamp(1, MAX_DOWN_SAMPLE_FACTOR),
+            history_seconds: settings
+                .history_seconds
+                .clamp(*HISTORY_RANGE.start(), *HISTORY_RANGE.end()),
+            color_overrides: settings.color_overrides,
+            auto_connect_pending: settings.auto_connect_last_stream
+                && settings.last_stream.is_some(),
+            last_stream: settings.last_stream,
+            auto_connect_last_stream: settings.auto_connect_last_stream,
+            adapt_to_rate: settings.adapt_to_rate,
+            recent_streams: settings.recent_streams.clone(),
+            stream_profiles: settings.stream_profiles,
+            auto_select_types: settings.auto_select_types.clone(),
+            theme: settings.theme,
+            recording_directory: settings.recording_directory,
+            recording_template: settings.recording_template,
             reference_channel: None,
+            display_update_hz: DEFAULT_DISPLAY_UPDATE_HZ,
+            export_downsample_factor: 1,
+            y_axis_title: "Value".to_string(),
+            max_display_backlog: DEFAULT_MAX_DISPLAY_BACKLOG,
+            max_responses_per_frame: DEFAULT_MAX_RESPONSES_PER_FRAME,
+            max_buffer_samples: IRREGULAR_BUFFER_LIMIT,
+            max_auto_selected_channels: DEFAULT_MAX_AUTO_SELECTED_CHANNELS,
+            #[cfg(feature = "audio")]
+            audio_volume: 0.5,
+            #[cfg(feature = "audio")]
+            audio_pitch_range: audio::DEFAULT_PITCH_RANGE,
+            image_scale: 1,
+            alert_threshold: DEFAULT_ALERT_THRESHOLD,
+            latency_cap_ms: DEFAULT_LATENCY_CAP_MS,
+            clip_min: -DEFAULT_CLIP_LIMIT,
+            clip_max: DEFAULT_CLIP_LIMIT,
+            clip_run: DEFAULT_CLIP_RUN,
+            alert_latch: true,
+            collapse_markers: true,
+            erp_pre_seconds: erp::DEFAULT_PRE_SECONDS,
+            erp_post_seconds: erp::DEFAULT_POST_SECONDS,
+            digits_sparkline: true,
+            envelope_window_ms: DEFAULT_ENVELOPE_WINDOW_MS,
+            moving_average_window: DEFAULT_MOVING_AVERAGE_WINDOW,
+            line_width: 1.0,
+            row_spacing: 1.0,
+            pixel_decimation: true,
+            previous_sweep_color: egui::Color32::from_gray(150),
+            auto_x_grid: true,
+            x_grid_spacing: 0.5,
+            grid_in_theme_color: true,
+            grid_color: egui::Color32::from_rgba_unmultiplied(128, 128, 128, 96),
+            show_sweep_line: true,
+            baseline_on_wrap: true,
+            lane_grid: true,
+            show_scale_bar: true,
+            unit_factor: 1.0,
+            clock_reference: Some((lsl::local_clock(), Instant::now())),
+            timestamp_decimals: 3,
+            profile,
 
             ..Default::default()
         };
 
         // Initial command to refresh streams
-        o.send_command(LslCommand::RefreshStreams);
+        o.refresh_streams();
+        let connect_to = connect_to.or_else(|| o.profile.as_ref().and_then(|p| p.stream.clone()));
+        if let Some(name) = connect_to {
+            o.auto_connect_pending = false;
+            o.connect_stage = Some(ConnectStage::Resolving);
+            o.send_command(LslCommand::ConnectByName(name, o.connect_options.clone()));
+        }
 
         o
     }
 
-    fn send_command(&self, command: LslCommand) {
-        if let Some(sender) = &self.command_sender {
-            let _ = sender.send(command);
-        }
+    /// Put a stream at the top of the recent list, dropping entries that are
+    /// stale or beyond the length limit.
+    fn remember_recent_stream(&mut self, name: &str) {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        let source_id = self
+            .selected_stream
+            .as_ref()
+            .map(|s| s.source_id.clone())
+            .unwrap_or_default();
+        self.recent_streams.retain(|recent| {
+            recent.name != name
+                && now.saturating_sub(recent.last_used) <= RECENT_STREAM_MAX_AGE.as_secs()
+        });
+        self.recent_streams.insert(
+            0,
+            RecentStream {
+                name: name.to_string(),
+                source_id,
+                last_used: now,
+            },
+        );
+        self.recent_streams.truncate(RECENT_STREAMS_LIMIT);
     }
 
-    fn process_responses(&mut self) {
-        if let Some(receiver) = &self.response_receiver {
-            // use pastel colors for channels
-            let colors = vec![
-                egui::Color32::from_rgb(255, 105, 180), // Pink
-                egui::Color32::from_rgb(135, 206, 235), // Sky Blue
-                egui::Color32::from_rgb(255, 215, 0),   // Gold
-                egui::Color32::from_rgb(144, 238, 144), // Light Green
-                egui::Color32::from_rgb(255, 160, 122), // Light Salmon
-                egui::Color32::from_rgb(255, 182, 193), // Light Pink
-                egui::Color32::from_rgb(255, 228, 181), // Moccasin
-                egui::Color32::from_rgb(173, 216, 230), // Light Blue
-                egui::Color32::from_rgb(221, 160, 221), // Plum
-                egui::Color32::from_rgb(255, 99, 71),   // Tomato
-                egui::Color32::from_rgb(255, 140, 0),   // Dark Orange
-                egui::Color32::from_rgb(255, 250, 205), // Lemon Chiffon
-                egui::Color32::from_rgb(240, 230, 140), // Khaki
-                egui::Color32::from_rgb(255, 218, 185), // Peach Puff
-            ];
-            // Process all available responses
-            while let Ok(response) = receiver.try_recv() {
-                match response {
-                    LslResponse::StreamsFound(streams) => {
-                        self.available_streams = streams;
-                        if self.available_streams.is_empty() {
-                            self.status_message = "No streams found".to_string();
-                        } else {
-                            self.status_message =
-                                format!("Found {} stream(s)", self.available_streams.len());
-                        }
-                    }
-                    LslResponse::Connected(name, channels) => {
-                        let channel_count = channels.len();
-                        self.channel_count = channel_count;
-                        self.selected_channels = vec![true; channel_count];
-                        self.data_buffer = vec![VecDeque::new(); channel_count];
-                        self.timestamp_buffer = VecDeque::new();
-                        self.channel_baselines = vec![0.0; channel_count];
-                        self.channel_names = channels;
-                        self.is_connected = true;
-                        self.status_message =
-                            format!("Connected to: {} ({} channels)", name, channel_count);
-                        // asign channel colors
-                        self.channel_colors = (0..channel_count)
-                            .map(|i| {
-                                let color_index = i % colors.len();
-                                colors[color_index].to_owned()
-                            })
-                            .collect();
-                    }
-                    LslResponse::Disconnected => {
-                        self.is_connected = false;
-                        self.selected_stream_index = None;
-                        self.status_message = "Disconnected".to_string();
-                    }
-                    LslResponse::Error(msg) => {
-                        self.status_message = format!("Error: {}", msg);
-                    }
-                    LslResponse::Data(sample) => {
-                        // Add the timestamp to the timestamp buffer
-                        self.timestamp_buffer.push_back(sample.timestamp);
-                        // Add data for each channel
-                        for (ch, &value) in sample.values.iter().enumerate() {
-                            if let Some(channel_data_buffer) = self.data_buffer.get_mut(ch) {
-                                channel_data_buffer.push_back(value);
-                            }
-                        }
-
-                        // Remove old data (older than TIME_WINDOW_SECONDS)
-                        let cutoff_time = sample.timestamp - self.time_window_seconds;
-                        let cuttoff_index = self
-                            .timestamp_buffer
-                            .iter()
-                            .rev()
-                            .position(|&t| t <= cutoff_time);
+    /// Resolve a stream by name and connect to it, without going through the list.
+    fn connect_by_name(&mut self, name: String) {
+        self.selected_stream = None;
+        self.connect_stage = Some(ConnectStage::Resolving);
+        self.send_command(LslCommand::ConnectByName(
+            name,
+            self.connect_options.clone(),
+        ));
+    }
 
-                        if let Some(index) = cuttoff_index {
-                            // Remove old timestamps
-                            while self.timestamp_buffer.len() > index + 1 {
-                                self.timestamp_buffer.pop_front();
-                            }
-                            // Remove old data for each channel
-                            for channel_data in self.data_buffer.iter_mut() {
-                                while channel_data.len() > index + 1 {
-                                    channel_data.pop_front();
-                                }
-                            }
-                        }
-                    }
-                }
-            }
+    /// Connect to the stream that was shown before the last disconnect, by name,
+    /// without going through the stream list.
+    fn reconnect_disconnected_stream(&mut self) {
+        if let Some(name) = self.disconnected_stream.clone() {
+            self.connect_by_name(name);
         }
     }
 
-    fn baseline_correct(&mut self) {
-        // Calculate baseline for each channel
-        for (i, channel_data) in self.data_buffer.iter_mut().enumerate() {
-            if !channel_data.is_empty() {
-                let mut sum = 0.0;
-                let mut count = 0;
-                let oldest_timestamp_to_inlcude = self.timestamp_buffer.back().unwrap_or(&0.0)
-                    - DEFAULT_BASELINE_TIME_WINDOW as f64;
-                for (_, &value) in self
-                    .timestamp_buffer
-                    .iter()
-                    .zip(channel_data.iter())
-                    .rev()
-                    .take_while(|&(t, _)| *t >= oldest_timestamp_to_inlcude)
-                {
-                    sum += value as f64;
-                    count += 1;
+    /// The settings to keep for the next launch.
+    fn settings(&self) -> Settings {
+        Settings {
+            data_scale: self.data_scale,
+            time_window_seconds: self.time_window_seconds,
+            downsample_factor: self.downsample_factor,
+            history_seconds: self.history_seconds,
+            auto_refresh: self.auto_refresh,
+            color_overrides: self.color_overrides.clone(),
+            last_stream: self.last_stream.clone(),
+            auto_connect_last_stream: self.auto_connect_last_stream,
+            adapt_to_rate: self.adapt_to_rate,
+            theme: self.theme,
+            auto_select_types: self.auto_select_types.clone(),
+            recent_streams: self.recent_streams.clone(),
+            stream_profiles: {
+                let mut profiles = self.stream_profiles.clone();
+                if let Some((name, profile)) = self.current_stream_profile() {
+                    profiles.insert(name, profile);
                 }
+                profiles
+            },
+            recording_directory: self.recording_directory.clone(),
+            recording_template: self.recording_template.clone(),
+        }
+    }
 
-                let baseline = sum / count as f64;
-                self.channel_baselines[i] = baseline;
-            }
+    /// The connected live stream's name and how it is displayed right now.
+    fn current_stream_profile(&self) -> Option<(String, StreamProfile)> {
+        if !self.is_connected || self.playback.is_some() {
+            return None;
         }
+        let name = self.last_stream.clone()?;
+        let selected_channels = (0..self.stream_channel_count())
+            .filter(|&ch| self.selected_channels.get(ch).copied().unwrap_or(false))
+            .filter_map(|ch| self.channel_names.get(ch).cloned())
+            .collect();
+        let colors = self
+            .channel_names
+            .iter()
+            .filter_map(|label| {
+                self.color_overrides
+                    .get(label)
+                    .map(|color| (label.clone(), *color))
+            })
+            .collect();
+        let profile = StreamProfile {
+            selected_channels,
+            colors,
+            data_scale: self.data_scale,
+            time_window_seconds: self.time_window_seconds,
+            filters: self.filter_settings,
+        };
+        Some((name, profile))
     }
-}
 
-fn extract_channel_names(info: &mut StreamInfo, expected_count: usize) -> Vec<String> {
-    let mut channel_names = vec![];
+    /// Keep the connected stream's display setup for the next time it connects.
+    fn remember_stream_profile(&mut self) {
+        if let Some((name, profile)) = self.current_stream_profile() {
+            self.stream_profiles.insert(name, profile);
+        }
+    }
 
-    let mut cursor = info.desc().child("channels").child("channel");
-    while cursor.is_valid() {
-        channel_names.push(cursor.child_value_named("label"));
-        cursor = cursor.next_sibling();
+    /// Restore how the stream called `name` was displayed last time. Streams
+    /// without a stored profile keep the defaults set up on connect.
+    fn apply_stream_profile(&mut self, name: &str) {
+        let Some(profile) = self.stream_profiles.get(name).cloned() else {
+            return;
+        };
+        if !profile.selected_channels.is_empty() {
+            self.selected_channels = self
+                .channel_names
+                .iter()
+                .map(|label| profile.selected_channels.contains(label))
+                .collect();
+        }
+        self.color_overrides.extend(profile.colors);
+        if profile.data_scale > 0.0 {
+            self.data_scale = profile
+                .data_scale
+                .clamp(*SCALE_RANGE.start(), *SCALE_RANGE.end());
+            self.channel_scales = vec![self.data_scale; self.channel_count];
+        }
+        if profile.time_window_seconds > 0.0 {
+            self.time_window_seconds = profile
+                .time_window_seconds
+                .clamp(*TIME_WINDOW_RANGE.start(), *TIME_WINDOW_RANGE.end());
+        }
+        if self.filter_settings != profile.filters {
+            self.filter_settings = profile.filters;
+            self.rebuild_filters();
+        }
     }
 
-    if channel_names.len() != expected_count {
-        (0..info.channel_count())
-            .map(|i| "Ch ".to_string() + &i.to_string())
-            .collect()
-    } else {
-        channel_names
+    /// The template's file name for a new recording, numbered past the files
+    /// already in the recording directory.
+    fn next_recording_file_name(&self) -> String {
+        let stream = self.last_stream.as_deref().unwrap_or("recording");
+        let date = chrono::Local::now().format("%Y-%m-%d").to_string();
+        let name = |index| recording_file_name(&self.recording_template, stream, &date, index);
+        let Some(directory) = &self.recording_directory else {
+            return name(1);
+        };
+        (1..1000)
+            .map(name)
+            .find(|name| !directory.join(name).exists())
+            .unwrap_or_else(|| name(1))
     }
-}
 
-fn lsl_handler_thread(cmd_rx: Receiver<LslCommand>, resp_tx: Sender<LslResponse>) {
-    let mut available_streams: Vec<StreamInfo> = Vec::new();
-    let mut inlet: Option<StreamInlet> = None;
-    let mut channel_count = 0;
+    /// Play `path` back in place of the live streams.
+    fn open_playback(&mut self, path: &std::path::Path) {
+        let recording = match playback::load_csv(path) {
+            Ok(recording) => recording,
+            Err(e) => {
+                self.set_status(format!("Error: {}", e));
+                return;
+            }
+        };
+        if self.is_connected {
+            self.send_command(LslCommand::Disconnect);
+            self.handle_response(LslResponse::Disconnected);
+        }
 
-    loop {
-        // Check for commands
-        match cmd_rx.try_recv() {
-            Ok(LslCommand::RefreshStreams) => match lsl::resolve_streams(3.0) {
-                Ok(streams) => {
-                    available_streams = streams;
-                    let stream_data: Vec<StreamData> = available_streams
-                        .iter()
-                        .map(|s| StreamData {
-                            name: s.stream_name().to_string(),
-                            channel_count: s.channel_count() as usize,
-                            sample_rate: s.nominal_srate(),
-                        })
-                        .collect();
-                    let _ = resp_tx.send(LslResponse::StreamsFound(stream_data));
+        let (cmd_tx, cmd_rx) = crossbeam_channel::unbounded::<LslCommand>();
+        let (resp_tx, resp_rx) = crossbeam_channel::unbounded::<LslResponse>();
+        self.playback = Some(PlaybackStatus {
+            name: recording.name.clone(),
+            position: 0.0,
+            duration: 0.0,
+            paused: false,
+            speed: 1.0,
+            seeking: false,
+        });
+        thread::spawn(move || playback::playback_thread(recording, cmd_rx, resp_tx));
+        let previous = (
+            self.command_sender.replace(cmd_tx),
+            self.response_receiver.replace(resp_rx),
+        );
+        // a file opened during playback replaces that file, the live channels stay set aside
+        if self.live_channels.is_none()
+            && let (Some(sender), Some(receiver)) = previous
+        {
+            self.live_channels = Some((sender, receiver));
+        }
+    }
+
+    /// Stop playback and go back to the live streams. Dropping the playback
+    /// channels ends its thread.
+    fn close_playback(&mut self) {
+        self.playback = None;
+        if let Some((sender, receiver)) = self.live_channels.take() {
+            // whatever the LSL thread sent meanwhile belongs to the old connection
+            while receiver.try_recv().is_ok() {}
+            self.command_sender = Some(sender);
+            self.response_receiver = Some(receiver);
+        }
+        self.refresh_streams();
+    }
+
+    /// Ask for a new stream listing, restricted by the host and source_id filters if set.
+    fn refresh_streams(&mut self) {
+        let conditions: Vec<String> = [
+            ("hostname", &self.hostname_filter),
+            ("source_id", &self.source_id_filter),
+        ]
+        .into_iter()
+        .map(|(field, value)| (field, value.trim()))
+        .filter(|(_, value)| !value.is_empty())
+        .map(|(field, value)| format!("{}='{}'", field, value))
+        .collect();
+        if conditions.is_empty() {
+            self.send_command(LslCommand::RefreshStreams);
+        } else if conditions.iter().any(|c| c.matches('\'').count() != 2) {
+            self.set_status("Error: Host and source_id filters can't contain quotes");
+        } else {
+            self.send_command(LslCommand::RefreshStreamsFiltered(conditions.join(" and ")));
+        }
+    }
+
+    fn playback_ui(&mut self, ui: &mut egui::Ui) {
+        let Some(status) = self.playback.as_mut() else {
+            return;
+        };
+        let mut command = None;
+        ui.group(|ui| {
+            ui.label(format!("Playing back {}", status.name));
+            ui.horizontal(|ui| {
+                let label = if status.paused {
+                    "▶ Play"
+                } else {
+                    "⏸ Pause"
+                };
+                if ui.button(label).clicked() {
+                    status.paused = !status.paused;
+                    command = Some(LslCommand::SetPlaybackPaused(status.paused));
+                }
+                egui::ComboBox::from_id_source("playback_speed")
+                    .selected_text(format!("{}×", status.speed))
+                    .show_ui(ui, |ui| {
+                        for speed in PLAYBACK_SPEEDS {
+                            if ui
+                                .selectable_value(&mut status.speed, speed, format!("{}×", speed))
+                                .clicked()
+                            {
+                                command = Some(LslCommand::SetPlaybackSpeed(speed));
+                            }
+                        }
+                    });
+            });
+            let response = ui.add(
+                egui::Slider::new(&mut status.position, 0.0..=status.duration.max(0.0))
+                    .suffix(" s")
+                    .max_decimals(1),
+            );
+            // seek once the handle is let go, not on every step of the drag
+            status.seeking = response.dragged();
+            if response.drag_stopped() || (response.changed() && !response.dragged()) {
+                command = Some(LslCommand::SeekPlayback(status.position));
+            }
+            if ui.button("Close File").clicked() {
+                command = Some(LslCommand::Disconnect);
+            }
+        });
+        if let Some(command) = command {
+            self.send_command(command);
+        }
+    }
+
+    fn send_command(&self, command: LslCommand) {
+        if let Some(sender) = &self.command_sender {
+            let _ = sender.send(command);
+        }
+    }
+
+    fn process_responses(&mut self) {
+        // Bound the work per frame; whatever is left stays queued for the next frame
+        let responses: Vec<LslResponse> = match &self.response_receiver {
+            Some(receiver) => {
+                let responses = receiver
+                    .try_iter()
+                    .take(self.max_responses_per_frame.max(1))
+                    .collect();
+                self.carried_over_responses = receiver.len();
+                responses
+            }
+            None => return,
+        };
+
+        // Check the counter on every raw sample, before the display drops any
+        if let Some(ch) = self.sequence_channel {
+            for response in responses.iter() {
+                if let LslResponse::Data(sample) = response
+                    && let Some(&counter) = sample.values.get(ch)
+                {
+                    self.check_sequence(counter as f64);
+                }
+            }
+        }
+
+        // Gaps are looked for in the raw stream too, display drops would look like gaps
+        let mut received = 0;
+        for response in responses.iter() {
+            match response {
+                LslResponse::Data(sample) => {
+                    self.check_gap(sample.timestamp);
+                    received += 1;
+                }
+                LslResponse::StringData(..) => received += 1,
+                LslResponse::Connected { .. } => {
+                    self.reset_gap_detection();
+                    self.arrivals.clear();
+                    self.last_data_instant = None;
+                    received = 0;
+                }
+                _ => {}
+            }
+        }
+        let now = Instant::now();
+        if received > 0 {
+            self.last_data_instant = Some(now);
+        }
+        self.arrivals.push_back((now, received));
+        while self
+            .arrivals
+            .front()
+            .is_some_and(|(at, _)| now.duration_since(*at) > EFFECTIVE_RATE_WINDOW)
+        {
+            self.arrivals.pop_front();
+        }
+
+        // Process all available responses
+        for response in self.apply_drop_policy(responses) {
+            self.handle_response(response);
+        }
+
+        // epochs are cut from the buffer once their post-event data is in
+        if let Some(erp) = self.erp.as_mut() {
+            erp.update(&self.timestamp_buffer, &self.data_buffer);
+        }
+
+        // the display has fallen behind the source, jump to live data
+        if self.latency_cap
+            && !self.frozen
+            && !self.acquisition_paused
+            && self.recording.is_none()
+            && self
+                .display_latency()
+                .is_some_and(|latency| latency * 1000.0 > self.latency_cap_ms)
+            && self
+                .last_latency_flush
+                .is_none_or(|at| at.elapsed() >= LATENCY_FLUSH_INTERVAL)
+        {
+            self.last_latency_flush = Some(Instant::now());
+            self.send_command(LslCommand::SkipBacklog);
+        }
+    }
+
+    /// Seconds from the newest displayed sample being taken to now, on the local
+    /// LSL clock. Only the newest sample of a regular live stream is due now.
+    fn display_latency(&self) -> Option<f64> {
+        if !self.is_connected || self.playback.is_some() || self.irregular {
+            return None;
+        }
+        let latest = *self.timestamp_buffer.back()?;
+        // without clock synchronization the timestamps are on the source's clock
+        let correction = if self
+            .applied_postprocessing
+            .contains(&PostprocessingFlag::ClockSync)
+        {
+            0.0
+        } else {
+            self.time_corrections.back().copied().unwrap_or(0.0)
+        };
+        Some(lsl::local_clock() - (latest + correction))
+    }
+
+    fn handle_response(&mut self, response: LslResponse) {
+        match response {
+            LslResponse::StreamsFound(streams) => {
+                // the first listing is the baseline, only later arrivals count as new
+                if self.streams_listed {
+                    let now = Instant::now();
+                    for stream in streams.iter() {
+                        if !self
+                            .available_streams
+                            .iter()
+                            .any(|s| s.key() == stream.key())
+                        {
+                            self.new_streams.insert(stream.key().to_string(), now);
+                        }
+                    }
+                }
+                self.new_streams
+                    .retain(|_, seen| seen.elapsed().as_secs_f64() < NEW_STREAM_BADGE_SECONDS);
+                self.streams_listed = true;
+                self.available_streams = streams;
+                if self.is_connected {
+                    // leave the connection status alone
+                } else if self.available_streams.is_empty() {
+                    self.set_status("No streams found");
+                } else {
+                    self.set_status(format!("Found {} stream(s)", self.available_streams.len()));
+                }
+
+                // only the first listing after startup is used to auto-connect
+                if std::mem::take(&mut self.auto_connect_pending) && !self.is_connected {
+                    let last_stream = self.last_stream.as_deref();
+                    if let Some(stream) = self
+                        .available_streams
+                        .iter()
+                        .find(|s| Some(s.name.as_str()) == last_stream)
+                        .cloned()
+                    {
+                        let key = stream.key().to_string();
+                        self.selected_stream = Some(stream);
+                        self.connect_stage = Some(ConnectStage::Resolving);
+                        self.send_command(LslCommand::Connect(key, self.connect_options.clone()));
+                    }
+                }
+            }
+            LslResponse::Connected {
+                stream,
+                channel_names: channels,
+                channel_units,
+                channel_types,
+                metadata_available,
+            } => {
+                // switching streams without a disconnect in between
+                self.remember_stream_profile();
+                let name = stream.name.clone();
+                self.selected_stream = Some(stream);
+                let channel_count = channels.len();
+                // a device reconfigured between connections comes back with another layout
+                let previous_channel_count = (self.last_stream.as_deref() == Some(&name))
+                    .then(|| self.stream_channel_count())
+                    .filter(|&previous| previous > 0 && previous != channel_count);
+                self.channel_count = channel_count;
+                // their sources were channels of the previous stream
+                self.derived_channels.clear();
+                self.selected_channels = self.auto_selected_channels(&channel_types);
+                self.muted_channels = vec![false; channel_count];
+                self.solo_channel = None;
+                self.bad_channels = vec![false; channel_count];
+                self.filter_bypass = vec![false; channel_count];
+                self.data_buffer = vec![VecDeque::new(); channel_count];
+                self.timestamp_buffer = VecDeque::new();
+                self.display_caches.clear();
+                self.reference_traces = None;
+                self.plot_geometry = PlotGeometry::default();
+                self.last_geometry_build = None;
+                self.channel_stats.clear();
+                self.last_stats_update = None;
+                self.acquisition_paused = false;
+                self.channel_baselines = vec![0.0; channel_count];
+                self.channel_offsets = vec![0.0; channel_count];
+                self.channel_order = (0..channel_count).collect();
+                self.channel_scales = vec![self.data_scale; channel_count];
+                self.channel_alerts = vec![None; channel_count];
+                self.clip_counts = vec![(0, 0.0); channel_count];
+                self.nonfinite_seen = vec![None; channel_count];
+                self.clip_buffer.clear();
+                self.rebuild_filters();
+                self.dragged_channel = None;
+                self.stats_range = None;
+                self.range_drag_start = None;
+                #[cfg(feature = "audio")]
+                {
+                    self.audio_monitor = None;
+                    self.audio_channel = 0;
+                }
+                if self.reference_channel.is_some_and(|ch| ch >= channel_count) {
+                    self.reference_channel = None;
+                }
+                self.channel_names = channels;
+                self.is_connected = true;
+                self.disconnected_stream = None;
+                self.connect_stage = None;
+                self.metadata_available = metadata_available;
+                self.stream_xml = None;
+                self.dropped_display_samples = 0;
+                self.timestamp_regressions = 0;
+                self.channel_count_changes = 0;
+                self.sequence_channel = None;
+                self.reset_sequence_check();
+                self.peak_channel = None;
+                self.reset_peaks();
+                let unit = common_unit(&channel_units);
+                self.channel_units = channel_units;
+                self.channel_types = channel_types;
+                let (unit_factor, display_unit) = detect_unit_conversion(&unit);
+                self.declared_unit = unit;
+                self.unit_factor = unit_factor;
+                self.display_unit = display_unit;
+                self.irregular = self.stream_sample_rate() <= 0.0;
+                self.text_stream = false;
+                self.text_log.clear();
+                self.time_corrections.clear();
+                self.pull_stats = None;
+                self.inlet_backlog = None;
+                // a played back file was timestamped by the clock of its own session
+                self.wall_clock_offset = if self.playback.is_none() {
+                    wall_clock_offset()
+                } else {
+                    None
+                };
+                self.set_status(match previous_channel_count {
+                    Some(previous) => format!(
+                        "Warning: {} now has {} channels instead of {}; channel settings were reset",
+                        name, channel_count, previous
+                    ),
+                    None if !metadata_available => format!(
+                        "Warning: connected to {}, but its metadata didn't arrive within {} s ({} retries); channel names are generated",
+                        name, self.connect_options.info_timeout, self.connect_options.info_retries
+                    ),
+                    None => format!("Connected to: {} ({} channels)", name, channel_count),
+                });
+                // the window remembered for the stream, or set by a profile, still wins
+                if self.adapt_to_rate {
+                    self.adapt_to_sample_rate();
+                }
+                // a --profile file overrides what was remembered for the stream
+                if self.playback.is_none() {
+                    self.apply_stream_profile(&name);
+                }
+                self.apply_profile(&name);
+                // a played back file isn't something to reconnect to on the next launch
+                if self.playback.is_none() {
+                    self.remember_recent_stream(&name);
+                    self.last_stream = Some(name);
+                }
+                self.assign_channel_colors();
+                self.reset_erp();
+                self.reset_spectrogram();
+                self.digits_range = None;
+            }
+            LslResponse::ConnectCancelled => {
+                if !self.is_connected {
+                    self.selected_stream = None;
+                }
+                self.connect_stage = None;
+                self.set_status("Connect cancelled");
+            }
+            LslResponse::Disconnected => {
+                self.remember_stream_profile();
+                // the last data stays on screen, with a way back to the same stream
+                self.disconnected_stream = if self.is_connected && self.playback.is_none() {
+                    self.last_stream.clone()
+                } else {
+                    None
+                };
+                if self.playback.is_some() {
+                    self.close_playback();
+                }
+                self.is_connected = false;
+                self.stream_xml = None;
+                self.show_stream_xml = false;
+                self.marker_buffer.clear();
+                self.marker_stream = None;
+                self.annotations.clear();
+                self.editing_annotation = None;
+                self.erp = None;
+                self.reconnecting = None;
+                self.frozen = false;
+                self.acquisition_paused = false;
+                self.recording = None;
+                self.connect_stage = None;
+                #[cfg(feature = "audio")]
+                {
+                    self.audio_monitor = None;
+                }
+                self.requested_postprocessing.clear();
+                self.applied_postprocessing.clear();
+                self.pull_stats = None;
+                self.inlet_backlog = None;
+                self.selected_stream = None;
+                self.set_status("Disconnected");
+            }
+            LslResponse::Error(msg) => {
+                // a failed connect leaves nothing selected, so the list can be clicked again
+                if !self.is_connected {
+                    self.selected_stream = None;
+                }
+                self.connect_stage = None;
+                self.set_status(format!("Error: {}", msg));
+            }
+            LslResponse::ConnectStage(stage) => {
+                self.connect_stage = Some(stage);
+            }
+            LslResponse::PostprocessingApplied { requested, applied } => {
+                self.requested_postprocessing = requested;
+                self.applied_postprocessing = applied;
+            }
+            LslResponse::PullStats {
+                chunks_per_sec,
+                avg_chunk_size,
+                max_chunk_size,
+            } => {
+                self.pull_stats = Some((chunks_per_sec, avg_chunk_size, max_chunk_size));
+            }
+            LslResponse::InletDiag { buffered } => {
+                let grew = self
+                    .inlet_backlog
+                    .is_some_and(|(previous, _)| buffered > previous);
+                self.inlet_backlog = Some((buffered, grew));
+            }
+            LslResponse::TimeCorrection(offset) => {
+                self.time_corrections.push_back(offset);
+                while self.time_corrections.len() > TIME_CORRECTION_HISTORY {
+                    self.time_corrections.pop_front();
+                }
+            }
+            LslResponse::Reconnecting { name, attempt } => {
+                // the spinner counts the attempts, the log only needs the loss
+                if attempt == 1 {
+                    self.event_log
+                        .push(Severity::Warning, format!("Lost {}, reconnecting", name));
+                }
+                self.reconnecting = Some((name, attempt));
+            }
+            LslResponse::StringData(timestamp, values) => {
+                if self.frozen {
+                    return;
+                }
+                self.text_stream = true;
+                self.text_log.push_back((timestamp, values.join(", ")));
+                while self.text_log.len() > TEXT_LOG_LIMIT {
+                    self.text_log.pop_front();
+                }
+            }
+            LslResponse::PlaybackSeeked => {
+                self.timestamp_buffer.clear();
+                for channel_data in self.data_buffer.iter_mut() {
+                    channel_data.clear();
+                }
+                self.reset_gap_detection();
+                self.reset_peaks();
+                self.reset_spectrogram();
+            }
+            LslResponse::PlaybackProgress { position, duration } => {
+                if let Some(status) = self.playback.as_mut().filter(|s| !s.seeking) {
+                    status.position = position;
+                    status.duration = duration;
+                }
+            }
+            LslResponse::StreamXml(xml) => {
+                let tree = xml_tree::parse(&xml);
+                self.stream_xml = Some((xml, tree));
+            }
+            LslResponse::BacklogSkipped(samples) => {
+                self.set_status(format!("Skipped {} queued-up samples to catch up", samples));
+            }
+            LslResponse::Reconnected => {
+                if let Some((name, _)) = self.reconnecting.take() {
+                    self.set_status(format!("Reconnected to {}", name));
+                }
+            }
+            LslResponse::MarkersConnected(name) => {
+                self.marker_buffer.clear();
+                self.set_status(format!("Showing markers from {}", name));
+                self.marker_stream = Some(name);
+                self.reset_erp();
+            }
+            LslResponse::MarkersDisconnected => {
+                self.marker_buffer.clear();
+                self.marker_stream = None;
+            }
+            LslResponse::Marker(timestamp, text) => {
+                // stays in the buffer until the data has moved past it
+                self.marker_buffer.push_back((timestamp, text));
+                if let Some(erp) = self.erp.as_mut() {
+                    erp.add_event(timestamp);
+                }
+            }
+            LslResponse::RecordingStarted(path) => {
+                self.set_status(format!("Recording to {}", path.display()));
+                self.recording_directory = path.parent().map(|p| p.to_path_buf());
+                self.recording = Some(RecordingStatus {
+                    path,
+                    started: Instant::now(),
+                    samples_written: 0,
+                });
+            }
+            LslResponse::RecordingProgress(samples_written) => {
+                if let Some(recording) = &mut self.recording {
+                    recording.samples_written = samples_written;
+                }
+            }
+            LslResponse::RecordingStopped(samples_written) => {
+                if let Some(recording) = self.recording.take() {
+                    self.set_status(format!(
+                        "Recorded {} samples to {}",
+                        samples_written,
+                        recording.path.display()
+                    ));
+                }
+            }
+            LslResponse::Data(mut sample) => {
+                // the LSL thread keeps pulling, the frozen display just doesn't take the data
+                if self.frozen {
+                    return;
+                }
+
+                // The stream changed shape under us; the buffers must all stay the same width
+                let stream_channel_count = self.stream_channel_count();
+                if sample.values.len() != stream_channel_count {
+                    self.channel_count_changes += 1;
+                    self.set_status(format!(
+                        "Warning: sample has {} channels, expected {}",
+                        sample.values.len(),
+                        stream_channel_count
+                    ));
+                    match self.channel_count_policy {
+                        ChannelCountChangePolicy::Reject => return,
+                        ChannelCountChangePolicy::Resize => {
+                            self.set_derived_channels(sample.values.len())
+                        }
+                    }
+                }
+
+                // The wrap math and trimming assume increasing timestamps, so this is
+                // settled before the sample reaches clipping, filter or alert state
+                if let Some(&latest) = self.timestamp_buffer.back()
+                    && self.timestamp_out_of_order(latest, sample.timestamp)
+                {
+                    self.timestamp_regressions += 1;
+                    self.set_status(if sample.timestamp == latest {
+                        format!("Warning: duplicate timestamp {:.3}", latest)
+                    } else {
+                        format!(
+                            "Warning: timestamp went backwards by {:.3} s",
+                            latest - sample.timestamp
+                        )
+                    });
+                    match self.timestamp_regression_policy {
+                        TimestampRegressionPolicy::DropSample => return,
+                        TimestampRegressionPolicy::ResetBuffer => {
+                            self.timestamp_buffer.clear();
+                            for channel_data in self.data_buffer.iter_mut() {
+                                channel_data.clear();
+                            }
+                        }
+                        TimestampRegressionPolicy::Monotonize => {
+                            sample.timestamp = self.next_timestamp(latest);
+                        }
+                    }
+                }
+
+                // Convert to the display unit before anything else sees the values
+                if self.unit_factor != 1.0 {
+                    for value in sample.values.iter_mut() {
+                        *value = (*value as f64 * self.unit_factor) as f32;
+                    }
+                }
+                // A rail is a raw value, before referencing or filtering moves it
+                self.check_clipping(sample.timestamp, &sample.values);
+                // e.g. a disconnected electrode; these stay gaps, the steps below skip them
+                for (ch, value) in sample.values.iter().enumerate() {
+                    if !value.is_finite()
+                        && let Some(seen) = self.nonfinite_seen.get_mut(ch)
+                    {
+                        *seen = Some(sample.timestamp);
+                    }
+                }
+                // Re-reference, then append the derived channels so they are filtered like the rest
+                if self.common_average {
+                    let included: Vec<bool> = (0..sample.values.len())
+                        .map(|ch| {
+                            self.selected_channels.get(ch).copied().unwrap_or(false)
+                                && !self.channel_bad(ch)
+                        })
+                        .collect();
+                    derived::common_average_reference(&mut sample.values, &included);
+                }
+                let derived: Vec<f32> = self
+                    .derived_channels
+                    .iter()
+                    .map(|channel| channel.compute(&sample.values))
+                    .collect();
+                sample.values.extend(derived);
+                // The buffer holds filtered data, so everything downstream sees it. A
+                // non-finite value would stay in the filter state for good, so the filters
+                // get the channel's last finite value and the gap is put back afterwards
+                self.last_finite.resize(sample.values.len(), 0.0);
+                let mut gaps = Vec::new();
+                for (ch, value) in sample.values.iter_mut().enumerate() {
+                    if value.is_finite() {
+                        self.last_finite[ch] = *value;
+                    } else {
+                        gaps.push(ch);
+                        *value = self.last_finite[ch];
+                    }
+                }
+                self.filters.process(&mut sample);
+                for ch in gaps {
+                    sample.values[ch] = f32::NAN;
+                }
+                self.check_alerts(&sample.values);
+                self.detect_peaks(sample.timestamp, &sample.values);
+
+                // Add the timestamp to the timestamp buffer
+                self.timestamp_buffer.push_back(sample.timestamp);
+                // Add data for each channel
+                for (ch, &value) in sample.values.iter().enumerate() {
+                    if let Some(channel_data_buffer) = self.data_buffer.get_mut(ch) {
+                        channel_data_buffer.push_back(value);
+                    }
+                }
+                if let Some(spectrogram) = self.spectrogram.as_mut()
+                    && let Some(&value) = sample.values.get(spectrogram.settings().channel)
+                {
+                    spectrogram.push(value);
+                }
+                if self.plot_mode == PlotMode::Digits
+                    && let Some(&value) = sample
+                        .values
+                        .get(self.digits_channel)
+                        .filter(|v| v.is_finite())
+                {
+                    let baseline = self
+                        .channel_baselines
+                        .get(self.digits_channel)
+                        .copied()
+                        .unwrap_or(0.0);
+                    let value = value as f64 - baseline;
+                    self.digits_range = Some(match self.digits_range {
+                        Some((min, max)) => (min.min(value), max.max(value)),
+                        None => (value, value),
+                    });
+                }
+
+                // Feed the monitored channel to the audio output, scaled like the display
+                #[cfg(feature = "audio")]
+                if let Some(monitor) = &self.audio_monitor
+                    && let Some(&value) = sample
+                        .values
+                        .get(self.audio_channel)
+                        .filter(|v| v.is_finite())
+                {
+                    let baseline = self.channel_baselines[self.audio_channel];
+                    let deflection = (value as f64 - baseline)
+                        * self.channel_scales[self.audio_channel]
+                        / SCALE_NORMALIZATION;
+                    monitor.push(deflection as f32);
+                }
+
+                // Remove old data (older than the time window, or the history kept)
+                let cutoff_time = sample.timestamp - self.history_span();
+                let cuttoff_index = self
+                    .timestamp_buffer
+                    .iter()
+                    .rev()
+                    .position(|&t| t <= cutoff_time);
+
+                while self
+                    .marker_buffer
+                    .front()
+                    .is_some_and(|(t, _)| *t < cutoff_time)
+                {
+                    self.marker_buffer.pop_front();
+                }
+                while self
+                    .annotations
+                    .front()
+                    .is_some_and(|(t, _)| *t < cutoff_time)
+                {
+                    self.annotations.pop_front();
+                }
+                while self
+                    .gap_buffer
+                    .front()
+                    .is_some_and(|(_, end)| *end < cutoff_time)
+                {
+                    self.gap_buffer.pop_front();
+                }
+                self.clip_buffer.retain(|(_, _, end)| *end >= cutoff_time);
+                while self
+                    .peak_buffer
+                    .front()
+                    .is_some_and(|(t, _)| *t < cutoff_time)
+                {
+                    self.peak_buffer.pop_front();
+                }
+
+                // A burst can put more than a window's worth in; the hard limit wins then
+                let limit = self.buffer_sample_limit();
+                let keep = match cuttoff_index {
+                    Some(index) => (index + 1).min(limit),
+                    None => limit,
+                };
+                // Remove old timestamps
+                while self.timestamp_buffer.len() > keep {
+                    self.timestamp_buffer.pop_front();
+                }
+                // Remove old data for each channel
+                for channel_data in self.data_buffer.iter_mut() {
+                    while channel_data.len() > keep {
+                        channel_data.pop_front();
+                    }
+                }
+            }
+        }
+    }
+
+    /// Adopt a new channel count mid-stream. Buffered data is discarded since it
+    /// can't be lined up with the new layout; per-channel settings are kept where
+    /// the channel still exists.
+    fn resize_channels(&mut self, count: usize) {
+        self.data_buffer = vec![VecDeque::new(); count];
+        self.display_caches.clear();
+        self.timestamp_buffer.clear();
+        self.resize_channel_state(count);
+    }
+
+    /// Bring every per-channel vector and index to `count` channels, leaving the buffers
+    /// alone, and restart what was computed from the old layout.
+    fn resize_channel_state(&mut self, count: usize) {
+        self.resize_channel_settings(count);
+        self.clip_buffer.clear();
+        self.rebuild_filters();
+        self.reset_erp();
+        self.reset_spectrogram();
+        self.dragged_channel = None;
+        self.last_sequence_value = None;
+        self.reset_peaks();
+    }
+
+    /// Bring every per-channel setting and index to `count` channels. Those of the
+    /// channels that remain are kept.
+    fn resize_channel_settings(&mut self, count: usize) {
+        self.channel_count = count;
+        self.channel_baselines.resize(count, 0.0);
+        self.channel_offsets.resize(count, 0.0);
+        let previous_order = self.channel_order.len();
+        self.channel_order.retain(|&ch| ch < count);
+        self.channel_order.extend(previous_order..count);
+        self.channel_scales.resize(count, self.data_scale);
+        self.channel_alerts.resize(count, None);
+        self.clip_counts.resize(count, (0, 0.0));
+        self.nonfinite_seen.resize(count, None);
+        self.filter_bypass.resize(count, false);
+        self.muted_channels.resize(count, false);
+        self.solo_channel = self.solo_channel.filter(|&ch| ch < count);
+        self.bad_channels.resize(count, false);
+        let previous = self.selected_channels.len();
+        self.selected_channels.truncate(count);
+        for i in previous..count {
+            self.selected_channels
+                .push(i < self.max_auto_selected_channels);
+        }
+        if self.channel_names.len() > count {
+            self.channel_names.truncate(count);
+        } else {
+            let names = default_channel_names(count);
+            self.channel_names
+                .extend_from_slice(&names[self.channel_names.len()..]);
+        }
+        self.channel_units.resize(count, String::new());
+        self.channel_types.resize(count, String::new());
+        self.assign_channel_colors();
+        if self.reference_channel.is_some_and(|r| r >= count) {
+            self.reference_channel = None;
+        }
+        if self.sequence_channel.is_some_and(|ch| ch >= count) {
+            self.sequence_channel = None;
+        }
+        if self.peak_channel.is_some_and(|ch| ch >= count) {
+            self.peak_channel = None;
+        }
+        #[cfg(feature = "audio")]
+        if self.audio_channel >= count {
+            self.audio_monitor = None;
+            self.audio_channel = 0;
+        }
+    }
+
+    /// Number of channels the stream itself has, without the derived ones.
+    fn stream_channel_count(&self) -> usize {
+        self.channel_count
+            .saturating_sub(self.derived_channels.len())
+    }
+
+    /// Lay the channels out again for a stream of `stream_channel_count` channels
+    /// followed by the derived ones; derived channels whose sources are gone are
+    /// dropped. The buffers restart, as for any change of layout.
+    fn set_derived_channels(&mut self, stream_channel_count: usize) {
+        self.derived_channels
+            .retain(|channel| channel.fits(stream_channel_count));
+        self.resize_channels(stream_channel_count + self.derived_channels.len());
+        self.label_derived_channels(stream_channel_count);
+    }
+
+    /// Name the derived channels, which follow the stream's own, after their sources.
+    fn label_derived_channels(&mut self, stream_channel_count: usize) {
+        let stream_names = self.channel_names[..stream_channel_count].to_vec();
+        for (i, channel) in self.derived_channels.iter().enumerate() {
+            let ch = stream_channel_count + i;
+            self.channel_names[ch] = channel.name(&stream_names);
+            self.channel_units[ch] = channel
+                .sources()
+                .first()
+                .map(|&source| self.channel_units[source].clone())
+                .unwrap_or_default();
+            self.channel_types[ch] = "Derived".to_string();
+        }
+        self.assign_channel_colors();
+    }
+
+    /// Append a derived channel and show it right away. Its buffer is filled in
+    /// from the buffered source channels, so the other channels, their filters and
+    /// a frozen display carry on as they were.
+    fn add_derived_channel(&mut self, channel: DerivedChannel) {
+        let stream_channel_count = self.stream_channel_count();
+        if !channel.fits(stream_channel_count) {
+            return;
+        }
+        // the buffer holds filtered values, and filtering the sum of channels is the
+        // same as summing the filtered channels
+        let sources = &self.data_buffer[..stream_channel_count];
+        let values: VecDeque<f32> = (0..self.timestamp_buffer.len())
+            .map(|i| {
+                let row: Vec<f32> = sources
+                    .iter()
+                    .map(|data| data.get(i).copied().unwrap_or(f32::NAN))
+                    .collect();
+                channel.compute(&row)
+            })
+            .collect();
+        self.data_buffer.push(values);
+        self.filters.push_combined(
+            &channel.weights(),
+            self.stream_sample_rate(),
+            self.filter_settings,
+        );
+        self.last_finite.resize(self.channel_count, 0.0);
+        let last_finite = channel.compute(&self.last_finite[..stream_channel_count]);
+        self.last_finite.push(last_finite);
+        if let Some(erp) = self.erp.as_mut() {
+            erp.add_channel();
+        }
+        self.derived_channels.push(channel);
+        self.resize_channel_settings(self.channel_count + 1);
+        self.label_derived_channels(stream_channel_count);
+        if let Some(selected) = self.selected_channels.last_mut() {
+            *selected = true;
+        }
+        self.last_geometry_build = None;
+    }
+
+    /// True if the channels declare different units, so no single unit applies.
+    fn mixed_units(&self) -> bool {
+        let mut declared = self.channel_units.iter().filter(|unit| !unit.is_empty());
+        declared
+            .next()
+            .is_some_and(|first| declared.any(|unit| unit != first))
+    }
+
+    /// The unit a channel's values are displayed in: its own declared unit when
+    /// units are mixed, the shared display unit otherwise.
+    fn channel_unit(&self, ch: usize) -> &str {
+        if self.mixed_units() {
+            self.channel_units.get(ch).map_or("", |unit| unit.as_str())
+        } else {
+            &self.display_unit
+        }
+    }
+
+    /// Whether the channel is marked bad.
+    fn channel_bad(&self, ch: usize) -> bool {
+        self.bad_channels.get(ch).copied().unwrap_or(false)
+    }
+
+    /// The channel's plot color, dimmed if it is marked bad.
+    fn trace_color(&self, ch: usize) -> egui::Color32 {
+        let color = self.channel_colors[ch];
+        if self.channel_bad(ch) {
+            color.gamma_multiply(BAD_CHANNEL_DIMMING)
+        } else {
+            color
+        }
+    }
+
+    /// Apply the `--profile` settings to a freshly connected stream. Labels the
+    /// stream doesn't have are left out and a band it can't filter is left
+    /// unused, with a warning.
+    fn apply_profile(&mut self, name: &str) {
+        let Some(profile) = self.profile.take() else {
+            return;
+        };
+        if profile.applies_to(name) {
+            if !profile.channels.is_empty() {
+                self.selected_channels = self
+                    .channel_names
+                    .iter()
+                    .map(|label| profile.channels.contains(label))
+                    .collect();
+            }
+            self.color_overrides.extend(profile.parsed_colors());
+            if let Some(scale) = profile.scale.filter(|s| *s > 0.0) {
+                self.data_scale = scale.clamp(*SCALE_RANGE.start(), *SCALE_RANGE.end());
+                self.channel_scales = vec![self.data_scale; self.channel_count];
+            }
+            if let Some(seconds) = profile.window_seconds {
+                self.time_window_seconds =
+                    seconds.clamp(*TIME_WINDOW_RANGE.start(), *TIME_WINDOW_RANGE.end());
+            }
+            let previous = self.filter_settings;
+            profile.apply_filters(&mut self.filter_settings);
+            if self.filter_settings != previous {
+                self.rebuild_filters();
+            }
+            let mut problems = Vec::new();
+            let missing = profile.missing_channels(&self.channel_names);
+            if !missing.is_empty() {
+                problems.push(format!("channels not in {}: {}", name, missing.join(", ")));
+            }
+            let missing = profile.missing_colors(&self.channel_names);
+            if !missing.is_empty() {
+                problems.push(format!(
+                    "colors for channels not in {}: {}",
+                    name,
+                    missing.join(", ")
+                ));
+            }
+            if profile.bandpass.is_some()
+                && let Some(problem) = self
+                    .filter_settings
+                    .bandpass
+                    .problem(self.stream_sample_rate())
+            {
+                problems.push(format!("bandpass not applied: {}", problem));
+            }
+            if !problems.is_empty() {
+                self.set_status(format!("Warning: profile {}", problems.join("; ")));
+            }
+        }
+        self.profile = Some(profile);
+    }
+
+    /// Palette colors cycled over the channels, with the user's picks applied
+    /// wherever a channel of that name exists.
+    fn assign_channel_colors(&mut self) {
+        self.channel_colors = self
+            .channel_names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| {
+                let palette = if self.light_palette {
+                    &CHANNEL_COLORS_LIGHT
+                } else {
+                    &CHANNEL_COLORS
+                };
+                self.color_overrides
+                    .get(name)
+                    .copied()
+                    .unwrap_or(palette[i % palette.len()])
+            })
+            .collect();
+    }
+
+    /// Most samples kept per channel, whatever the time window says.
+    fn buffer_sample_limit(&self) -> usize {
+        if self.custom_buffer_limit {
+            return self.max_buffer_samples.max(1);
+        }
+        let sample_rate = self.stream_sample_rate();
+        if sample_rate > 0.0 {
+            ((AUTO_BUFFER_WINDOWS * self.history_span() * sample_rate).ceil() as usize).max(1)
+        } else {
+            IRREGULAR_BUFFER_LIMIT
+        }
+    }
+
+    /// Seconds of data kept buffered: the time window, or the history if that is longer.
+    fn history_span(&self) -> f64 {
+        self.time_window_seconds.max(self.history_seconds)
+    }
+
+    /// Whether more than the time window is kept, so the overview strip has something to show.
+    fn keeps_history(&self) -> bool {
+        self.history_seconds > self.time_window_seconds
+    }
+
+    /// Rough size of the buffered samples and timestamps in bytes.
+    fn buffer_memory_bytes(&self) -> usize {
+        let values: usize = self.data_buffer.iter().map(|b| b.len()).sum();
+        values * std::mem::size_of::<f32>()
+            + self.timestamp_buffer.len() * std::mem::size_of::<f64>()
+    }
+
+    /// Raise an alert for every channel deflecting further than the threshold from
+    /// its baseline, beeping for alerts that weren't already showing.
+    fn check_alerts(&mut self, values: &[f32]) {
+        if !self.alerts_enabled {
+            return;
+        }
+        let mut new_alert = false;
+        for (ch, &value) in values.iter().enumerate() {
+            let baseline = self.channel_baselines.get(ch).copied().unwrap_or(0.0);
+            if (value as f64 - baseline).abs() > self.alert_threshold {
+                new_alert |= !self.alert_active(ch);
+                if let Some(alert) = self.channel_alerts.get_mut(ch) {
+                    *alert = Some(Instant::now());
+                }
+            }
+        }
+        if new_alert
+            && self.alert_beep
+            && self
+                .last_alert_beep
+                .is_none_or(|at| at.elapsed() >= ALERT_BEEP_INTERVAL)
+        {
+            #[cfg(feature = "audio")]
+            audio::beep(self.audio_errors.sender());
+            self.last_alert_beep = Some(Instant::now());
+        }
+    }
+
+    /// Track runs of samples at the clipping limits. A run that reaches `clip_run`
+    /// samples is recorded from its first sample and extended while it lasts.
+    fn check_clipping(&mut self, timestamp: f64, values: &[f32]) {
+        if !self.clip_detection {
+            return;
+        }
+        let tolerance = CLIP_TOLERANCE * (self.clip_max - self.clip_min).abs();
+        for (ch, &value) in values.iter().enumerate() {
+            let Some((count, start)) = self.clip_counts.get_mut(ch) else {
+                break;
+            };
+            let value = value as f64;
+            if value < self.clip_max - tolerance && value > self.clip_min + tolerance {
+                *count = 0;
+                continue;
+            }
+            if *count == 0 {
+                *start = timestamp;
+            }
+            *count += 1;
+            if *count == self.clip_run.max(1) {
+                self.clip_buffer.push_back((ch, *start, timestamp));
+            } else if *count > self.clip_run.max(1) {
+                // the newest stretch of this channel is the one still going
+                if let Some(clip) = self.clip_buffer.iter_mut().rev().find(|c| c.0 == ch) {
+                    clip.2 = timestamp;
+                }
+            }
+        }
+    }
+
+    /// Run the peak detector over the chosen channel and keep the peaks it finds.
+    fn detect_peaks(&mut self, timestamp: f64, values: &[f32]) {
+        let Some(&value) = self.peak_channel.and_then(|ch| values.get(ch)) else {
+            return;
+        };
+        let baseline = self
+            .peak_channel
+            .and_then(|ch| self.channel_baselines.get(ch))
+            .copied()
+            .unwrap_or(0.0);
+        if let Some(peak) = self
+            .peak_detector
+            .push(&self.peak_settings, timestamp, value, baseline)
+        {
+            self.peak_buffer.push_back(peak);
+        }
+    }
+
+    fn reset_peaks(&mut self) {
+        self.peak_detector = PeakDetector::default();
+        self.peak_buffer.clear();
+    }
+
+    /// Channels with clipping anywhere in the buffered window.
+    fn clipped_channels(&self) -> Vec<usize> {
+        let mut channels: Vec<usize> = self.clip_buffer.iter().map(|c| c.0).collect();
+        channels.sort_unstable();
+        channels.dedup();
+        channels
+    }
+
+    /// Channels with a NaN or infinite value anywhere in the buffered window.
+    fn nonfinite_channels(&self) -> Vec<usize> {
+        let Some(&oldest) = self.timestamp_buffer.front() else {
+            return Vec::new();
+        };
+        self.nonfinite_seen
+            .iter()
+            .enumerate()
+            .filter(|(_, seen)| seen.is_some_and(|t| t >= oldest))
+            .map(|(ch, _)| ch)
+            .collect()
+    }
+
+    fn alert_active(&self, ch: usize) -> bool {
+        self.channel_alerts
+            .get(ch)
+            .copied()
+            .flatten()
+            .is_some_and(|at| self.alert_latch || at.elapsed() < ALERT_HOLD)
+    }
+
+    /// Start the ERP average over, for the current epoch and channel count.
+    fn reset_erp(&mut self) {
+        let sample_rate = self.stream_sample_rate();
+        self.erp = (sample_rate > 0.0).then(|| {
+            ErpAverager::new(
+                self.channel_count,
+                sample_rate,
+                self.erp_pre_seconds,
+                self.erp_post_seconds,
+            )
+        });
+    }
+
+    /// Start the spectrogram over from the buffered data of its channel, if it
+    /// is shown and the stream has a regular sample rate.
+    fn reset_spectrogram(&mut self) {
+        if self.spectrogram_settings.channel >= self.data_buffer.len() {
+            self.spectrogram_settings.channel = 0;
+        }
+        let sample_rate = self.stream_sample_rate();
+        self.spectrogram =
+            (sample_rate > 0.0 && self.plot_mode == PlotMode::Spectrogram).then(|| {
+                let mut spectrogram = Spectrogram::new(self.spectrogram_settings, sample_rate);
+                spectrogram.set_window(self.time_window_seconds);
+                if let Some(channel_data) = self.data_buffer.get(self.spectrogram_settings.channel)
+                {
+                    for &value in channel_data {
+                        spectrogram.push(value);
+                    }
+                }
+                spectrogram
+            });
+    }
+
+    /// Recreate the filters for the current settings, channel count and sample
+    /// rate. Filter state starts from zero.
+    fn rebuild_filters(&mut self) {
+        self.filters = ChannelFilters::new(
+            self.channel_count,
+            self.stream_sample_rate(),
+            self.filter_settings,
+            &self.filter_bypass,
+        );
+    }
+
+    /// Compare a counter value with the previous one; anything but a step of
+    /// one means samples went missing or the counter restarted.
+    fn check_sequence(&mut self, value: f64) {
+        if let Some(last) = self.last_sequence_value {
+            let step = value - last;
+            if step > 1.0 {
+                self.sequence_skipped += (step - 1.0).round() as usize;
+            } else if step < 1.0 {
+                self.sequence_resets += 1;
+            }
+        }
+        self.last_sequence_value = Some(value);
+    }
+
+    /// Record a gap if the step from the previous timestamp is well beyond the
+    /// nominal sample interval. Irregular streams have no interval to compare to.
+    fn check_gap(&mut self, timestamp: f64) {
+        let sample_rate = self.stream_sample_rate();
+        if let Some(previous) = self.last_arrival_timestamp
+            && sample_rate > 0.0
+            && timestamp - previous > GAP_THRESHOLD / sample_rate
+        {
+            self.gap_count += 1;
+            self.gap_buffer.push_back((previous, timestamp));
+            self.event_log.push(
+                Severity::Warning,
+                format!("Timing gap of {:.3} s", timestamp - previous),
+            );
+        }
+        self.last_arrival_timestamp = Some(timestamp);
+    }
+
+    /// Samples per second actually delivered over the last few seconds, once
+    /// there is at least a second of history.
+    fn effective_sample_rate(&self) -> Option<f64> {
+        let (oldest, _) = self.arrivals.front()?;
+        let span = oldest.elapsed().as_secs_f64();
+        if span < 1.0 {
+            return None;
+        }
+        // the oldest entry's samples arrived before its timestamp, outside the span
+        let count: usize = self.arrivals.iter().skip(1).map(|(_, n)| n).sum();
+        Some(count as f64 / span)
+    }
+
+    fn reset_gap_detection(&mut self) {
+        self.gap_buffer.clear();
+        self.gap_count = 0;
+        self.last_arrival_timestamp = None;
+    }
+
+    fn reset_sequence_check(&mut self) {
+        self.last_sequence_value = None;
+        self.sequence_skipped = 0;
+        self.sequence_resets = 0;
+    }
+
+    /// Thin out the pending data samples according to `drop_policy` when more
+    /// than `max_display_backlog` of them arrived since the last frame.
+    /// Non-data responses are always kept, in order.
+    fn apply_drop_policy(&mut self, responses: Vec<LslResponse>) -> Vec<LslResponse> {
+        let data_count = responses
+            .iter()
+            .filter(|r| matches!(r, LslResponse::Data(_)))
+            .count();
+        let limit = self.max_display_backlog.max(1);
+        if data_count <= limit {
+            return responses;
+        }
+
+        let mut kept = Vec::with_capacity(responses.len() - data_count + limit);
+        match self.drop_policy {
+            SampleDropPolicy::DropOldest | SampleDropPolicy::DropNewest => {
+                let (first_kept, last_kept) = if self.drop_policy == SampleDropPolicy::DropOldest {
+                    (data_count - limit, data_count)
+                } else {
+                    (0, limit)
+                };
+                let mut data_idx = 0;
+                for response in responses {
+                    if let LslResponse::Data(_) = response {
+                        let keep = data_idx >= first_kept && data_idx < last_kept;
+                        data_idx += 1;
+                        if !keep {
+                            continue;
+                        }
+                    }
+                    kept.push(response);
+                }
+            }
+            SampleDropPolicy::Coalesce => {
+                let group_size = data_count.div_ceil(limit);
+                let mut group: Vec<DataSample> = Vec::with_capacity(group_size);
+                for response in responses {
+                    match response {
+                        LslResponse::Data(sample) => {
+                            group.push(sample);
+                            if group.len() == group_size {
+                                kept.push(LslResponse::Data(coalesce_samples(&group)));
+                                group.clear();
+                            }
+                        }
+                        other => {
+                            if !group.is_empty() {
+                                kept.push(LslResponse::Data(coalesce_samples(&group)));
+                                group.clear();
+                            }
+                            kept.push(other);
+                        }
+                    }
+                }
+                if !group.is_empty() {
+                    kept.push(LslResponse::Data(coalesce_samples(&group)));
+                }
+            }
+        }
+
+        let kept_data = kept
+            .iter()
+            .filter(|r| matches!(r, LslResponse::Data(_)))
+            .count();
+        self.dropped_display_samples += data_count - kept_data;
+        kept
+    }
+
+    /// Switch to a new unit conversion, rescaling what's already buffered so the
+    /// display stays consistent.
+    fn set_unit_conversion(&mut self, factor: f64, unit: String) {
+        let ratio = factor / self.unit_factor;
+        if ratio.is_finite() && ratio != 1.0 {
+            for channel_data in self.data_buffer.iter_mut() {
+                for value in channel_data.iter_mut() {
+                    *value = (*value as f64 * ratio) as f32;
+                }
+            }
+            for baseline in self.channel_baselines.iter_mut() {
+                *baseline *= ratio;
+            }
+            self.display_caches.clear();
+        }
+        self.unit_factor = factor;
+        self.display_unit = unit;
+    }
+
+    /// Start writing every selected channel to its own CSV file in `dir`.
+    fn start_per_channel_export(&mut self, dir: std::path::PathBuf) {
+        let timestamps: Vec<f64> = self.timestamp_buffer.iter().copied().collect();
+        let channels: Vec<(String, Vec<f32>)> = self
+            .data_buffer
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| self.selected_channels.get(*i).copied().unwrap_or(false))
+            .map(|(i, channel_data)| {
+                (
+                    self.channel_names[i].clone(),
+                    channel_data.iter().copied().collect(),
+                )
+            })
+            .collect();
+        if channels.is_empty() {
+            self.set_status("No channels selected for export");
+            return;
+        }
+        self.export_job = Some(export::spawn_per_channel_export(
+            dir,
+            timestamps,
+            channels,
+            self.annotations.iter().cloned().collect(),
+            self.export_downsample_factor,
+            self.export_aggregation,
+        ));
+    }
+
+    /// Ask where to save the plot area of `screenshot` and write it there as a PNG.
+    fn save_plot_image(
+        &mut self,
+        ctx: &egui::Context,
+        screenshot: &egui::ColorImage,
+        capture_rect: egui::Rect,
+    ) {
+        let image = screenshot.region(&capture_rect, Some(ctx.pixels_per_point()));
+        let unix_time = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("PNG image", &["png"])
+            .set_file_name(format!("lsl_plot_{}.png", unix_time))
+            .save_file()
+        else {
+            return;
+        };
+        self.set_status(match snapshot::save_png(&path, &image, self.image_scale) {
+            Ok(()) => format!("Saved image to {}", path.display()),
+            Err(e) => format!("Error: {}", e),
+        });
+    }
+
+    /// Write the statistics in the table, with the range they were taken over.
+    fn export_statistics(&self, path: &std::path::Path) -> std::io::Result<()> {
+        let span = self.stats_range.unwrap_or((
+            self.timestamp_buffer.front().copied().unwrap_or(0.0),
+            self.timestamp_buffer.back().copied().unwrap_or(0.0),
+        ));
+        let rows: Vec<(&str, ChannelStats)> = self
+            .channel_stats
+            .iter()
+            .map(|(ch, s)| (self.channel_names[*ch].as_str(), *s))
+            .collect();
+        stats::write_csv(path, span, &rows)
+    }
+
+    /// Write the buffered samples of all channels to a minimal XDF file.
+    fn export_xdf(&self, path: &std::path::Path) -> std::io::Result<()> {
+        let name = self
+            .selected_stream
+            .as_ref()
+            .map_or("", |s| s.name.as_str());
+        let timestamps: Vec<f64> = self.timestamp_buffer.iter().copied().collect();
+        let channels: Vec<Vec<f32>> = self
+            .data_buffer
+            .iter()
+            .map(|channel_data| channel_data.iter().copied().collect())
+            .collect();
+        let annotations: Vec<(f64, String)> = self.annotations.iter().cloned().collect();
+        xdf::write_xdf(
+            path,
+            &xdf::XdfStream {
+                name,
+                channel_names: &self.channel_names,
+                unit: &self.display_unit,
+                sample_rate: self.stream_sample_rate(),
+                timestamps: &timestamps,
+                channels: &channels,
+                annotations: &annotations,
+            },
+        )
+    }
+
+    fn poll_export(&mut self) {
+        if let Some(job) = &mut self.export_job
+            && let Some(result) = job.poll()
+        {
+            self.set_status(match result {
+                Ok(n) => format!("Exported {} channel file(s)", n),
+                Err(e) => format!("Error: {}", e),
+            });
+            self.export_job = None;
+        }
+    }
+
+    #[cfg(feature = "audio")]
+    fn poll_audio_errors(&mut self) {
+        let errors: Vec<String> = self.audio_errors.drain().collect();
+        for error in errors {
+            self.set_status(error);
+        }
+    }
+
+    fn stream_xml_window(&mut self, ctx: &egui::Context) {
+        let Some((xml, tree)) = &self.stream_xml else {
+            return;
+        };
+        let mut open = self.show_stream_xml;
+        egui::Window::new("Stream Info")
+            .open(&mut open)
+            .default_width(400.0)
+            .show(ctx, |ui| {
+                ui.checkbox(&mut self.show_raw_xml, "Raw XML");
+                egui::ScrollArea::both().show(ui, |ui| match tree {
+                    Some(tree) if !self.show_raw_xml => {
+                        xml_node_ui(ui, tree, egui::Id::new("stream_xml"))
+                    }
+                    _ => {
+                        ui.add(egui::Label::new(
+                            egui::RichText::new(xml.as_str()).monospace(),
+                        ));
+                    }
+                });
+            });
+        self.show_stream_xml = open;
+    }
+
+    fn gallery_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_gallery;
+        egui::Window::new("Snapshots")
+            .open(&mut open)
+            .default_width(500.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    if ui.button("Export all…").clicked()
+                        && let Some(dir) = rfd::FileDialog::new().pick_folder()
+                    {
+                        self.set_status(match snapshot::export_snapshots(&dir, &self.snapshots) {
+                            Ok(n) => {
+                                format!("Exported {} snapshot(s) to {}", n, dir.display())
+                            }
+                            Err(e) => format!("Error: {}", e),
+                        });
+                    }
+                    if ui.button("Clear").clicked() {
+                        self.snapshots.clear();
+                    }
+                });
+                egui::ScrollArea::horizontal().show(ui, |ui| {
+                    ui.horizontal(|ui| {
+                        let mut remove = None;
+                        for (i, snapshot) in self.snapshots.iter().enumerate() {
+                            ui.vertical(|ui| {
+                                ui.add(
+                                    egui::Image::new(&snapshot.texture)
+                                        .max_height(120.0)
+                                        .maintain_aspect_ratio(true),
+                                )
+                                .on_hover_text(&snapshot.settings);
+                                ui.horizontal(|ui| {
+                                    ui.label(format!("t = {:.3}", snapshot.stream_time));
+                                    if ui.small_button("✖").clicked() {
+                                        remove = Some(i);
+                                    }
+                                });
+                            });
+                        }
+                        if let Some(i) = remove {
+                            self.snapshots.remove(i);
+                        }
+                    });
+                });
+            });
+        self.show_gallery = open && !self.snapshots.is_empty();
+    }
+
+    fn statistics_ui(&mut self, ui: &mut egui::Ui) {
+        if self
+            .last_stats_update
+            .is_none_or(|last| last.elapsed() >= STATS_UPDATE_INTERVAL)
+        {
+            let (first, end) = match self.stats_range {
+                Some((start, end)) => (
+                    self.timestamp_buffer.partition_point(|&t| t < start),
+                    self.timestamp_buffer.partition_point(|&t| t <= end),
+                ),
+                None => (0, self.timestamp_buffer.len()),
+            };
+            self.channel_stats = (0..self.data_buffer.len())
+                .filter(|&i| self.channel_shown(i) && !self.channel_bad(i))
+                .filter_map(|i| {
+                    let channel_data = &self.data_buffer[i];
+                    let end = end.min(channel_data.len());
+                    ChannelStats::compute(channel_data.range(first.min(end)..end).copied())
+                        .map(|s| (i, s))
+                })
+                .collect();
+            self.last_stats_update = Some(Instant::now());
+        }
+
+        if let Some((start, end)) = self.stats_range {
+            let mut clear = false;
+            ui.horizontal(|ui| {
+                ui.label(format!(
+                    "Range {} to {} ({:.3} s)",
+                    self.format_timestamp(start),
+                    self.format_timestamp(end),
+                    end - start
+                ));
+                clear = ui
+                    .small_button("✖")
+                    .on_hover_text("Back to the whole buffer")
+                    .clicked();
+            });
+            if clear {
+                self.stats_range = None;
+                self.last_stats_update = None;
+            }
+        } else {
+            ui.label(
+                egui::RichText::new("Shift-drag on the plot to select a time range")
+                    .small()
+                    .weak(),
+            );
+        }
+        if let Some(name) = self.peak_channel.and_then(|ch| self.channel_names.get(ch)) {
+            let rate = peaks::rate_per_minute(self.peak_buffer.iter().map(|(t, _)| *t));
+            ui.label(format!(
+                "{} peak rate: {}",
+                name,
+                rate.map_or("–".to_string(), |rate| format!("{:.0} /min", rate))
+            ));
+        }
+        if self.channel_stats.is_empty() {
+            ui.label(if self.stats_range.is_some() {
+                "No data in the selected range"
+            } else {
+                "No data for the selected channels"
+            });
+            return;
+        }
+
+        const COLUMNS: [&str; 7] = ["Channel", "Min", "Max", "P-P", "Mean", "Std", "RMS"];
+        let rows: Vec<[String; 7]> = self
+            .channel_stats
+            .iter()
+            .map(|(ch, s)| {
+                [
+                    self.channel_names[*ch].clone(),
+                    format!("{:.3}", s.min),
+                    format!("{:.3}", s.max),
+                    format!("{:.3}", s.peak_to_peak()),
+                    format!("{:.3}", s.mean),
+                    format!("{:.3}", s.std),
+                    format!("{:.3}", s.rms),
+                ]
+            })
+            .collect();
+
+        ui.horizontal(|ui| {
+            if ui
+                .button("Copy")
+                .on_hover_text("Copy the table as tab-separated text")
+                .clicked()
+            {
+                let mut text = COLUMNS.join("\t");
+                for row in rows.iter() {
+                    text += "\n";
+                    text += &row.join("\t");
+                }
+                ui.ctx().copy_text(text);
+            }
+            if ui
+                .button("Export CSV…")
+                .on_hover_text("Mean, std, min, max and peak-to-peak of every channel in the table")
+                .clicked()
+                && let Some(path) = rfd::FileDialog::new()
+                    .add_filter("CSV", &["csv"])
+                    .set_file_name("statistics.csv")
+                    .save_file()
+            {
+                self.set_status(match self.export_statistics(&path) {
+                    Ok(()) => format!("Exported statistics to {}", path.display()),
+                    Err(e) => format!("Error: failed to write {}: {}", path.display(), e),
+                });
+            }
+        });
+        ui.label(
+            egui::RichText::new(format!(
+                "Over the {}, in {}",
+                if self.stats_range.is_some() {
+                    "selected range"
+                } else {
+                    "buffered window"
+                },
+                self.display_unit
+            ))
+            .small()
+            .weak(),
+        );
+        let bad = (0..self.data_buffer.len())
+            .filter(|&i| self.channel_shown(i) && self.channel_bad(i))
+            .count();
+        if bad > 0 {
+            ui.label(
+                egui::RichText::new(format!("{} bad channel(s) left out", bad))
+                    .small()
+                    .weak(),
+            );
+        }
+        egui::Grid::new("channel_stats")
+            .striped(true)
+            .show(ui, |ui| {
+                for column in COLUMNS {
+                    ui.strong(column);
+                }
+                ui.end_row();
+                for row in rows.iter() {
+                    for cell in row {
+                        ui.label(cell);
+                    }
+                    ui.end_row();
+                }
+            });
+    }
+
+    fn diagnostics_ui(&mut self, ui: &mut egui::Ui) {
+        if self.is_connected {
+            ui.horizontal(|ui| {
+                ui.label("Sequence counter:");
+                let previous = self.sequence_channel;
+                egui::ComboBox::from_id_source("sequence_channel")
+                    .selected_text(
+                        self.sequence_channel
+                            .and_then(|ch| self.channel_names.get(ch))
+                            .map_or("None", |name| name.as_str()),
+                    )
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut self.sequence_channel, None, "None");
+                        for (i, name) in self.channel_names.iter().enumerate() {
+                            ui.selectable_value(&mut self.sequence_channel, Some(i), name);
+                        }
+                    });
+                if self.sequence_channel != previous {
+                    self.reset_sequence_check();
+                }
+            })
+            .response
+            .on_hover_text("A channel that counts up by one per sample, used to detect dropped samples exactly");
+            if self.sequence_channel.is_some() {
+                let text = format!(
+                    "Dropped samples: {} (counter restarts: {})",
+                    self.sequence_skipped, self.sequence_resets
+                );
+                if self.sequence_skipped > 0 || self.sequence_resets > 0 {
+                    ui.colored_label(egui::Color32::from_rgb(255, 165, 0), text);
+                } else {
+                    ui.label(text);
+                }
+            }
+            if let Some((chunks_per_sec, avg_chunk_size, max_chunk_size)) = self.pull_stats {
+                ui.label(format!(
+                    "Pulls: {:.1} chunks/s, {:.1} samples on average, {} at most ({:.0} samples/s)",
+                    chunks_per_sec,
+                    avg_chunk_size,
+                    max_chunk_size,
+                    chunks_per_sec * avg_chunk_size
+                ))
+                .on_hover_text("How the inlet hands out data: few large chunks mean the source sends in bursts");
+            }
+            if let Some((buffered, grew)) = self.inlet_backlog {
+                let rate = self.stream_sample_rate();
+                let mut text = format!("Inlet backlog: {} samples", buffered);
+                if rate > 0.0 {
+                    text.push_str(&format!(" ({:.2} s)", buffered as f64 / rate));
+                }
+                let response = if grew {
+                    ui.colored_label(egui::Color32::from_rgb(255, 165, 0), format!("{} ↑", text))
+                } else {
+                    ui.label(text)
+                };
+                response.on_hover_text("Left in the inlet after a pull; if it keeps growing, the LSL thread isn't keeping up with the stream");
+            }
+            if let Some(latency) = self.display_latency() {
+                let text = format!("Display latency: {:.1} ms", latency * 1000.0);
+                let response = if self.latency_cap && latency * 1000.0 > self.latency_cap_ms {
+                    ui.colored_label(egui::Color32::from_rgb(255, 165, 0), text)
+                } else {
+                    ui.label(text)
+                };
+                response.on_hover_text("local_clock() minus the timestamp of the newest sample on screen: transmission, buffering and processing together");
+            }
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut self.latency_cap, "Catch up above");
+                ui.add_enabled(
+                    self.latency_cap,
+                    egui::DragValue::new(&mut self.latency_cap_ms)
+                        .range(10.0..=10_000.0)
+                        .speed(1.0)
+                        .suffix(" ms"),
+                );
+            })
+            .response
+            .on_hover_text("Drop the samples queued up in the inlet when the display latency exceeds this. Never while recording");
+        }
+
+        if self.timestamp_regressions > 0 {
+            ui.colored_label(
+                egui::Color32::from_rgb(255, 165, 0),
+                format!(
+                    "Out-of-order timestamps: {} ({})",
+                    self.timestamp_regressions,
+                    self.timestamp_regression_policy.label().to_lowercase()
+                ),
+            )
+            .on_hover_text("Samples whose timestamp wasn't after the previous one, e.g. after a clock adjustment at the source");
+        }
+
+        if self.last_geometry_build.is_some() {
+            ui.label(format!(
+                "Plot rebuild: {:.2} ms",
+                self.geometry_build_time.as_secs_f64() * 1000.0
+            ))
+            .on_hover_text(
+                "Time to turn the buffers into plot points, done at the display update rate",
+            );
+        }
+
+        // LSL clock vs the OS clock since startup
+        let now = lsl::local_clock();
+        ui.label(format!("local_clock(): {:.3} s", now));
+        if let Some((lsl_start, os_start)) = self.clock_reference {
+            let os_elapsed = os_start.elapsed().as_secs_f64();
+            let drift = (now - lsl_start) - os_elapsed;
+            let ppm = if os_elapsed > 0.0 {
+                drift / os_elapsed * 1e6
+            } else {
+                0.0
+            };
+            ui.label(format!(
+                "Clock drift vs OS: {:+.3} ms over {:.0} s ({:+.1} ppm)",
+                drift * 1000.0,
+                os_elapsed,
+                ppm
+            ));
+        }
+    }
+
+    /// Whether a sample at `timestamp` can't follow the newest one at `latest`.
+    /// Irregular streams may repeat a timestamp, regular ones may not.
+    fn timestamp_out_of_order(&self, latest: f64, timestamp: f64) -> bool {
+        if self.irregular {
+            timestamp < latest
+        } else {
+            timestamp <= latest
+        }
+    }
+
+    /// The timestamp a monotonized sample gets: one nominal interval after `latest`,
+    /// or barely after it for an irregular stream.
+    fn next_timestamp(&self, latest: f64) -> f64 {
+        let rate = self.stream_sample_rate();
+        if rate > 0.0 {
+            latest + 1.0 / rate
+        } else {
+            latest + latest.abs().max(1.0) * f64::EPSILON
+        }
+    }
+
+    /// A timestamp as time of day when that is chosen and known, in LSL seconds otherwise.
+    fn format_timestamp(&self, timestamp: f64) -> String {
+        let timestamp = timestamp + self.timestamp_shift();
+        match self.wall_clock_offset.filter(|_| self.show_wall_clock) {
+            Some(offset) => format_time_of_day(timestamp + offset),
+            None => format!("{:.*} s", self.timestamp_decimals, timestamp),
+        }
+    }
+
+    /// What to add to a buffered timestamp to show it in the chosen clock. With
+    /// ClockSync applied the buffer holds local times; the source's own clock is
+    /// that minus the latest offset. Without ClockSync they are source times already.
+    fn timestamp_shift(&self) -> f64 {
+        if self.show_source_clock
+            && self
+                .applied_postprocessing
+                .contains(&PostprocessingFlag::ClockSync)
+        {
+            -self.time_corrections.back().copied().unwrap_or(0.0)
+        } else {
+            0.0
+        }
+    }
+
+    /// Nominal sample rate of the connected stream, or 0.0 if unknown/irregular.
+    fn stream_sample_rate(&self) -> f64 {
+        self.selected_stream.as_ref().map_or(0.0, |s| s.sample_rate)
+    }
+
+    /// Set the time window and display downsampling to suit the connected stream's
+    /// rate. Irregular streams keep what they have.
+    fn adapt_to_sample_rate(&mut self) {
+        let rate = self.stream_sample_rate();
+        if rate <= 0.0 {
+            return;
+        }
+        self.time_window_seconds = auto_time_window(rate);
+        self.downsample_factor = auto_downsample_factor(rate);
+        self.snap_back_to_live = true;
+    }
+
+    /// The channels to tick on connect: those of the auto-selected types, or all
+    /// of them if no type matches, capped because dense montages are slow to draw.
+    fn auto_selected_channels(&self, channel_types: &[String]) -> Vec<bool> {
+        let wanted: Vec<String> = self
+            .auto_select_types
+            .split(',')
+            .map(|t| t.trim().to_lowercase())
+            .filter(|t| !t.is_empty())
+            .collect();
+        let matches: Vec<bool> = channel_types
+            .iter()
+            .map(|t| wanted.is_empty() || wanted.contains(&t.trim().to_lowercase()))
+            .collect();
+        let any_match = matches.iter().any(|&m| m);
+        let mut ticked = 0;
+        matches
+            .into_iter()
+            .map(|m| {
+                let select = (m || !any_match) && ticked < self.max_auto_selected_channels;
+                ticked += select as usize;
+                select
+            })
+            .collect()
+    }
+
+    /// Hold the traces on screen so they can be compared with the live data.
+    /// Values are kept rather than plot coordinates, so later changes of scale,
+    /// offset or baseline apply to them as they do to the live traces.
+    fn hold_reference_traces(&mut self) {
+        let layout = self.plot_layout;
+        let held = self
+            .plot_geometry
+            .traces
+            .iter()
+            .map(|trace| {
+                let points = trace
+                    .current
+                    .iter()
+                    .chain(trace.previous.iter())
+                    .map(|point| [point.x, trace.value_at(point.y, layout)])
+                    .collect();
+                (trace.channel, points)
+            })
+            .collect();
+        self.reference_traces = Some(held);
+    }
+
+    /// The buffered sample closest to `timestamp`: its timestamp and the value of
+    /// each channel in `channels`, referenced the way the plot shows it.
+    fn values_at(&self, timestamp: f64, channels: &[usize]) -> Option<(f64, Vec<(usize, f64)>)> {
+        let after = self.timestamp_buffer.partition_point(|&t| t < timestamp);
+        let index = [after.checked_sub(1), Some(after)]
+            .into_iter()
+            .flatten()
+            .filter(|&i| i < self.timestamp_buffer.len())
+            .min_by(|&a, &b| {
+                (self.timestamp_buffer[a] - timestamp)
+                    .abs()
+                    .total_cmp(&(self.timestamp_buffer[b] - timestamp).abs())
+            })?;
+        let reference = self.reference_channel.and_then(|ref_idx| {
+            let value = *self.data_buffer.get(ref_idx)?.get(index)? as f64;
+            // the stacked layout subtracts the reference's deviation from its baseline
+            Some(match self.plot_layout {
+                PlotLayout::Stacked => value - self.channel_baselines[ref_idx],
+                PlotLayout::Overlay => value,
+            })
+        });
+        let values = channels
+            .iter()
+            .filter_map(|&ch| {
+                let value = *self.data_buffer.get(ch)?.get(index)? as f64;
+                Some((ch, value - reference.unwrap_or(0.0)))
+            })
+            .collect();
+        Some((self.timestamp_buffer[index], values))
+    }
+
+    /// A legend in the plot's upper right corner with every shown channel's
+    /// value at the crosshair.
+    fn crosshair_readout_ui(
+        &self,
+        ctx: &egui::Context,
+        plot_rect: egui::Rect,
+        timestamp: f64,
+        channels: &[usize],
+    ) {
+        let Some((sample_time, values)) = self.values_at(timestamp, channels) else {
+            return;
+        };
+        egui::Area::new(egui::Id::new("crosshair_readout"))
+            .fixed_pos(plot_rect.right_top() + egui::vec2(-8.0, 8.0))
+            .pivot(egui::Align2::RIGHT_TOP)
+            .interactable(false)
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.label(format!("t = {}", self.format_timestamp(sample_time)));
+                    egui::Grid::new("crosshair_values").show(ui, |ui| {
+                        for (ch, value) in values {
+                            ui.colored_label(self.channel_colors[ch], &self.channel_names[ch]);
+                            ui.label(format!("{:.3} {}", value, self.channel_unit(ch)));
+                            ui.end_row();
+                        }
+                    });
+                });
+            });
+    }
+
+    /// Draw each stacked lane's zero line and short ticks at the roundest value
+    /// whose deflection still fits inside the lane, labelled with that value.
+    fn draw_lane_grid(
+        &self,
+        plot_ui: &mut egui_plot::PlotUi,
+        traces: &[TraceGeometry],
+        window: f64,
+    ) {
+        let color = egui::Color32::from_rgba_unmultiplied(128, 128, 128, 110);
+        let tick_length = window * 0.02;
+        for trace in traces {
+            let zero = trace.lane_center + trace.offset;
+            let half_height = trace.lane_height / 2.0;
+            if (zero - trace.lane_center).abs() > half_height {
+                continue;
+            }
+            plot_ui.line(
+                Line::new(
+                    format!("Channel {} zero", trace.channel),
+                    vec![[0.0, zero], [window, zero]],
+                )
+                .stroke(Stroke::new(1.0, color)),
+            );
+
+            // values are plotted as value * scale / SCALE_NORMALIZATION from the zero line
+            let room = half_height - (zero - trace.lane_center).abs();
+            let tick = round_tick_value(room * 0.9 * SCALE_NORMALIZATION / trace.scale);
+            if tick <= 0.0 {
+                continue;
+            }
+            let height = tick * trace.scale / SCALE_NORMALIZATION;
+            for y in [zero + height, zero - height] {
+                plot_ui.line(
+                    Line::new(
+                        format!("Channel {} ticks", trace.channel),
+                        vec![[0.0, y], [tick_length, y]],
+                    )
+                    .stroke(Stroke::new(1.0, color)),
+                );
+            }
+            let label = format!("±{} {}", tick, self.channel_unit(trace.channel));
+            plot_ui.text(
+                Text::new(
+                    format!("Channel {} ticks", trace.channel),
+                    PlotPoint::new(tick_length, zero + height),
+                    egui::RichText::new(label.trim_end()).small(),
+                )
+                .anchor(egui::Align2::LEFT_CENTER)
+                .color(color),
+            );
+        }
+    }
+
+    /// Draw a vertical calibration bar of a round value in the lower right corner.
+    /// Traces scaled differently can't share one bar, so it is left out for them.
+    fn draw_scale_bar(&self, plot_ui: &mut egui_plot::PlotUi, traces: &[TraceGeometry]) {
+        let Some(scale) = traces.first().map(|trace| trace.scale) else {
+            return;
+        };
+        if traces.iter().any(|trace| trace.scale != scale) {
+            return;
+        }
+        // at most about half a lane, so it stays clear of the traces' own range
+        let value = round_tick_value(0.5 * SCALE_NORMALIZATION / scale);
+        if value <= 0.0 {
+            return;
+        }
+        let height = value * scale / SCALE_NORMALIZATION;
+        let bounds = plot_ui.plot_bounds();
+        let (min, max) = (bounds.min(), bounds.max());
+        let x = max[0] - (max[0] - min[0]) * 0.03;
+        let bottom = min[1] + (max[1] - min[1]) * 0.03;
+        let color = plot_ui.ctx().style().visuals.text_color();
+        plot_ui.line(
+            Line::new("Scale bar", vec![[x, bottom], [x, bottom + height]])
+                .stroke(Stroke::new(2.0, color)),
+        );
+        let unit = if self.mixed_units() {
+            ""
+        } else {
+            self.display_unit.as_str()
+        };
+        plot_ui.text(
+            Text::new(
+                "Scale bar",
+                PlotPoint::new(x, bottom + height / 2.0),
+                egui::RichText::new(format!("{} {}", value, unit).trim_end()).small(),
+            )
+            .anchor(egui::Align2::RIGHT_CENTER)
+            .color(color),
+        );
+    }
+
+    /// Gridlines in the picked color, in place of egui_plot's own which always take
+    /// the theme's text color. Like those, they fade out as they get denser.
+    fn draw_grid(&self, plot_ui: &mut egui_plot::PlotUi, x_grid: Option<(f64, f64)>, lanes: usize) {
+        let bounds = plot_ui.plot_bounds();
+        let (min, max) = (bounds.min(), bounds.max());
+        let dvalue_dpos = plot_ui.transform().dvalue_dpos();
+        let input = |axis: usize| GridInput {
+            bounds: (min[axis], max[axis]),
+            base_step_size: dvalue_dpos[axis].abs() * *GRID_FADE_RANGE.start() as f64,
+        };
+        let x_marks = match x_grid {
+            Some((step, origin)) => time_grid_marks((min[0], max[0]), step, origin),
+            None => egui_plot::log_grid_spacer(10)(input(0)),
+        };
+        let y_marks = if self.plot_layout == PlotLayout::Stacked {
+            lane_grid_marks(lanes, self.row_spacing, self.lane_grid)
+        } else if self.log_y {
+            signed_log_grid_marks((min[1], max[1]))
+        } else {
+            egui_plot::log_grid_spacer(10)(input(1))
+        };
+        let stroke = |axis: usize, step_size: f64| {
+            let spacing = (step_size / dvalue_dpos[axis]).abs() as f32;
+            (spacing > *GRID_FADE_RANGE.start()).then(|| {
+                let strength = egui::remap_clamp(spacing, GRID_FADE_RANGE, 0.0..=1.0);
+                Stroke::new(1.0, self.grid_color.gamma_multiply(strength.sqrt()))
+            })
+        };
+        for mark in x_marks {
+            if let Some(stroke) = stroke(0, mark.step_size) {
+                plot_ui.vline(VLine::new("", mark.value).stroke(stroke));
+            }
+        }
+        for mark in y_marks {
+            if let Some(stroke) = stroke(1, mark.step_size) {
+                plot_ui.hline(HLine::new("", mark.value).stroke(stroke));
+            }
+        }
+    }
+
+    /// Map scroll and drag gestures over the (navigation-locked) plot onto the
+    /// display scale and per-channel offsets. `lanes` maps lane index to channel index.
+    fn handle_plot_interaction(&mut self, plot_ui: &mut egui_plot::PlotUi, lanes: &[usize]) {
+        let response = plot_ui.response();
+        let row_spacing = self.row_spacing;
+        let lane_under_pointer = || {
+            let pointer = plot_ui.pointer_coordinate()?;
+            let lane = (-pointer.y / row_spacing).round();
+            if lane < 0.0 {
+                return None;
+            }
+            lanes.get(lane as usize).copied()
+        };
+
+        if response.hovered() {
+            // egui turns Ctrl + scroll into a zoom gesture
+            let zoom = plot_ui.ctx().input(|i| i.zoom_delta()) as f64;
+            if zoom != 1.0 {
+                self.scale_all_channels(zoom);
+            }
+            let scroll = plot_ui.ctx().input(|i| i.smooth_scroll_delta.y);
+            if scroll != 0.0 {
+                let zoom = |scale: f64| {
+                    (scale * (scroll as f64 / 200.0).exp())
+                        .clamp(*SCALE_RANGE.start(), *SCALE_RANGE.end())
+                };
+                // linked gains zoom together, otherwise only the lane under the pointer
+                if self.link_scales {
+                    self.data_scale = zoom(self.data_scale);
+                } else if let Some(ch) = lane_under_pointer() {
+                    self.channel_scales[ch] = zoom(self.channel_scales[ch]);
+                }
+            }
+        }
+
+        if response.double_clicked()
+            && let Some(ch) = lane_under_pointer()
+        {
+            self.channel_offsets[ch] = 0.0;
+        }
+        if response.drag_started() {
+            self.dragged_channel = lane_under_pointer();
+        }
+        if response.dragged()
+            && let Some(ch) = self.dragged_channel
+        {
+            self.channel_offsets[ch] += plot_ui.pointer_coordinate_drag_delta().y as f64;
+        }
+        if response.drag_stopped() {
+            self.dragged_channel = None;
+        }
+    }
+
+    /// Track a Shift-drag over the plot as the statistics range, in timestamps so it
+    /// stays on the same data as the sweep moves on.
+    fn handle_range_selection(&mut self, plot_ui: &egui_plot::PlotUi, geometry: &PlotGeometry) {
+        let response = plot_ui.response();
+        if let Some(pointer) = plot_ui.pointer_coordinate() {
+            let timestamp =
+                geometry.timestamp_at(pointer.x, self.time_axis_scale, self.time_window_seconds);
+            if response.drag_started() {
+                self.range_drag_start = Some(timestamp);
+            }
+            if let Some(start) = self.range_drag_start {
+                self.stats_range = Some((start.min(timestamp), start.max(timestamp)));
+                self.last_stats_update = None;
+            }
+        }
+        if response.drag_stopped() || !response.dragged() {
+            self.range_drag_start = None;
+        }
+    }
+
+    /// Multiply the master gain, and every channel's own gain, by `factor`.
+    fn scale_all_channels(&mut self, factor: f64) {
+        let clamp = |scale: f64| scale.clamp(*SCALE_RANGE.start(), *SCALE_RANGE.end());
+        self.data_scale = clamp(self.data_scale * factor);
+        for scale in self.channel_scales.iter_mut() {
+            *scale = clamp(*scale * factor);
+        }
+    }
+
+    /// The audio monitor controls: channel, mode, pitch range, volume and playback.
+    #[cfg(feature = "audio")]
+    fn audio_ui(&mut self, ui: &mut egui::Ui) {
+        ui.group(|ui| {
+            ui.label("Audio Monitor");
+            egui::ComboBox::from_id_source("audio_channel")
+                .selected_text(
+                    self.channel_names
+                        .get(self.audio_channel)
+                        .cloned()
+                        .unwrap_or_default(),
+                )
+                .show_ui(ui, |ui| {
+                    for (i, name) in self.channel_names.iter().enumerate() {
+                        ui.selectable_value(&mut self.audio_channel, i, name);
+                    }
+                });
+            ui.horizontal(|ui| {
+                for mode in AudioMode::ALL {
+                    if ui
+                        .selectable_value(&mut self.audio_mode, mode, mode.label())
+                        .changed()
+                        && let Some(monitor) = &self.audio_monitor
+                    {
+                        monitor.set_mode(mode);
+                    }
+                }
+            });
+            ui.add_enabled_ui(self.audio_mode == AudioMode::Pitch, |ui| {
+                ui.horizontal(|ui| {
+                    let (low, high) = &mut self.audio_pitch_range;
+                    let low_changed = ui
+                        .add(
+                            egui::DragValue::new(low)
+                                .range(audio::PITCH_LIMITS)
+                                .speed(5.0)
+                                .suffix(" Hz"),
+                        )
+                        .changed();
+                    ui.label("to");
+                    let high_changed = ui
+                        .add(
+                            egui::DragValue::new(high)
+                                .range(audio::PITCH_LIMITS)
+                                .speed(5.0)
+                                .suffix(" Hz"),
+                        )
+                        .changed();
+                    if (low_changed || high_changed)
+                        && let Some(monitor) = &self.audio_monitor
+                    {
+                        monitor.set_pitch_range(self.audio_pitch_range);
+                    }
+                })
+                .response
+                .on_hover_text(
+                    "Pitch at the bottom and top of the signal, which is scaled like the display",
+                );
+            });
+            if ui
+                .add(egui::Slider::new(&mut self.audio_volume, 0.0..=1.0).text("Volume"))
+                .changed()
+                && let Some(monitor) = &self.audio_monitor
+            {
+                monitor.set_volume(self.audio_volume);
+            }
+            let mut playing = self.audio_monitor.is_some();
+            if ui.checkbox(&mut playing, "Play").changed() {
+                if playing {
+                    match AudioMonitor::start(
+                        self.stream_sample_rate(),
+                        self.audio_volume,
+                        self.audio_mode,
+                        self.audio_pitch_range,
+                        self.audio_errors.sender(),
+                    ) {
+                        Ok(monitor) => self.audio_monitor = Some(monitor),
+                        Err(e) => self.set_status(format!("Error: {}", e)),
+                    }
+                } else {
+                    self.audio_monitor = None;
+                }
+            }
+        });
+    }
+
+    /// Keyboard control of the display, ignored while a text field has focus.
+    fn handle_shortcuts(&mut self, ctx: &egui::Context) {
+        if ctx.wants_keyboard_input() {
+            return;
+        }
+        let pressed = |key| ctx.input_mut(|i| i.consume_key(egui::Modifiers::NONE, key));
+
+        if pressed(egui::Key::ArrowUp) {
+            self.scale_all_channels(SCALE_KEY_STEP);
+        }
+        if pressed(egui::Key::ArrowDown) {
+            self.scale_all_channels(1.0 / SCALE_KEY_STEP);
+        }
+        let window = self.time_window_seconds;
+        let stepped = if pressed(egui::Key::ArrowLeft) {
+            TIME_WINDOW_PRESETS.iter().rev().find(|&&w| w < window)
+        } else if pressed(egui::Key::ArrowRight) {
+            TIME_WINDOW_PRESETS.iter().find(|&&w| w > window)
+        } else {
+            None
+        };
+        if let Some(&stepped) = stepped {
+            self.time_window_seconds = stepped;
+            self.snap_back_to_live = true;
+        }
+        if pressed(egui::Key::Space) && self.is_connected {
+            self.frozen = !self.frozen;
+            if !self.frozen {
+                self.snap_back_to_live = true;
+            }
+        }
+        if pressed(egui::Key::B) {
+            self.baseline_correct();
+        }
+        if pressed(egui::Key::H) {
+            self.presentation_mode = !self.presentation_mode;
+        }
+        if pressed(egui::Key::A) && !self.timestamp_buffer.is_empty() {
+            self.add_annotation();
+        }
+        if pressed(egui::Key::F1) {
+            self.show_shortcuts = !self.show_shortcuts;
+        }
+    }
+
+    /// Show `message` in the status bar and keep it in the log.
+    fn set_status(&mut self, message: impl Into<String>) {
+        let message = message.into();
+        self.event_log.push(Severity::of(&message), message.clone());
+        self.status_message = message;
+    }
+
+    /// The log entries shown, one line each, for copying or saving.
+    fn log_text(&self) -> String {
+        let mut text = String::new();
+        for entry in self.event_log.entries(self.log_min_severity) {
+            text += &format!(
+                "{}\t{}\t{}",
+                format_time_of_day(entry.time),
+                entry.severity.label(),
+                entry.message
+            );
+            if entry.repeats > 0 {
+                text += &format!(" (×{})", entry.repeats + 1);
+            }
+            text += "\n";
+        }
+        text
+    }
+
+    fn log_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_log;
+        egui::Window::new("Log")
+            .open(&mut open)
+            .default_size([500.0, 300.0])
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    egui::ComboBox::from_id_source("log_min_severity")
+                        .selected_text(format!("{} and up", self.log_min_severity.label()))
+                        .show_ui(ui, |ui| {
+                            for severity in Severity::ALL {
+                                ui.selectable_value(
+                                    &mut self.log_min_severity,
+                                    severity,
+                                    format!("{} and up", severity.label()),
+                                );
+                            }
+                        });
+                    if ui.button("Copy").clicked() {
+                        ui.ctx().copy_text(self.log_text());
+                    }
+                    if ui.button("Save…").clicked()
+                        && let Some(path) = rfd::FileDialog::new()
+                            .add_filter("Text", &["txt", "log"])
+                            .set_file_name("lsl-viewer.log")
+                            .save_file()
+                    {
+                        match std::fs::write(&path, self.log_text()) {
+                            Ok(()) => {
+                                self.set_status(format!("Saved the log to {}", path.display()))
+                            }
+                            Err(e) => self.set_status(format!(
+                                "Error: failed to write {}: {}",
+                                path.display(),
+                                e
+                            )),
+                        }
+                    }
+                    if ui.button("Clear").clicked() {
+                        self.event_log.clear();
+                    }
+                });
+                ui.separator();
+                egui::ScrollArea::vertical()
+                    .stick_to_bottom(true)
+                    .auto_shrink(false)
+                    .show(ui, |ui| {
+                        egui::Grid::new("log").striped(true).show(ui, |ui| {
+                            for entry in self.event_log.entries(self.log_min_severity) {
+                                ui.label(
+                                    egui::RichText::new(format_time_of_day(entry.time))
+                                        .monospace()
+                                        .weak(),
+                                );
+                                let color = match entry.severity {
+                                    Severity::Info => ui.visuals().text_color(),
+                                    Severity::Warning => egui::Color32::from_rgb(255, 165, 0),
+                                    Severity::Error => egui::Color32::from_rgb(255, 99, 71),
+                                };
+                                ui.colored_label(color, entry.severity.label());
+                                if entry.repeats > 0 {
+                                    ui.label(format!("{} (×{})", entry.message, entry.repeats + 1));
+                                } else {
+                                    ui.label(&entry.message);
+                                }
+                                ui.end_row();
+                            }
+                        });
+                    });
+            });
+        self.show_log = open;
+    }
+
+    /// Annotate the newest sample with the typed note, or a running number without one.
+    fn add_annotation(&mut self) {
+        let Some(&timestamp) = self.timestamp_buffer.back() else {
+            return;
+        };
+        self.annotation_count += 1;
+        let note = self.annotation_note.trim();
+        let text = if note.is_empty() {
+            format!("#{}", self.annotation_count)
+        } else {
+            note.to_string()
+        };
+        self.annotations.push_back((timestamp, text));
+        self.annotation_note.clear();
+        self.last_geometry_build = None;
+    }
+
+    /// Edit or delete the annotation clicked in the plot.
+    fn annotation_window(&mut self, ctx: &egui::Context) {
+        let Some((timestamp, original, mut text)) = self.editing_annotation.take() else {
+            return;
+        };
+        let time = format!("t = {}", self.format_timestamp(timestamp));
+        let mut open = true;
+        let (mut save, mut delete) = (false, false);
+        egui::Window::new("Annotation")
+            .open(&mut open)
+            .resizable(false)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                ui.label(time);
+                let response = ui.text_edit_singleline(&mut text);
+                save = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                ui.horizontal(|ui| {
+                    save |= ui.button("Save").clicked();
+                    delete = ui.button("🗑 Delete").clicked();
+                });
+            });
+
+        let index = self
+            .annotations
+            .iter()
+            .position(|(t, text)| *t == timestamp && *text == original);
+        match index {
+            Some(i) if delete => {
+                self.annotations.remove(i);
+            }
+            Some(i) if save && !text.trim().is_empty() => {
+                self.annotations[i].1 = text.trim().to_string();
+            }
+            // pruned while the window was open
+            None => return,
+            _ => {}
+        }
+        if save || delete {
+            self.last_geometry_build = None;
+        } else if open {
+            self.editing_annotation = Some((timestamp, original, text));
+        }
+    }
+
+    fn shortcuts_window(&mut self, ctx: &egui::Context) {
+        egui::Window::new("Keyboard Shortcuts")
+            .open(&mut self.show_shortcuts)
+            .resizable(false)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                egui::Grid::new("shortcuts").striped(true).show(ui, |ui| {
+                    for (keys, action) in SHORTCUTS {
+                        ui.strong(keys);
+                        ui.label(action);
+                        ui.end_row();
+                    }
+                });
+            });
+    }
+
+    /// Whether the buffer holds at least two samples with different timestamps.
+    fn buffer_spans_time(&self) -> bool {
+        match (self.timestamp_buffer.front(), self.timestamp_buffer.back()) {
+            (Some(first), Some(last)) => first < last,
+            _ => false,
+        }
+    }
+
+    /// Whether the time axis scrolls instead of sweeping; irregular streams always do.
+    fn scrolls(&self) -> bool {
+        self.irregular || self.scroll_mode == PlotScrollMode::Scroll
+    }
+
+    /// Whether a channel is drawn: the soloed one alone, otherwise every
+    /// selected channel that isn't muted.
+    fn channel_shown(&self, ch: usize) -> bool {
+        match self.solo_channel {
+            Some(solo) => solo == ch,
+            None => {
+                self.selected_channels.get(ch).copied().unwrap_or(false)
+                    && !self.muted_channels.get(ch).copied().unwrap_or(false)
+            }
+        }
+    }
+
+    /// Selected channels in lane order, top lane first.
+    fn displayed_channels(&self) -> Vec<usize> {
+        let mut channels: Vec<usize> = self
+            .channel_order
+            .iter()
+            .copied()
+            .filter(|&ch| self.channel_shown(ch))
+            .collect();
+        if self.reverse_order {
+            channels.reverse();
+        }
+        channels
+    }
+
+    fn plot_geometry_stale(&self) -> bool {
+        // points of a channel that no longer exists can't be drawn with its name and colour
+        let outdated = self
+            .plot_geometry
+            .traces
+            .iter()
+            .any(|trace| trace.channel >= self.channel_names.len());
+        outdated
+            || self.last_geometry_build.is_none_or(|built| {
+                built.elapsed().as_secs_f64() >= 1.0 / self.display_update_hz.max(1.0)
+            })
+    }
+
+    /// Whether every per-channel vector has an entry for each buffered channel. If
+    /// not, they are reset to the buffers' channel count so indexing can't panic.
+    fn ensure_channel_state(&mut self) -> bool {
+        let count = self.data_buffer.len();
+        let lengths = [
+            self.selected_channels.len(),
+            self.muted_channels.len(),
+            self.bad_channels.len(),
+            self.channel_names.len(),
+            self.channel_units.len(),
+            self.channel_types.len(),
+            self.channel_colors.len(),
+            self.channel_baselines.len(),
+            self.channel_offsets.len(),
+            self.channel_order.len(),
+            self.channel_scales.len(),
+            self.channel_alerts.len(),
+        ];
+        if count == self.channel_count && lengths.iter().all(|&len| len == count) {
+            return true;
+        }
+        self.channel_order = (0..count).collect();
+        self.resize_channel_state(count);
+        self.set_status(format!(
+            "Warning: channel settings were out of step and were reset for {} channels",
+            count
+        ));
+        false
+    }
+
+    /// Turn the buffered samples into plot points for every selected channel.
+    fn rebuild_plot_geometry(&mut self) {
+        let started = Instant::now();
+        self.ensure_channel_state();
+        let window = self.time_window_seconds;
+        let mut geometry = PlotGeometry::default();
+
+        // Find the most recent timestamp to use as reference; while frozen the overview
+        // strip can move the view back through the history
+        let newest = self.timestamp_buffer.back().cloned().unwrap_or(0.0);
+        let latest_timestamp = self.view_end.filter(|_| self.frozen).unwrap_or(newest);
+        // with history kept, only one window of it is laid out
+        let view_start = if self.keeps_history() {
+            latest_timestamp - window
+        } else {
+            f64::NEG_INFINITY
+        };
+        let in_view = |timestamp: f64| timestamp >= view_start && timestamp <= latest_timestamp;
+        let samples_in_view = self
+            .timestamp_buffer
+            .partition_point(|&t| t <= latest_timestamp)
+            - self.timestamp_buffer.partition_point(|&t| t < view_start);
+
+        // decide on the current time window to be shown (always n * TIME_WINDOW_SECONDS, where n is an integer);
+        // when scrolling, the window simply ends at the newest sample
+        let scrolls = self.scrolls();
+        let signed_log = self.log_y && self.plot_layout == PlotLayout::Overlay;
+        let z_scored = self.z_score && self.plot_layout == PlotLayout::Overlay;
+        let t0 = if scrolls {
+            latest_timestamp - window
+        } else {
+            sweep_start(latest_timestamp, window)
+        };
+        geometry.sweep_start = t0;
+        geometry.latest_timestamp = latest_timestamp;
+
+        // if we're re-referencing, the reference channel and its baseline
+        let ref_channel: Option<(&VecDeque<f32>, f64)> =
+            self.reference_channel.and_then(|ref_idx| {
+                self.data_buffer
+                    .get(ref_idx)
+                    .map(|data| (data, self.channel_baselines[ref_idx]))
+            });
+        let mut caches = std::mem::take(&mut self.display_caches);
+        caches.resize_with(self.data_buffer.len(), ChannelCache::default);
+
+        // where a sample is drawn, and whether it belongs to the current sweep
+        let time_axis_scale = self.time_axis_scale;
+        let place = |timestamp: f64| -> (f64, bool) {
+            if time_axis_scale == TimeAxisScale::RecentEmphasis {
+                (
+                    recent_emphasis_x(latest_timestamp - timestamp, window),
+                    true,
+                )
+            } else if scrolls {
+                (timestamp - t0, true)
+            } else {
+                // We show a rolling window of data, so that new data is drawn from left to right
+                map_to_window(timestamp, latest_timestamp, window)
+            }
+        };
+        // the envelope window in samples; irregular streams have no rate to base it on
+        let envelope_window =
+            (self.envelope_mode != EnvelopeMode::Off && !self.irregular).then(|| {
+                (self.envelope_window_ms / 1000.0 * self.stream_sample_rate()).round() as usize
+            });
+
+        // dense traces are reduced to their smallest and largest value per pixel column,
+        // which looks the same as drawing every sample at a fraction of the points
+        let samples_per_column = self
+            .plot_rect
+            .filter(|_| self.pixel_decimation && self.time_axis_scale == TimeAxisScale::Linear)
+            .map_or(0.0, |rect| {
+                window * self.stream_sample_rate() / rect.width().max(1.0) as f64
+            });
+        let pixel_factor = if samples_per_column > PIXEL_DECIMATION_THRESHOLD {
+            samples_per_column.ceil() as usize
+        } else {
+            1
+        };
+        // neighbouring samples of an irregular stream can be far apart in time
+        let (n, method) = if self.irregular {
+            (1, self.decimation)
+        } else if pixel_factor > self.downsample_factor {
+            (pixel_factor, DecimationMethod::MinMax)
+        } else {
+            (self.downsample_factor.max(1), self.decimation)
+        };
+
+        // min/max groups show the extremes on purpose, filtering would flatten them
+        let anti_alias_cutoff = (self.anti_alias && n > 1 && method != DecimationMethod::MinMax)
+            .then(|| self.stream_sample_rate() / (2.0 * n as f64));
+
+        let stacked = self.plot_layout == PlotLayout::Stacked;
+        let mut plot_idx = 0;
+        for ch_idx in self.displayed_channels() {
+            let Some(channel_data) = self.data_buffer.get(ch_idx) else {
+                continue;
+            };
+            let clear_previous = !scrolls && self.sweep_boundary == SweepBoundary::Clear;
+            if !channel_data.is_empty() {
+                let mut points_vec_a: Vec<PlotPoint> = Vec::new();
+                let mut points_vec_b: Vec<PlotPoint> = Vec::new();
+
+                // baseline-correct the data; the overlay shows real values instead
+                let baseline = if stacked {
+                    self.channel_baselines[ch_idx]
+                } else {
+                    0.0
+                };
+
+                // the reference is subtracted sample by sample, before decimating
+                let key = CacheKey {
+                    baseline,
+                    reference_channel: ref_channel.and(self.reference_channel),
+                    reference_baseline: ref_channel
+                        .map(|(_, ref_baseline)| if stacked { ref_baseline } else { 0.0 }),
+                    factor: n,
+                    method,
+                    anti_alias: anti_alias_cutoff.map(|cutoff| (self.stream_sample_rate(), cutoff)),
+                };
+                let reference = ref_channel.map(|(data, _)| data);
+                let deflections =
+                    caches[ch_idx].points(key, channel_data, &self.timestamp_buffer, reference);
+                // the envelope and moving average need every sample, so they aren't cached
+                let full_rate = (envelope_window.is_some() || self.moving_average).then(|| {
+                    ChannelCache::default().points(
+                        CacheKey {
+                            factor: 1,
+                            anti_alias: None,
+                            ..key
+                        },
+                        channel_data,
+                        &self.timestamp_buffer,
+                        reference,
+                    )
+                });
+                let envelope =
+                    envelope_window
+                        .zip(full_rate.as_ref())
+                        .map(|(samples, full_rate)| {
+                            decimate(&moving_rms(full_rate, samples), n, DecimationMethod::Step)
+                        });
+                let average = full_rate
+                    .as_ref()
+                    .filter(|_| self.moving_average)
+                    .map(|full_rate| {
+                        decimate(
+                            &moving_average(full_rate, self.moving_average_window),
+                            n,
+                            DecimationMethod::Step,
+                        )
+                    });
+
+                // channel gain, or one that makes this trace fill its lane
+                let channel_scale = self.channel_scales[ch_idx];
+                let scale = match self.lane_scaling {
+                    LaneScaling::Shared => channel_scale,
+                    LaneScaling::Independent => {
+                        let max = deflections.iter().map(|(_, v)| v.abs()).fold(0.0, f64::max);
+                        if max > 0.0 {
+                            scale_for_full_scale(max)
+                        } else {
+                            channel_scale
+                        }
+                    }
+                };
+
+                let lane_center = if stacked {
+                    -self.row_spacing * plot_idx as f64
+                } else {
+                    0.0
+                };
+                let offset = self.channel_offsets[ch_idx];
+                // mean and spread of what is drawn, so every overlaid trace has the same size
+                let z_score = z_scored
+                    .then(|| ChannelStats::compute(deflections.iter().map(|(_, v)| *v as f32)))
+                    .flatten()
+                    .map(|s| (s.mean, if s.std > 0.0 { s.std } else { 1.0 }));
+                let to_y = |v: f64| {
+                    let v = z_score.map_or(v, |(mean, std)| (v - mean) / std);
+                    if stacked {
+                        v * scale / SCALE_NORMALIZATION + lane_center + offset
+                    } else if signed_log {
+                        signed_log_value(v)
+                    } else {
+                        v
+                    }
+                };
+                let mut off_scale_up = false;
+                let mut off_scale_down = false;
+                for (timestamp, v) in deflections {
+                    if !in_view(timestamp) {
+                        continue;
+                    }
+                    let val = to_y(v);
+                    if stacked {
+                        off_scale_up |= val > lane_center + self.row_spacing / 2.0;
+                        off_scale_down |= val < lane_center - self.row_spacing / 2.0;
+                    }
+                    match place(timestamp) {
+                        (x, true) => points_vec_a.push([x, val].into()),
+                        (x, false) if !clear_previous => points_vec_b.push([x, val].into()),
+                        _ => {}
+                    }
                 }
-                Err(e) => {
-                    let _ = resp_tx.send(LslResponse::Error(format!(
-                        "Failed to refresh streams: {}",
-                        e
-                    )));
+                let mut envelope_current = Vec::new();
+                let mut envelope_previous = Vec::new();
+                for (timestamp, rms) in envelope.unwrap_or_default() {
+                    if !in_view(timestamp) {
+                        continue;
+                    }
+                    match place(timestamp) {
+                        (x, true) => envelope_current.push([x, to_y(rms)].into()),
+                        (x, false) if !clear_previous => {
+                            envelope_previous.push([x, to_y(rms)].into())
+                        }
+                        _ => {}
+                    }
                 }
-            },
-            Ok(LslCommand::Connect(index)) => {
-                if let Some(stream_info) = available_streams.get(index) {
-                    channel_count = stream_info.channel_count() as usize;
-                    match StreamInlet::new(stream_info, BUFFER_SIZE, 0, true) {
-                        Ok(new_inlet) => {
-                            new_inlet
-                                .set_postprocessing(&[
-                                    lsl::ProcessingOption::ClockSync,
-                                    lsl::ProcessingOption::Dejitter,
-                                ])
-                                .expect("Failed to set postprocessing");
-
-                            //
-                            //
-                            let mut info = new_inlet.info(5.0).expect("Failed to get stream info");
-
-                            let channel_names = extract_channel_names(&mut info, channel_count);
-                            inlet = Some(new_inlet);
-                            let _ = resp_tx.send(LslResponse::Connected(
-                                stream_info.stream_name().to_string(),
-                                channel_names.clone(),
-                            ));
+                let mut average_current = Vec::new();
+                let mut average_previous = Vec::new();
+                for (timestamp, mean) in average.unwrap_or_default() {
+                    if !in_view(timestamp) {
+                        continue;
+                    }
+                    match place(timestamp) {
+                        (x, true) => average_current.push([x, to_y(mean)].into()),
+                        (x, false) if !clear_previous => {
+                            average_previous.push([x, to_y(mean)].into())
+                        }
+                        _ => {}
+                    }
+                }
+
+                // the furthest trace decides, one whose samples all fall in the previous sweep
+                // (or a hidden one) mustn't pull the sweep line back to zero
+                if let Some(last) = points_vec_a.last() {
+                    geometry.t_last = geometry.t_last.max(last.x);
+                }
+                geometry.points_drawn += points_vec_a.len() + points_vec_b.len();
+                geometry.points_available += samples_in_view;
+                geometry.traces.push(TraceGeometry {
+                    channel: ch_idx,
+                    lane_center,
+                    lane_height: self.row_spacing,
+                    current: points_vec_a,
+                    previous: points_vec_b,
+                    envelope_current,
+                    envelope_previous,
+                    average_current,
+                    average_previous,
+                    off_scale_up,
+                    off_scale_down,
+                    baseline,
+                    scale,
+                    offset,
+                    signed_log,
+                    z_score,
+                });
+                plot_idx += 1;
+            }
+        }
+
+        // check if we moved to a new time window; a scrolling regular stream passes
+        // one where a sweep would have wrapped. Until the buffer spans some time there
+        // is no window to speak of, and the position would jump around
+        if self.buffer_spans_time() && latest_timestamp == newest {
+            let sweep_position = if scrolls && !self.irregular {
+                latest_timestamp.rem_euclid(window)
+            } else {
+                geometry.t_last
+            };
+            if sweep_wrapped(self.last_t, sweep_position) && self.baseline_on_wrap {
+                self.baseline_correct();
+            }
+            self.last_t = sweep_position;
+        } else {
+            self.last_t = 0.0;
+        }
+
+        // markers and gaps go where a data sample with the same timestamp would be drawn
+        let to_x = |timestamp: f64| place(timestamp).0;
+        geometry.markers = self
+            .marker_buffer
+            .iter()
+            .filter(|(timestamp, _)| in_view(*timestamp))
+            .map(|(timestamp, text)| (to_x(*timestamp), *timestamp, text.clone()))
+            .collect();
+        // identical markers close together share a label, placed at the first of them
+        let collapse_distance = MARKER_COLLAPSE_FRACTION * window;
+        let mut previous_x = f64::NEG_INFINITY;
+        for (x, _, text) in geometry.markers.iter() {
+            match geometry.marker_labels.last_mut() {
+                Some((_, last_text, count))
+                    if self.collapse_markers
+                        && last_text == text
+                        && (x - previous_x).abs() < collapse_distance =>
+                {
+                    *count += 1
+                }
+                _ => geometry.marker_labels.push((*x, text.clone(), 1)),
+            }
+            previous_x = *x;
+        }
+        geometry.annotations = self
+            .annotations
+            .iter()
+            .filter(|(timestamp, _)| in_view(*timestamp))
+            .map(|(timestamp, text)| (to_x(*timestamp), *timestamp, text.clone()))
+            .collect();
+        // stretches are cut to the shown window
+        let clamp_to_view = |start: f64, end: f64| {
+            (end >= view_start && start <= latest_timestamp)
+                .then(|| (start.max(view_start), end.min(latest_timestamp)))
+        };
+        for (start, end) in self
+            .gap_buffer
+            .iter()
+            .filter_map(|&(start, end)| clamp_to_view(start, end))
+        {
+            let (start_x, end_x) = (to_x(start), to_x(end));
+            if start_x <= end_x {
+                geometry.gaps.push((start_x, end_x));
+            } else {
+                // the gap spans the sweep boundary
+                geometry.gaps.push((start_x, window));
+                geometry.gaps.push((0.0, end_x));
+            }
+        }
+        if let Some((start, end)) = self
+            .stats_range
+            .and_then(|(start, end)| clamp_to_view(start, end))
+        {
+            let (start_x, end_x) = (to_x(start), to_x(end));
+            if start_x <= end_x {
+                geometry.selection.push((start_x, end_x));
+            } else {
+                geometry.selection.push((start_x, window));
+                geometry.selection.push((0.0, end_x));
+            }
+        }
+        for (ch, (start, end)) in self
+            .clip_buffer
+            .iter()
+            .filter_map(|&(ch, start, end)| Some((ch, clamp_to_view(start, end)?)))
+        {
+            let (start_x, end_x) = (to_x(start), to_x(end));
+            if start_x <= end_x {
+                geometry.clips.push((ch, start_x, end_x));
+            } else {
+                geometry.clips.push((ch, start_x, window));
+                geometry.clips.push((ch, 0.0, end_x));
+            }
+        }
+        // peaks sit on their trace, wherever it was laid out
+        if let Some(trace) = self
+            .peak_channel
+            .and_then(|ch| geometry.traces.iter().find(|t| t.channel == ch))
+        {
+            let clear_previous = !scrolls && self.sweep_boundary == SweepBoundary::Clear;
+            let peaks = self
+                .peak_buffer
+                .iter()
+                .filter(|(timestamp, _)| in_view(*timestamp))
+                .filter_map(|&(timestamp, value)| {
+                    let (x, current) = place(timestamp);
+                    (current || !clear_previous)
+                        .then(|| [x, trace.plot_y(value as f64, self.plot_layout)].into())
+                })
+                .collect();
+            geometry.peaks = peaks;
+        }
+        if self.keeps_history() {
+            geometry.overview = self.build_overview();
+        }
+        self.plot_geometry = geometry;
+        self.display_caches = caches;
+        self.last_geometry_build = Some(Instant::now());
+        self.geometry_build_time = started.elapsed();
+    }
+
+    /// The marker labels egui_plot can't draw itself: rotated text, or numbered
+    /// ticks with a legend in the top right corner.
+    fn draw_marker_labels(
+        &self,
+        ui: &egui::Ui,
+        transform: &egui_plot::PlotTransform,
+        labels: &[(f64, String, usize)],
+    ) {
+        let frame = *transform.frame();
+        let painter = ui.painter_at(frame);
+        let color = egui::Color32::from_rgb(255, 215, 0);
+        let font = egui::TextStyle::Small.resolve(ui.style());
+        match self.marker_labels {
+            MarkerLabels::Rotated => {
+                for (x, text, count) in labels {
+                    let x = transform.position_from_point_x(*x);
+                    let galley =
+                        painter.layout_no_wrap(marker_label(text, *count), font.clone(), color);
+                    // turned to read upwards, running down from the top along the line
+                    let pos = egui::pos2(x + 1.0, frame.top() + galley.size().x + 4.0);
+                    painter.add(
+                        egui::epaint::TextShape::new(pos, galley, color)
+                            .with_angle(-std::f32::consts::FRAC_PI_2),
+                    );
+                }
+            }
+            MarkerLabels::Legend => {
+                let mut texts: Vec<&str> = Vec::new();
+                for (x, text, _) in labels {
+                    let number = match texts.iter().position(|t| t == text) {
+                        Some(i) => i + 1,
+                        None => {
+                            texts.push(text);
+                            texts.len()
                         }
-                        Err(e) => {
-                            let _ = resp_tx
-                                .send(LslResponse::Error(format!("Failed to connect: {}", e)));
+                    };
+                    let x = transform.position_from_point_x(*x);
+                    painter.line_segment(
+                        [
+                            egui::pos2(x, frame.top()),
+                            egui::pos2(x, frame.top() + MARKER_TICK_LENGTH),
+                        ],
+                        Stroke::new(1.5, color),
+                    );
+                    painter.text(
+                        egui::pos2(x + 2.0, frame.top() + MARKER_TICK_LENGTH),
+                        egui::Align2::LEFT_TOP,
+                        number.to_string(),
+                        font.clone(),
+                        color,
+                    );
+                }
+                if texts.is_empty() {
+                    return;
+                }
+                let legend: Vec<String> = texts
+                    .iter()
+                    .enumerate()
+                    .map(|(i, text)| format!("{}  {}", i + 1, text))
+                    .collect();
+                let galley = painter.layout_no_wrap(legend.join("\n"), font, color);
+                let rect = egui::Align2::RIGHT_TOP
+                    .anchor_size(frame.right_top() + egui::vec2(-8.0, 8.0), galley.size())
+                    .expand(4.0);
+                painter.rect_filled(
+                    rect,
+                    2.0,
+                    ui.visuals().extreme_bg_color.gamma_multiply(0.85),
+                );
+                painter.galley(rect.shrink(4.0).min, galley, color);
+            }
+            MarkerLabels::Inline | MarkerLabels::Hover => {}
+        }
+    }
+
+    /// Reduce the whole buffer of every displayed channel to min/max columns, each
+    /// trace scaled to fill the strip.
+    fn build_overview(&self) -> Overview {
+        let (Some(&first), Some(&last)) =
+            (self.timestamp_buffer.front(), self.timestamp_buffer.back())
+        else {
+            return Overview::default();
+        };
+        let span = (last - first).max(f64::EPSILON);
+        let traces = self
+            .displayed_channels()
+            .into_iter()
+            .filter_map(|ch| {
+                let data = self.data_buffer.get(ch)?;
+                let mut columns: Vec<OverviewColumn> = vec![None; OVERVIEW_COLUMNS];
+                for (&timestamp, &value) in self.timestamp_buffer.iter().zip(data) {
+                    if !value.is_finite() {
+                        continue;
+                    }
+                    let column = ((timestamp - first) / span * (OVERVIEW_COLUMNS - 1) as f64)
+                        .round() as usize;
+                    let column = &mut columns[column.min(OVERVIEW_COLUMNS - 1)];
+                    *column = Some(column.map_or((value, value), |(low, high)| {
+                        (low.min(value), high.max(value))
+                    }));
+                }
+                let low = columns
+                    .iter()
+                    .flatten()
+                    .map(|c| c.0)
+                    .fold(f32::INFINITY, f32::min);
+                let high = columns
+                    .iter()
+                    .flatten()
+                    .map(|c| c.1)
+                    .fold(f32::NEG_INFINITY, f32::max);
+                let range = (high - low).max(f32::EPSILON);
+                let columns = columns
+                    .into_iter()
+                    .map(|c| c.map(|(l, h)| ((l - low) / range, (h - low) / range)))
+                    .collect();
+                Some((ch, columns))
+            })
+            .collect();
+        Overview {
+            first,
+            last,
+            traces,
+        }
+    }
+
+    /// The buffered history in miniature with the shown window boxed; while frozen,
+    /// clicking or dragging on it moves the plot there.
+    fn overview_ui(&mut self, ui: &mut egui::Ui) {
+        if !self.frozen {
+            self.view_end = None;
+        }
+        let (rect, response) = ui.allocate_exact_size(
+            egui::vec2(ui.available_width(), OVERVIEW_HEIGHT),
+            egui::Sense::click_and_drag(),
+        );
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 2.0, ui.visuals().extreme_bg_color);
+        let overview = &self.plot_geometry.overview;
+        let (first, last) = (overview.first, overview.last);
+        let span = (last - first).max(f64::EPSILON);
+        let x_at =
+            |timestamp: f64| rect.left() + ((timestamp - first) / span) as f32 * rect.width();
+        let column_width = rect.width() / OVERVIEW_COLUMNS as f32;
+        for (ch, columns) in overview.traces.iter() {
+            let color = self
+                .channel_colors
+                .get(*ch)
+                .copied()
+                .unwrap_or(egui::Color32::GRAY);
+            for (i, column) in columns.iter().enumerate() {
+                let Some((low, high)) = column else {
+                    continue;
+                };
+                let x = rect.left() + (i as f32 + 0.5) * column_width;
+                painter.line_segment(
+                    [
+                        egui::pos2(x, rect.bottom() - low * rect.height()),
+                        egui::pos2(x, rect.bottom() - high * rect.height() - 1.0),
+                    ],
+                    Stroke::new(1.0, color.gamma_multiply(0.7)),
+                );
+            }
+        }
+        // the stretch the plot shows
+        let view_end = self.plot_geometry.latest_timestamp;
+        let view = egui::Rect::from_x_y_ranges(
+            x_at(view_end - self.time_window_seconds).max(rect.left())..=x_at(view_end),
+            rect.y_range(),
+        );
+        painter.rect_filled(
+            view,
+            0.0,
+            egui::Color32::from_rgba_unmultiplied(135, 206, 235, 40),
+        );
+        painter.rect_stroke(
+            view,
+            0.0,
+            Stroke::new(1.0, egui::Color32::from_rgb(135, 206, 235)),
+            egui::StrokeKind::Inside,
+        );
+
+        let response = response.on_hover_text(if self.frozen {
+            "Click or drag to move the plot through the history"
+        } else {
+            "Freeze the display to move through the history"
+        });
+        if self.frozen
+            && (response.clicked() || response.dragged())
+            && let Some(pointer) = response.interact_pointer_pos()
+        {
+            // the clicked time goes to the middle of the plot
+            let window = self.time_window_seconds;
+            let timestamp = first + ((pointer.x - rect.left()) / rect.width()) as f64 * span;
+            self.view_end = Some((timestamp + window / 2.0).max(first + window).min(last));
+            self.last_geometry_build = None;
+            self.snap_back_to_live = true;
+        }
+    }
+
+    /// Frequency-domain view of the newest samples of every selected channel.
+    fn spectrum_ui(&mut self, ui: &mut egui::Ui) {
+        let sample_rate = self.stream_sample_rate();
+        if sample_rate <= 0.0 {
+            ui.label("The spectrum needs a stream with a regular sample rate.");
+            return;
+        }
+
+        let mut planner = rustfft::FftPlanner::new();
+        Plot::new("spectrum_plot")
+            .x_axis_label("Frequency (Hz)")
+            .y_axis_label(format!("Magnitude (dB re 1 {})", self.display_unit))
+            .default_x_bounds(0.0, sample_rate / 2.0)
+            .legend(egui_plot::Legend::default())
+            .show(ui, |plot_ui| {
+                for (ch_idx, channel_data) in self.data_buffer.iter().enumerate() {
+                    if !self.channel_shown(ch_idx) {
+                        continue;
+                    }
+                    let newest: Vec<f32> = channel_data
+                        .iter()
+                        .skip(channel_data.len().saturating_sub(spectrum::FFT_SIZE))
+                        .copied()
+                        .collect();
+                    let points = spectrum::magnitude_spectrum(
+                        &mut planner,
+                        &newest,
+                        sample_rate,
+                        spectrum::FFT_SIZE,
+                    );
+                    plot_ui.line(
+                        Line::new(self.channel_names[ch_idx].clone(), PlotPoints::new(points))
+                            .stroke(Stroke::new(self.line_width, self.channel_colors[ch_idx])),
+                    );
+                }
+            });
+    }
+
+    /// The newest baseline-corrected value of one channel in large digits, like
+    /// a multimeter, with the range seen since the last reset.
+    fn digits_ui(&mut self, ui: &mut egui::Ui) {
+        let displayed = self.displayed_channels();
+        let Some(&first) = displayed.first() else {
+            ui.label("Select a channel to show its value.");
+            return;
+        };
+        if !displayed.contains(&self.digits_channel) {
+            self.digits_channel = first;
+            self.digits_range = None;
+        }
+        ui.horizontal(|ui| {
+            ui.label("Channel");
+            let previous = self.digits_channel;
+            egui::ComboBox::from_id_source("digits_channel")
+                .selected_text(&self.channel_names[self.digits_channel])
+                .show_ui(ui, |ui| {
+                    for &ch in &displayed {
+                        ui.selectable_value(&mut self.digits_channel, ch, &self.channel_names[ch]);
+                    }
+                });
+            if self.digits_channel != previous {
+                self.digits_range = None;
+            }
+            if ui.button("Reset min/max").clicked() {
+                self.digits_range = None;
+            }
+            ui.checkbox(&mut self.digits_sparkline, "Sparkline")
+                .on_hover_text("The channel over the time window, beneath the value");
+        });
+
+        let channel = self.digits_channel;
+        let baseline = self.channel_baselines.get(channel).copied().unwrap_or(0.0);
+        let value = self.data_buffer[channel]
+            .back()
+            .map(|&v| v as f64 - baseline);
+        let format = |value: Option<f64>| value.map_or("--".to_string(), |v| format!("{:.2}", v));
+        let sparkline_height = if self.digits_sparkline {
+            ui.available_height() / 4.0
+        } else {
+            0.0
+        };
+        // sized so the value fills the width of the panel without wrapping
+        let digits = (format(value).len() + 1 + self.display_unit.len()).max(4) as f32;
+        let size = (ui.available_width() / (0.6 * digits))
+            .min((ui.available_height() - sparkline_height) * 0.5)
+            .max(24.0);
+        ui.vertical_centered(|ui| {
+            ui.label(
+                egui::RichText::new(&self.channel_names[channel])
+                    .size(size * 0.2)
+                    .color(self.channel_colors[channel]),
+            );
+            ui.label(
+                egui::RichText::new(format!("{} {}", format(value), self.display_unit))
+                    .monospace()
+                    .size(size),
+            );
+            ui.label(
+                egui::RichText::new(format!(
+                    "min {}  max {}",
+                    format(self.digits_range.map(|(min, _)| min)),
+                    format(self.digits_range.map(|(_, max)| max))
+                ))
+                .monospace()
+                .size(size * 0.2)
+                .weak(),
+            );
+        });
+
+        if self.digits_sparkline {
+            let latest = self.timestamp_buffer.back().copied().unwrap_or(0.0);
+            let points: PlotPoints = self
+                .timestamp_buffer
+                .iter()
+                .zip(self.data_buffer[channel].iter())
+                .map(|(&t, &v)| [t - latest, v as f64 - baseline])
+                .collect();
+            Plot::new("digits_sparkline")
+                .height(ui.available_height())
+                .show_axes(false)
+                .show_grid(false)
+                .show_x(false)
+                .show_y(false)
+                .allow_drag(false)
+                .allow_zoom(false)
+                .allow_scroll(false)
+                .allow_boxed_zoom(false)
+                .show(ui, |plot_ui| {
+                    plot_ui.line(
+                        Line::new("", points)
+                            .stroke(Stroke::new(self.line_width, self.channel_colors[channel])),
+                    );
+                });
+        }
+    }
+
+    /// Short-time spectra of one channel as a heatmap, newest on the right.
+    fn spectrogram_ui(&mut self, ui: &mut egui::Ui) {
+        let sample_rate = self.stream_sample_rate();
+        if sample_rate <= 0.0 {
+            ui.label("The spectrogram needs a stream with a regular sample rate.");
+            return;
+        }
+
+        let previous = self.spectrogram_settings;
+        let settings = &mut self.spectrogram_settings;
+        ui.horizontal(|ui| {
+            ui.label("Channel");
+            egui::ComboBox::from_id_source("spectrogram_channel")
+                .selected_text(
+                    self.channel_names
+                        .get(settings.channel)
+                        .map_or("", |name| name.as_str()),
+                )
+                .show_ui(ui, |ui| {
+                    for (i, name) in self.channel_names.iter().enumerate() {
+                        ui.selectable_value(&mut settings.channel, i, name);
+                    }
+                });
+            ui.label("FFT size");
+            egui::ComboBox::from_id_source("spectrogram_fft_size")
+                .selected_text(settings.fft_size.to_string())
+                .show_ui(ui, |ui| {
+                    for size in spectrogram::FFT_SIZES {
+                        ui.selectable_value(&mut settings.fft_size, size, size.to_string());
+                    }
+                });
+            ui.label("Hop");
+            ui.add(
+                egui::DragValue::new(&mut settings.hop)
+                    .range(1..=settings.fft_size)
+                    .suffix(" samples"),
+            )
+            .on_hover_text("Samples from one spectrum to the next");
+            ui.label("Frequencies");
+            let nyquist = sample_rate / 2.0;
+            ui.add(
+                egui::DragValue::new(&mut settings.min_frequency)
+                    .range(0.0..=settings.max_frequency)
+                    .speed(0.5)
+                    .suffix(" Hz"),
+            );
+            ui.label("to");
+            ui.add(
+                egui::DragValue::new(&mut settings.max_frequency)
+                    .range(settings.min_frequency..=nyquist)
+                    .speed(0.5)
+                    .suffix(" Hz"),
+            );
+        });
+        self.spectrogram_settings.hop = self
+            .spectrogram_settings
+            .hop
+            .min(self.spectrogram_settings.fft_size);
+        let changed = self.spectrogram_settings != previous;
+        if changed || self.spectrogram.is_none() {
+            self.reset_spectrogram();
+        }
+        let Some(spectrogram) = self.spectrogram.as_mut() else {
+            return;
+        };
+        spectrogram.set_window(self.time_window_seconds);
+        ui.label(
+            egui::RichText::new(format!(
+                "{:.2} Hz per bin, one spectrum every {:.0} ms. Brightness is relative to the strongest bin.",
+                spectrogram.bin_width(),
+                spectrogram.column_seconds() * 1000.0
+            ))
+            .small()
+            .weak(),
+        );
+
+        let width = spectrogram.width_seconds();
+        let (low, high) = spectrogram.frequency_edges();
+        let texture = spectrogram.texture(ui.ctx());
+        let mut plot = Plot::new("spectrogram_plot")
+            .x_axis_label("Time (s)")
+            .y_axis_label("Frequency (Hz)")
+            .default_x_bounds(-width, 0.0)
+            .default_y_bounds(low, high);
+        if changed {
+            plot = plot.reset();
+        }
+        plot.show(ui, |plot_ui| {
+            if let Some(texture) = texture {
+                plot_ui.image(PlotImage::new(
+                    "Spectrogram",
+                    texture,
+                    PlotPoint::new(-width / 2.0, (low + high) / 2.0),
+                    egui::vec2(width as f32, (high - low) as f32),
+                ));
+            }
+        });
+    }
+
+    /// Scrolling log of a string stream, newest at the bottom.
+    fn text_log_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label(format!("{} entries", self.text_log.len()));
+            if ui.button("Clear").clicked() {
+                self.text_log.clear();
+            }
+        });
+        let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+        egui::ScrollArea::vertical()
+            .stick_to_bottom(true)
+            .auto_shrink(false)
+            .show_rows(ui, row_height, self.text_log.len(), |ui, rows| {
+                for (timestamp, text) in self.text_log.range(rows) {
+                    ui.label(
+                        egui::RichText::new(format!(
+                            "{}  {}",
+                            self.format_timestamp(*timestamp),
+                            text
+                        ))
+                        .monospace(),
+                    );
+                }
+            });
+    }
+
+    /// Average of the epochs around every marker event, per selected channel.
+    fn erp_ui(&mut self, ui: &mut egui::Ui) {
+        if self.marker_stream.is_none() {
+            ui.label(
+                "Connect a marker stream (under Markers) to average epochs around its events.",
+            );
+            return;
+        }
+        ui.horizontal(|ui| {
+            let mut pre_ms = self.erp_pre_seconds * 1000.0;
+            let mut post_ms = self.erp_post_seconds * 1000.0;
+            ui.label("Epoch from");
+            let pre_changed = ui
+                .add(
+                    egui::DragValue::new(&mut pre_ms)
+                        .range(0.0..=2000.0)
+                        .prefix("-")
+                        .suffix(" ms"),
+                )
+                .changed();
+            ui.label("to");
+            let post_changed = ui
+                .add(
+                    egui::DragValue::new(&mut post_ms)
+                        .range(10.0..=5000.0)
+                        .prefix("+")
+                        .suffix(" ms"),
+                )
+                .changed();
+            if pre_changed || post_changed {
+                self.erp_pre_seconds = pre_ms / 1000.0;
+                self.erp_post_seconds = post_ms / 1000.0;
+                self.reset_erp();
+            }
+            if ui.button("Reset").clicked() {
+                self.reset_erp();
+            }
+        });
+        let Some(erp) = &self.erp else {
+            ui.label("ERP averaging needs a stream with a regular sample rate.");
+            return;
+        };
+        ui.horizontal(|ui| {
+            ui.label(format!("Trials: {}", erp.trials));
+            if erp.skipped > 0 {
+                ui.colored_label(
+                    egui::Color32::from_rgb(255, 165, 0),
+                    format!("{} skipped", erp.skipped),
+                )
+                .on_hover_text("The data before these events was no longer buffered; a longer time window keeps more of it");
+            }
+        });
+        ui.label(
+            egui::RichText::new("Each epoch is baseline-corrected to its pre-event mean.")
+                .small()
+                .weak(),
+        );
+
+        Plot::new("erp_plot")
+            .x_axis_label("Time from event (ms)")
+            .y_axis_label(&self.display_unit)
+            .legend(egui_plot::Legend::default())
+            .show(ui, |plot_ui| {
+                plot_ui
+                    .vline(VLine::new("Event", 0.0).stroke(Stroke::new(1.0, egui::Color32::GRAY)));
+                for ch_idx in 0..self.channel_count {
+                    if !self.channel_shown(ch_idx) {
+                        continue;
+                    }
+                    plot_ui.line(
+                        Line::new(
+                            self.channel_names[ch_idx].clone(),
+                            PlotPoints::new(erp.average(ch_idx)),
+                        )
+                        .stroke(Stroke::new(self.line_width, self.channel_colors[ch_idx])),
+                    );
+                }
+            });
+    }
+
+    /// Largest absolute baseline-corrected value over the selected channels,
+    /// or `None` if there is nothing (non-flat) to measure.
+    fn max_deflection(&self) -> Option<f64> {
+        (0..self.data_buffer.len())
+            .filter(|&i| self.channel_shown(i))
+            .filter_map(|i| self.channel_max_deflection(i))
+            .reduce(f64::max)
+    }
+
+    /// Largest absolute baseline-corrected value of one channel.
+    fn channel_max_deflection(&self, channel: usize) -> Option<f64> {
+        let baseline = self.channel_baselines[channel];
+        let max = self.data_buffer[channel]
+            .iter()
+            .map(|&v| (v as f64 - baseline).abs())
+            .fold(0.0, f64::max);
+        (max > 0.0).then_some(max)
+    }
+
+    /// Robust amplitude of one channel: the given percentile of its absolute
+    /// baseline-corrected values, or `None` if the channel is flat.
+    fn channel_robust_amplitude(&self, channel: usize) -> Option<f64> {
+        let baseline = self.channel_baselines[channel];
+        let mut magnitudes: Vec<f64> = self.data_buffer[channel]
+            .iter()
+            .map(|&v| (v as f64 - baseline).abs())
+            .filter(|m| m.is_finite())
+            .collect();
+        if magnitudes.is_empty() {
+            return None;
+        }
+        let index = ((magnitudes.len() - 1) as f64 * AUTO_SCALE_PERCENTILE).round() as usize;
+        let (_, &mut amplitude, _) = magnitudes.select_nth_unstable_by(index, f64::total_cmp);
+        (amplitude > 0.0).then_some(amplitude)
+    }
+
+    /// Fit the gain to the data so the robust amplitude of the selected channels
+    /// fills a fixed fraction of a lane; per channel when the gains are unlinked.
+    fn auto_scale(&mut self) {
+        self.baseline_correct();
+        let fit = |amplitude: f64| {
+            (AUTO_SCALE_LANE_FRACTION * SCALE_NORMALIZATION / amplitude)
+                .clamp(*SCALE_RANGE.start(), *SCALE_RANGE.end())
+        };
+        let selected: Vec<usize> = (0..self.data_buffer.len())
+            .filter(|&i| self.channel_shown(i) && !self.channel_bad(i))
+            .collect();
+        if self.link_scales {
+            // the largest channel decides, so no lane overflows
+            if let Some(amplitude) = selected
+                .iter()
+                .filter_map(|&i| self.channel_robust_amplitude(i))
+                .reduce(f64::max)
+            {
+                self.data_scale = fit(amplitude);
+            }
+        } else {
+            for ch in selected {
+                if let Some(amplitude) = self.channel_robust_amplitude(ch) {
+                    self.channel_scales[ch] = fit(amplitude);
+                }
+            }
+        }
+        self.last_auto_scale = Some(Instant::now());
+    }
+
+    fn baseline_correct(&mut self) {
+        // Calculate baseline for each channel
+        for (i, channel_data) in self.data_buffer.iter_mut().enumerate() {
+            if !channel_data.is_empty() {
+                let mut sum = 0.0;
+                let mut count = 0;
+                let oldest_timestamp_to_inlcude = self.timestamp_buffer.back().unwrap_or(&0.0)
+                    - DEFAULT_BASELINE_TIME_WINDOW as f64;
+                for (_, &value) in self
+                    .timestamp_buffer
+                    .iter()
+                    .zip(channel_data.iter())
+                    .rev()
+                    .take_while(|&(t, _)| *t >= oldest_timestamp_to_inlcude)
+                {
+                    if value.is_finite() {
+                        sum += value as f64;
+                        count += 1;
+                    }
+                }
+
+                // with nothing finite to go on, the old baseline is the best guess
+                if count > 0 {
+                    self.channel_baselines[i] = sum / count as f64;
+                }
+            }
+        }
+    }
+}
+
+/// One metadata element: leaves as `name: value`, the rest as collapsible sections.
+/// `id` tells apart sibling elements of the same name, like the channels.
+fn xml_node_ui(ui: &mut egui::Ui, node: &xml_tree::XmlNode, id: egui::Id) {
+    if node.children.is_empty() {
+        ui.horizontal(|ui| {
+            ui.strong(&node.name);
+            ui.label(&node.text);
+        });
+        return;
+    }
+    // the top levels start open, single channels and the like collapsed
+    egui::CollapsingHeader::new(&node.name)
+        .id_salt(id)
+        .default_open(node.name == "info" || node.name == "desc")
+        .show(ui, |ui| {
+            if !node.text.is_empty() {
+                ui.label(&node.text);
+            }
+            for (i, child) in node.children.iter().enumerate() {
+                xml_node_ui(ui, child, id.with(i));
+            }
+        });
+}
+
+/// Point of a trace segment (sorted by x) closest to `x`.
+fn nearest_point(points: &[PlotPoint], x: f64) -> Option<PlotPoint> {
+    let i = points.partition_point(|p| p.x < x);
+    [i.checked_sub(1), Some(i)]
+        .into_iter()
+        .flatten()
+        .filter_map(|i| points.get(i).copied())
+        .min_by(|a, b| (a.x - x).abs().total_cmp(&(b.x - x).abs()))
+}
+
+impl PlotGeometry {
+    /// The timestamp a plot x-coordinate stands for. Left of the sweep line that
+    /// is the current sweep, right of it the previous one.
+    fn timestamp_at(&self, x: f64, time_axis_scale: TimeAxisScale, window: f64) -> f64 {
+        match time_axis_scale {
+            TimeAxisScale::RecentEmphasis => self.latest_timestamp - recent_emphasis_age(x, window),
+            TimeAxisScale::Linear if x > self.t_last => self.sweep_start + x - window,
+            TimeAxisScale::Linear => self.sweep_start + x,
+        }
+    }
+
+    /// Channel, timestamp and real value of the sample under the pointer. In the
+    /// stacked layout the lane under the pointer decides the channel, in the
+    /// overlay the trace closest to the pointer does.
+    fn sample_at(
+        &self,
+        pointer: PlotPoint,
+        layout: PlotLayout,
+        time_axis_scale: TimeAxisScale,
+        window: f64,
+    ) -> Option<(usize, f64, f64)> {
+        let candidates = self.traces.iter().filter(|trace| {
+            layout == PlotLayout::Overlay
+                || (trace.lane_center - pointer.y).abs() <= trace.lane_height / 2.0
+        });
+        let (trace, point, in_previous) = candidates
+            .flat_map(|trace| {
+                [(&trace.current, false), (&trace.previous, true)]
+                    .into_iter()
+                    .filter_map(move |(points, in_previous)| {
+                        nearest_point(points, pointer.x).map(|p| (trace, p, in_previous))
+                    })
+            })
+            .min_by(|(_, a, _), (_, b, _)| {
+                let distance = |p: &PlotPoint| {
+                    let dy = if layout == PlotLayout::Overlay {
+                        (p.y - pointer.y).abs()
+                    } else {
+                        0.0
+                    };
+                    ((p.x - pointer.x).abs(), dy)
+                };
+                distance(a)
+                    .partial_cmp(&distance(b))
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            })?;
+
+        let timestamp = match time_axis_scale {
+            TimeAxisScale::RecentEmphasis => {
+                self.latest_timestamp - recent_emphasis_age(point.x, window)
+            }
+            TimeAxisScale::Linear if in_previous => self.sweep_start + point.x - window,
+            TimeAxisScale::Linear => self.sweep_start + point.x,
+        };
+        Some((trace.channel, timestamp, trace.value_at(point.y, layout)))
+    }
+}
+
+impl TraceGeometry {
+    /// The value a plot y-coordinate of this trace stands for.
+    fn value_at(&self, y: f64, layout: PlotLayout) -> f64 {
+        let unnormalized = |z: f64| self.z_score.map_or(z, |(mean, std)| z * std + mean);
+        match layout {
+            PlotLayout::Overlay if self.signed_log => unnormalized(signed_exp_value(y)),
+            PlotLayout::Overlay => unnormalized(y),
+            PlotLayout::Stacked => {
+                (y - self.lane_center - self.offset) * SCALE_NORMALIZATION / self.scale
+                    + self.baseline
+            }
+        }
+    }
+
+    /// The plot y-coordinate of `value`, the inverse of `value_at`.
+    fn plot_y(&self, value: f64, layout: PlotLayout) -> f64 {
+        let normalized = self
+            .z_score
+            .map_or(value, |(mean, std)| (value - mean) / std);
+        match layout {
+            PlotLayout::Overlay if self.signed_log => signed_log_value(normalized),
+            PlotLayout::Overlay => normalized,
+            PlotLayout::Stacked => {
+                (value - self.baseline) * self.scale / SCALE_NORMALIZATION
+                    + self.lane_center
+                    + self.offset
+            }
+        }
+    }
+}
+
+/// The default time window, lengthened to the first preset that shows enough of
+/// a slow stream or shortened to quarter seconds so a fast one isn't a blur.
+fn auto_time_window(rate: f64) -> f64 {
+    let shortest = AUTO_WINDOW_MIN_SAMPLES / rate;
+    let longest = AUTO_WINDOW_MAX_SAMPLES / rate;
+    let window = if shortest > DEFAULT_TIME_WINDOW_SECONDS {
+        TIME_WINDOW_PRESETS
+            .into_iter()
+            .find(|&preset| preset >= shortest)
+            .unwrap_or(TIME_WINDOW_PRESETS[TIME_WINDOW_PRESETS.len() - 1])
+    } else if longest < DEFAULT_TIME_WINDOW_SECONDS {
+        (longest * 4.0).floor() / 4.0
+    } else {
+        DEFAULT_TIME_WINDOW_SECONDS
+    };
+    window.clamp(*TIME_WINDOW_RANGE.start(), *TIME_WINDOW_RANGE.end())
+}
+
+/// Display downsampling that brings a very fast stream down to about
+/// `AUTO_DOWNSAMPLE_RATE`; none for anything slower.
+fn auto_downsample_factor(rate: f64) -> usize {
+    ((rate / AUTO_DOWNSAMPLE_RATE) as usize).clamp(1, MAX_DOWN_SAMPLE_FACTOR)
+}
+
+/// A sample rate in Hz or, from 1000 Hz up, in kHz.
+fn format_rate(hz: f64) -> String {
+    if hz >= 1000.0 {
+        format!("{} kHz", hz.round() / 1000.0)
+    } else {
+        format!("{} Hz", (hz * 100.0).round() / 100.0)
+    }
+}
+
+/// Scale at which a deflection of `±full_scale` spans exactly one channel lane.
+fn scale_for_full_scale(full_scale: f64) -> f64 {
+    0.5 * SCALE_NORMALIZATION / full_scale
+}
+
+/// Catmull-Rom curve through `points`, with enough points per segment for it to
+/// look smooth at `pixels_per_x`. Segments touching a non-finite value stay straight.
+fn smooth_points(points: &[PlotPoint], pixels_per_x: f64) -> Vec<PlotPoint> {
+    if points.len() < 3 {
+        return points.to_vec();
+    }
+    let mut smoothed = Vec::with_capacity(points.len());
+    for i in 0..points.len() - 1 {
+        let p0 = points[i.saturating_sub(1)];
+        let (p1, p2) = (points[i], points[i + 1]);
+        let p3 = points[(i + 2).min(points.len() - 1)];
+        smoothed.push(p1);
+        if ![p0, p1, p2, p3].iter().all(|p| p.y.is_finite()) {
+            continue;
+        }
+        let steps = ((p2.x - p1.x).abs() * pixels_per_x / SMOOTHING_STEP_PIXELS).ceil() as usize;
+        let steps = steps.clamp(1, MAX_SMOOTHING_STEPS);
+        let curve = |a: f64, b: f64, c: f64, d: f64, t: f64| {
+            0.5 * (2.0 * b
+                + (c - a) * t
+                + (2.0 * a - 5.0 * b + 4.0 * c - d) * t * t
+                + (3.0 * b - a - 3.0 * c + d) * t * t * t)
+        };
+        for step in 1..steps {
+            let t = step as f64 / steps as f64;
+            smoothed.push(PlotPoint::new(
+                curve(p0.x, p1.x, p2.x, p3.x, t),
+                curve(p0.y, p1.y, p2.y, p3.y, t),
+            ));
+        }
+    }
+    smoothed.extend(points.last());
+    smoothed
+}
+
+/// `sign(x) * log10(1 + |x|)`: logarithmic for large magnitudes, defined at and
+/// around zero, and keeping the sign.
+fn signed_log_value(x: f64) -> f64 {
+    x.signum() * x.abs().ln_1p() / std::f64::consts::LN_10
+}
+
+/// The inverse of `signed_log_value`.
+fn signed_exp_value(y: f64) -> f64 {
+    y.signum() * (y.abs() * std::f64::consts::LN_10).exp_m1()
+}
+
+/// The largest 1-2-5 value no bigger than `max`, so ticks land on round numbers.
+fn round_tick_value(max: f64) -> f64 {
+    if !(max > 0.0 && max.is_finite()) {
+        return 0.0;
+    }
+    let magnitude = 10f64.powf(max.log10().floor());
+    [5.0, 2.0, 1.0]
+        .into_iter()
+        .map(|m| m * magnitude)
+        .find(|&v| v <= max)
+        .unwrap_or(magnitude)
+}
+
+/// Average a run of samples into one, stamped with the time of the last sample.
+/// Non-finite values are left out; a channel without any finite value stays NaN.
+fn coalesce_samples(samples: &[DataSample]) -> DataSample {
+    let last = &samples[samples.len() - 1];
+    let mut sums = vec![(0.0f32, 0usize); last.values.len()];
+    for sample in samples {
+        for (acc, &value) in sums.iter_mut().zip(sample.values.iter()) {
+            if value.is_finite() {
+                acc.0 += value;
+                acc.1 += 1;
+            }
+        }
+    }
+    let values = sums
+        .into_iter()
+        .map(|(sum, count)| {
+            if count > 0 {
+                sum / count as f32
+            } else {
+                f32::NAN
+            }
+        })
+        .collect();
+    DataSample {
+        timestamp: last.timestamp,
+        values,
+    }
+}
+
+/// The unit all channels that declare one agree on, or an empty string if there
+/// is none or they differ.
+fn common_unit(units: &[String]) -> String {
+    let mut declared = units.iter().filter(|unit| !unit.is_empty());
+    match declared.next() {
+        Some(first) if declared.all(|unit| unit == first) => first.clone(),
+        _ => String::new(),
+    }
+}
+
+/// Pick a conversion factor and display unit for a declared unit, so that
+/// voltages are shown in µV by default. Unknown units are left untouched.
+fn detect_unit_conversion(declared: &str) -> (f64, String) {
+    match declared.trim().to_lowercase().as_str() {
+        "v" | "volt" | "volts" => (1e6, "µV".to_string()),
+        "mv" | "millivolt" | "millivolts" => (1e3, "µV".to_string()),
+        "nv" | "nanovolt" | "nanovolts" => (1e-3, "µV".to_string()),
+        "uv" | "µv" | "μv" | "microvolt" | "microvolts" => (1.0, "µV".to_string()),
+        _ => (1.0, declared.trim().to_string()),
+    }
+}
+
+// The plot area that `update` draws and captures, kept next to it
+impl LslViewer {
+    /// Pick up a requested screenshot and keep the plot area of it.
+    fn handle_screenshot(&mut self, ctx: &egui::Context) {
+        let screenshot = if self.snapshot_requested || self.image_save_requested {
+            ctx.input(|i| {
+                i.raw.events.iter().find_map(|e| match e {
+                    egui::Event::Screenshot { image, .. } => Some(image.clone()),
+                    _ => None,
+                })
+            })
+        } else {
+            None
+        };
+        if self.image_save_requested
+            && let (Some(image), Some(capture_rect)) = (&screenshot, self.plot_capture_rect)
+        {
+            self.image_save_requested = false;
+            self.save_plot_image(ctx, image, capture_rect);
+        }
+        if self.snapshot_requested
+            && let (Some(image), Some(plot_rect)) = (screenshot, self.plot_rect)
+        {
+            self.snapshot_requested = false;
+            let settings = format!(
+                "Scale {:.1}, window {} s, {} channel(s) shown",
+                self.data_scale,
+                self.time_window_seconds,
+                self.displayed_channels().len()
+            );
+            let stream_time = self.timestamp_buffer.back().copied().unwrap_or(0.0);
+            self.snapshots.push(Snapshot::from_screenshot(
+                ctx,
+                &image,
+                plot_rect,
+                stream_time,
+                settings,
+            ));
+            self.show_gallery = true;
+        }
+    }
+
+    /// The plot or other data view with its stats row; in the central panel or the pop-out window.
+    fn central_ui(&mut self, ui: &mut egui::Ui) {
+        let ctx = ui.ctx().clone();
+        ui.vertical(|ui| {
+            if let Some(name) = self.disconnected_stream.clone().filter(|_| !self.is_connected) {
+                ui.horizontal(|ui| {
+                    ui.colored_label(
+                        egui::Color32::from_rgb(255, 165, 0),
+                        format!("Disconnected from {}, showing its last data", name),
+                    );
+                    if ui
+                        .add_enabled(self.connect_stage.is_none(), egui::Button::new("Reconnect"))
+                        .clicked()
+                    {
+                        self.reconnect_disconnected_stream();
+                    }
+                    if ui.button("Clear").clicked() {
+                        self.disconnected_stream = None;
+                    }
+                });
+            }
+            if (self.is_connected || self.disconnected_stream.is_some()) && self.channel_count > 0 {
+                // Data visualization
+                if self.text_stream {
+                    self.text_log_ui(ui);
+                } else if self.plot_mode == PlotMode::Spectrum {
+                    self.spectrum_ui(ui);
+                } else if self.plot_mode == PlotMode::Spectrogram {
+                    self.spectrogram_ui(ui);
+                } else if self.plot_mode == PlotMode::Erp {
+                    self.erp_ui(ui);
+                } else if self.plot_mode == PlotMode::Digits {
+                    self.digits_ui(ui);
+                } else if !self.data_buffer.is_empty() && self.data_buffer[0].len() > 0 {
+                    let displayed_channels = self.displayed_channels();
+                    let selected_channel_count = displayed_channels.len();
+                    let selected_channel_labels: Vec<String> = displayed_channels
+                        .iter()
+                        .map(|&i| self.channel_names[i].clone())
+                        .collect();
+
+                    let row_spacing = self.row_spacing;
+                    let y_formatter =
+                        |grid_mark: GridMark, _range: &std::ops::RangeInclusive<f64>| {
+                            // lane gridlines are left unlabelled
+                            if grid_mark.step_size < row_spacing {
+                                return String::new();
+                            }
+                            let index = (-grid_mark.value / row_spacing).round() as usize;
+
+                            if index >= selected_channel_labels.len() {
+                                return "??".to_string();
+                            }
+                            selected_channel_labels[index].to_string()
+                        };
+
+                    let lane_grid = self.lane_grid;
+                    let y_grid_spacer = move |_grid_input: GridInput| {
+                        lane_grid_marks(selected_channel_count, row_spacing, lane_grid)
+                    };
+
+                    let window = self.time_window_seconds;
+                    let stacked = self.plot_layout == PlotLayout::Stacked;
+                    // Shift-drag selects a time range instead of panning or moving a trace
+                    let selecting_range = ui.input(|i| i.modifiers.shift);
+                    // separate ids, so the y-range of one layout doesn't carry over to the other
+                    let mut plot = Plot::new(if stacked { "lsl_plot" } else { "lsl_plot_overlay" })
+                        .default_x_bounds(0.0, self.time_window_seconds)
+                        .allow_zoom(self.frozen)
+                        .allow_drag(self.frozen && !selecting_range)
+                        .allow_scroll(self.frozen)
+                        .show_x(false)
+                        .show_y(false)
+                        .x_axis_label(self.time_axis_scale.x_axis_label());
+                    if stacked {
+                        plot = plot
+                            .default_y_bounds(
+                                -row_spacing * (selected_channel_count as f64 - 0.5),
+                                row_spacing / 2.0,
+                            )
+                            .y_axis_formatter(y_formatter)
+                            .y_grid_spacer(y_grid_spacer);
+                    } else {
+                        plot = plot.legend(egui_plot::Legend::default());
+                        if self.log_y {
+                            // gridlines at 0 and every power of ten, labelled in real values
+                            plot = plot
+                                .y_grid_spacer(|input: GridInput| {
+                                    signed_log_grid_marks(input.bounds)
+                                })
+                                .y_axis_formatter(|mark, _range| {
+                                    let value = signed_exp_value(mark.value);
+                                    if value.abs() >= 1.0 {
+                                        format!("{:.0}", value)
+                                    } else {
+                                        format!("{:.2}", value)
+                                    }
+                                });
                         }
                     }
-                } else {
-                    let _ = resp_tx.send(LslResponse::Error("Invalid stream index".to_string()));
-                }
-            }
-            Ok(LslCommand::Disconnect) => {
-                inlet = None;
-                let _ = resp_tx.send(LslResponse::Disconnected);
-            }
-            Err(mpsc::TryRecvError::Disconnected) => break,
-            Err(mpsc::TryRecvError::Empty) => {}
-        }
+                    if self.show_y_axis_title {
+                        let title = if self.z_score && !stacked {
+                            "Z-score".to_string()
+                        } else {
+                            self.y_axis_title.clone()
+                        };
+                        plot = plot.y_axis_label(title);
+                    }
+                    if self.snap_back_to_live {
+                        // drop whatever panning/zooming happened while frozen
+                        plot = plot.reset();
+                        self.snap_back_to_live = false;
+                    }
+                    // a chosen spacing replaces the gridlines that fit the window
+                    let x_grid_step = (!self.auto_x_grid
+                        && self.time_axis_scale == TimeAxisScale::Linear)
+                        .then_some(self.x_grid_spacing);
+                    // step and origin of the time gridlines, None for egui_plot's own
+                    let mut x_grid = None;
+                    if self.time_axis_scale == TimeAxisScale::RecentEmphasis {
+                        plot = plot.x_axis_formatter(move |mark, _range| {
+                            format!("-{:.2}", recent_emphasis_age(mark.value, window))
+                        });
+                    } else if self.scroll_mode == PlotScrollMode::Scroll && !self.irregular {
+                        // seconds before the newest sample, or the time of day it was taken
+                        let sweep_start = self.plot_geometry.sweep_start + self.timestamp_shift();
+                        let wall_clock_offset =
+                            self.wall_clock_offset.filter(|_| self.show_wall_clock);
+                        x_grid = x_grid_step.map(|step| {
+                            (step, wall_clock_offset.map_or(-window, |offset| sweep_start + offset))
+                        });
+                        plot = plot.x_axis_formatter(move |mark, _range| {
+                            if x_grid_step.is_some_and(|step| mark.step_size < step) {
+                                return String::new();
+                            }
+                            match wall_clock_offset {
+                                Some(offset) => {
+                                    format_time_of_day(sweep_start + mark.value + offset)
+                                }
+                                None => format!("-{:.1}", window - mark.value),
+                            }
+                        });
+                    } else if !self.irregular {
+                        // milliseconds for short sweeps, minutes for long ones
+                        let unit = TimeUnit::for_window(window);
+                        let step = x_grid_step.unwrap_or_else(|| unit.tick_step(window));
+                        x_grid = Some((step, 0.0));
+                        plot = plot
+                            .x_axis_label(unit.axis_label())
+                            .x_axis_formatter(move |mark, _range| {
+                                if mark.step_size < step {
+                                    String::new()
+                                } else {
+                                    unit.format(mark.value)
+                                }
+                            });
+                    } else {
+                        // the scrolling axis is labelled with the timestamps themselves
+                        let sweep_start = self.plot_geometry.sweep_start + self.timestamp_shift();
+                        let wall_clock_offset =
+                            self.wall_clock_offset.filter(|_| self.show_wall_clock);
+                        x_grid = x_grid_step
+                            .map(|step| (step, sweep_start + wall_clock_offset.unwrap_or(0.0)));
+                        plot = plot.x_axis_formatter(move |mark, _range| {
+                            if x_grid_step.is_some_and(|step| mark.step_size < step) {
+                                return String::new();
+                            }
+                            let timestamp = sweep_start + mark.value;
+                            match wall_clock_offset {
+                                Some(offset) => format_time_of_day(timestamp + offset),
+                                None => format!("{:.1}", timestamp),
+                            }
+                        });
+                    }
+                    if let Some((step, origin)) = x_grid {
+                        plot = plot.x_grid_spacer(move |input: GridInput| {
+                            time_grid_marks(input.bounds, step, origin)
+                        });
+                    }
+                    if !self.grid_in_theme_color {
+                        plot = plot.show_grid(false);
+                    }
 
-        // Pull data if connected
-        if let Some(ref inlet) = inlet {
-            if let Ok((chunk, timestamps)) = inlet.pull_chunk() {
-                if !chunk.is_empty() {
-                    for (i, &timestamp) in timestamps.iter().enumerate() {
-                        let data = DataSample {
-                            timestamp,
-                            values: chunk[i].to_vec(),
+                    // room for the overview strip, the plot takes the rest of the height
+                    let show_overview = self.keeps_history() && !self.presentation_mode;
+                    if show_overview {
+                        plot = plot.height(
+                            (ui.available_height()
+                                - OVERVIEW_HEIGHT
+                                - ui.spacing().item_spacing.y)
+                                .max(64.0),
+                        );
+                    }
+
+                    if self.plot_geometry_stale() {
+                        self.rebuild_plot_geometry();
+                    }
+                    // moved out for the duration of the frame so the plot can borrow the points
+                    let geometry = std::mem::take(&mut self.plot_geometry);
+
+                    let mut hovered_sample = None;
+                    let mut hovered_line = None;
+                    let mut crosshair_time = None;
+                    let plot_top_left = ui.cursor().min;
+                    let plot_response = plot.show(ui, |plot_ui| {
+                        if !self.grid_in_theme_color {
+                            self.draw_grid(plot_ui, x_grid, selected_channel_count);
+                        }
+                        if let Some(pointer) = plot_ui.pointer_coordinate() {
+                            if self.crosshair {
+                                crosshair_time = Some(geometry.timestamp_at(
+                                    pointer.x,
+                                    self.time_axis_scale,
+                                    window,
+                                ));
+                                plot_ui.vline(
+                                    VLine::new("Crosshair", pointer.x)
+                                        .stroke(Stroke::new(1.0, egui::Color32::GRAY)),
+                                );
+                            } else {
+                                hovered_sample = geometry.sample_at(
+                                    pointer,
+                                    self.plot_layout,
+                                    self.time_axis_scale,
+                                    window,
+                                );
+                            }
+                        }
+
+                        // Navigation is locked while live, so scrolling adjusts the scale and
+                        // dragging a trace vertically adjusts that channel's offset
+                        if selecting_range || self.range_drag_start.is_some() {
+                            self.handle_range_selection(plot_ui, &geometry);
+                        } else if !self.frozen && stacked {
+                            self.handle_plot_interaction(plot_ui, &displayed_channels);
+                        }
+
+                        // channels over the alert threshold get a red row
+                        if stacked {
+                            for trace in geometry.traces.iter() {
+                                if !self.alert_active(trace.channel) {
+                                    continue;
+                                }
+                                let half_height = trace.lane_height / 2.0;
+                                let (bottom, top) = (
+                                    trace.lane_center - half_height,
+                                    trace.lane_center + half_height,
+                                );
+                                plot_ui.polygon(
+                                    Polygon::new(
+                                        format!("Channel {} alert", trace.channel),
+                                        vec![
+                                            [0.0, bottom],
+                                            [window, bottom],
+                                            [window, top],
+                                            [0.0, top],
+                                        ],
+                                    )
+                                    .fill_color(egui::Color32::from_rgba_unmultiplied(
+                                        255, 0, 0, 40,
+                                    ))
+                                    .stroke(Stroke::NONE),
+                                );
+                            }
+                        }
+
+                        // clipped stretches get a red block in their channel's row
+                        if stacked {
+                            for &(ch, start_x, end_x) in geometry.clips.iter() {
+                                let Some(trace) =
+                                    geometry.traces.iter().find(|t| t.channel == ch)
+                                else {
+                                    continue;
+                                };
+                                let half_height = trace.lane_height / 2.0;
+                                let (bottom, top) = (
+                                    trace.lane_center - half_height,
+                                    trace.lane_center + half_height,
+                                );
+                                plot_ui.polygon(
+                                    Polygon::new(
+                                        format!("Channel {} clipping", ch),
+                                        vec![
+                                            [start_x, bottom],
+                                            [end_x, bottom],
+                                            [end_x, top],
+                                            [start_x, top],
+                                        ],
+                                    )
+                                    .fill_color(egui::Color32::from_rgba_unmultiplied(
+                                        255, 99, 71, 90,
+                                    ))
+                                    .stroke(Stroke::NONE),
+                                );
+                            }
+                        }
+
+                        if let Some(ch) = self.peak_channel.filter(|_| !geometry.peaks.is_empty()) {
+                            plot_ui.points(
+                                Points::new(
+                                    format!("{} peaks", self.channel_names[ch]),
+                                    PlotPoints::Borrowed(&geometry.peaks),
+                                )
+                                .shape(if self.peak_settings.troughs {
+                                    MarkerShape::Down
+                                } else {
+                                    MarkerShape::Up
+                                })
+                                .radius(4.0)
+                                .filled(true)
+                                .color(self.trace_color(ch)),
+                            );
+                        }
+
+                        if stacked && self.lane_grid {
+                            self.draw_lane_grid(plot_ui, &geometry.traces, window);
+                        }
+                        if stacked && self.show_scale_bar {
+                            self.draw_scale_bar(plot_ui, &geometry.traces);
+                        }
+
+                        // held traces, dimmed and dashed behind the live ones
+                        if let Some(reference) = &self.reference_traces {
+                            for trace in geometry.traces.iter() {
+                                let Some(points) = reference.get(&trace.channel) else {
+                                    continue;
+                                };
+                                let points: Vec<[f64; 2]> = points
+                                    .iter()
+                                    .map(|&[x, value]| [x, trace.plot_y(value, self.plot_layout)])
+                                    .collect();
+                                plot_ui.line(
+                                    Line::new(
+                                        format!("{} (reference)", self.channel_names[trace.channel]),
+                                        points,
+                                    )
+                                    .stroke(Stroke::new(
+                                        self.line_width,
+                                        self.channel_colors[trace.channel].gamma_multiply(0.4),
+                                    ))
+                                    .style(LineStyle::dashed_loose()),
+                                );
+                            }
+                        }
+
+                        // pixels per unit of x, for spacing the interpolated points
+                        let smoothing_scale = if self.smooth_traces && !self.irregular {
+                            let frame = plot_ui.transform().frame();
+                            frame.width() as f64 / plot_ui.plot_bounds().width().max(f64::EPSILON)
+                        } else {
+                            0.0
+                        };
+                        for trace in geometry.traces.iter() {
+                            let ch_idx = trace.channel;
+                            let color = self.trace_color(ch_idx);
+                            let previous_color = if self.previous_sweep_in_channel_color {
+                                color
+                            } else if self.channel_bad(ch_idx) {
+                                self.previous_sweep_color.gamma_multiply(BAD_CHANNEL_DIMMING)
+                            } else {
+                                self.previous_sweep_color
+                            };
+                            if self.irregular {
+                                // lines would suggest values between samples that may be seconds apart
+                                plot_ui.points(
+                                    Points::new(
+                                        self.channel_names[ch_idx].clone(),
+                                        PlotPoints::Borrowed(&trace.current),
+                                    )
+                                    .radius(2.0)
+                                    .filled(true)
+                                    .color(color),
+                                );
+                            } else if trace.current.len() + trace.previous.len() == 1 {
+                                // a line through one point has no length and wouldn't show
+                                plot_ui.points(
+                                    Points::new(
+                                        self.channel_names[ch_idx].clone(),
+                                        PlotPoints::Owned(
+                                            trace.current.iter().chain(&trace.previous).copied().collect(),
+                                        ),
+                                    )
+                                    .radius(self.line_width + 1.0)
+                                    .filled(true)
+                                    .color(color),
+                                );
+                            } else if self.envelope_mode != EnvelopeMode::Only {
+                                for (points, color) in
+                                    [(&trace.current, color), (&trace.previous, previous_color)]
+                                {
+                                    for run in finite_runs(points) {
+                                        let run = if smoothing_scale > 0.0 {
+                                            PlotPoints::Owned(smooth_points(run, smoothing_scale))
+                                        } else {
+                                            PlotPoints::Borrowed(run)
+                                        };
+                                        plot_ui.line(
+                                            Line::new(self.channel_names[ch_idx].clone(), run)
+                                                .stroke(Stroke::new(self.line_width, color)),
+                                        );
+                                    }
+                                }
+                            }
+                            if !trace.envelope_current.is_empty() || !trace.envelope_previous.is_empty() {
+                                // a lighter shade of the channel colour stands out against the raw trace
+                                let envelope_color = color.lerp_to_gamma(egui::Color32::WHITE, 0.5);
+                                let name = format!("{} RMS", self.channel_names[ch_idx]);
+                                for (points, color) in [
+                                    (&trace.envelope_current, envelope_color),
+                                    (&trace.envelope_previous, previous_color),
+                                ] {
+                                    for run in finite_runs(points) {
+                                        plot_ui.line(
+                                            Line::new(name.clone(), PlotPoints::Borrowed(run))
+                                                .stroke(Stroke::new(2.0 * self.line_width, color)),
+                                        );
+                                    }
+                                }
+                            }
+                            if !trace.average_current.is_empty() || !trace.average_previous.is_empty() {
+                                // bolder than the raw trace, in the same colour
+                                let name = format!("{} average", self.channel_names[ch_idx]);
+                                for (points, color) in [
+                                    (&trace.average_current, color),
+                                    (&trace.average_previous, previous_color),
+                                ] {
+                                    for run in finite_runs(points) {
+                                        plot_ui.line(
+                                            Line::new(name.clone(), PlotPoints::Borrowed(run))
+                                                .stroke(Stroke::new(2.5 * self.line_width, color)),
+                                        );
+                                    }
+                                }
+                            }
+
+                            // flag traces that leave their lane, they're clipped or overlap neighbours
+                            let marker_x = self.time_window_seconds * 0.99;
+                            for (off_scale, y, shape) in [
+                                (
+                                    trace.off_scale_up,
+                                    trace.lane_center + 0.4 * trace.lane_height,
+                                    MarkerShape::Up,
+                                ),
+                                (
+                                    trace.off_scale_down,
+                                    trace.lane_center - 0.4 * trace.lane_height,
+                                    MarkerShape::Down,
+                                ),
+                            ] {
+                                if off_scale {
+                                    plot_ui.points(
+                                        Points::new(
+                                            format!("Channel {} off-scale", ch_idx),
+                                            vec![[marker_x, y]],
+                                        )
+                                        .shape(shape)
+                                        .radius(5.0)
+                                        .filled(true)
+                                        .color(egui::Color32::from_rgb(255, 165, 0)),
+                                    );
+                                }
+                            }
+                        }
+
+                        // timing gaps, shaded across all lanes
+                        let bounds = plot_ui.plot_bounds();
+                        for &(start_x, end_x) in geometry.gaps.iter() {
+                            let (bottom, top) = (bounds.min()[1], bounds.max()[1]);
+                            plot_ui.polygon(
+                                Polygon::new(
+                                    "Gap",
+                                    vec![
+                                        [start_x, bottom],
+                                        [end_x, bottom],
+                                        [end_x, top],
+                                        [start_x, top],
+                                    ],
+                                )
+                                .fill_color(egui::Color32::from_rgba_unmultiplied(
+                                    255, 165, 0, 30,
+                                ))
+                                .stroke(Stroke::NONE),
+                            );
+                        }
+
+                        // the statistics range, shaded across all lanes
+                        for &(start_x, end_x) in geometry.selection.iter() {
+                            let (bottom, top) = (bounds.min()[1], bounds.max()[1]);
+                            plot_ui.polygon(
+                                Polygon::new(
+                                    "Statistics range",
+                                    vec![
+                                        [start_x, bottom],
+                                        [end_x, bottom],
+                                        [end_x, top],
+                                        [start_x, top],
+                                    ],
+                                )
+                                .fill_color(egui::Color32::from_rgba_unmultiplied(
+                                    135, 206, 235, 40,
+                                ))
+                                .stroke(Stroke::NONE),
+                            );
+                        }
+
+                        // event markers, labelled at the top of the plot
+                        let marker_label_y = if stacked {
+                            self.row_spacing / 2.0
+                        } else {
+                            plot_ui.plot_bounds().max()[1]
                         };
+                        // ticks with a legend replace the lines, drawn after the plot
+                        if self.marker_labels != MarkerLabels::Legend {
+                            for (x, _, text) in geometry.markers.iter() {
+                                plot_ui.vline(
+                                    VLine::new(text.clone(), *x).stroke(Stroke::new(
+                                        1.0,
+                                        egui::Color32::from_rgb(255, 215, 0),
+                                    )),
+                                );
+                            }
+                        }
+                        let shown_labels: Vec<&(f64, String, usize)> = match self.marker_labels {
+                            MarkerLabels::Inline => geometry.marker_labels.iter().collect(),
+                            MarkerLabels::Hover => plot_ui
+                                .response()
+                                .hover_pos()
+                                .and_then(|pointer| {
+                                    geometry.marker_labels.iter().find(|(x, ..)| {
+                                        let line_x =
+                                            plot_ui.screen_from_plot(PlotPoint::new(*x, 0.0)).x;
+                                        (line_x - pointer.x).abs() <= LINE_HIT_DISTANCE
+                                    })
+                                })
+                                .into_iter()
+                                .collect(),
+                            // drawn after the plot, egui_plot can't rotate text
+                            MarkerLabels::Rotated | MarkerLabels::Legend => Vec::new(),
+                        };
+                        for (x, text, count) in shown_labels {
+                            let label = marker_label(text, *count);
+                            plot_ui.text(
+                                Text::new(
+                                    label.clone(),
+                                    PlotPoint::new(*x, marker_label_y),
+                                    egui::RichText::new(label).small(),
+                                )
+                                .anchor(egui::Align2::LEFT_TOP)
+                                .color(egui::Color32::from_rgb(255, 215, 0)),
+                            );
+                        }
+
+                        // annotations, labelled at the bottom to stay clear of the markers
+                        let annotation_label_y = plot_ui.plot_bounds().min()[1];
+                        for (x, _, text) in geometry.annotations.iter() {
+                            plot_ui.vline(
+                                VLine::new(text.clone(), *x)
+                                    .stroke(Stroke::new(1.5, egui::Color32::from_rgb(0, 191, 255))),
+                            );
+                            plot_ui.text(
+                                Text::new(
+                                    text.clone(),
+                                    PlotPoint::new(*x, annotation_label_y),
+                                    egui::RichText::new(text).small(),
+                                )
+                                .anchor(egui::Align2::LEFT_BOTTOM)
+                                .color(egui::Color32::from_rgb(0, 191, 255)),
+                            );
+                        }
+                        // clicking next to an annotation's line opens it for editing
+                        let clicked_at = plot_ui.response().interact_pointer_pos();
+                        if let Some(pointer) = clicked_at.filter(|_| plot_ui.response().clicked())
+                            && let Some((timestamp, text)) =
+                                line_at(plot_ui, pointer, &geometry.annotations)
+                        {
+                            self.editing_annotation = Some((timestamp, text.clone(), text.clone()));
+                        }
+                        // hovering one shows its full text, which the label may cut short
+                        if let Some(pointer) = plot_ui.response().hover_pos() {
+                            hovered_line = line_at(plot_ui, pointer, &geometry.annotations)
+                                .map(|(timestamp, text)| ("Annotation", timestamp, text.clone()))
+                                .or_else(|| {
+                                    line_at(plot_ui, pointer, &geometry.markers)
+                                        .map(|(timestamp, text)| ("Marker", timestamp, text.clone()))
+                                });
+                        }
+
+                        // add a vertical line at t_last
+                        if self.show_sweep_line
+                            && self.time_axis_scale == TimeAxisScale::Linear
+                            && !self.scrolls()
+                            && !geometry.traces.is_empty()
+                        {
+                            plot_ui.vline(
+                                VLine::new("Time Window Start", geometry.t_last)
+                                    .stroke(Stroke::new(
+                                        1.0,
+                                        egui::Color32::from_rgb(255, 10, 10),
+                                    ))
+                                    .name("Time Window Start"),
+                            );
+                        }
+                    });
+                    self.plot_rect = Some(plot_response.response.rect);
+                    self.draw_marker_labels(ui, &plot_response.transform, &geometry.marker_labels);
+                    // the axes sit outside the plot's own rect, left of and below it
+                    self.plot_capture_rect = Some(egui::Rect::from_min_max(
+                        plot_top_left,
+                        egui::pos2(plot_response.response.rect.max.x, ui.cursor().min.y),
+                    ));
+                    // the plot's y-coordinates are lane positions, so show the real value instead
+                    if let Some(timestamp) = crosshair_time {
+                        self.crosshair_readout_ui(
+                            ui.ctx(),
+                            plot_response.response.rect,
+                            timestamp,
+                            &displayed_channels,
+                        );
+                    }
+                    // the controls are hidden, so the essentials stay a right-click away
+                    if self.presentation_mode {
+                        plot_response.response.context_menu(|ui| {
+                            if ui.button("Show controls").clicked() {
+                                self.presentation_mode = false;
+                                ui.close_menu();
+                            }
+                            if self.is_connected && ui.button("Disconnect").clicked() {
+                                self.send_command(LslCommand::Disconnect);
+                                ui.close_menu();
+                            }
+                        });
+                    }
+                    if let Some((kind, timestamp, text)) = hovered_line {
+                        plot_response.response.on_hover_ui_at_pointer(|ui| {
+                            ui.label(egui::RichText::new(kind).small().weak());
+                            ui.label(text);
+                            ui.label(format!("t = {}", self.format_timestamp(timestamp)));
+                        });
+                    } else if let Some((ch, timestamp, value)) = hovered_sample {
+                        plot_response.response.on_hover_ui_at_pointer(|ui| {
+                            ui.label(&self.channel_names[ch]);
+                            ui.label(format!("{:.3} {}", value, self.channel_unit(ch)));
+                            ui.label(format!("t = {}", self.format_timestamp(timestamp)));
+                        });
+                    }
+                    let (points_drawn, points_available) =
+                        (geometry.points_drawn, geometry.points_available);
+                    self.plot_geometry = geometry;
+                    if show_overview {
+                        self.overview_ui(ui);
+                    }
+
+                    // Display some stats; presentation mode shows only the traces
+                    if !self.presentation_mode {
+                        ui.horizontal(|ui| {
+                            let total_samples: usize =
+                                self.data_buffer.iter().map(|b| b.len()).sum();
+                            ui.label(format!("Total samples buffered: {}", total_samples))
+                                .on_hover_text(format!(
+                                    "At most {} samples per channel",
+                                    self.buffer_sample_limit()
+                                ));
+                            ui.label(format!(
+                                "≈ {:.1} MB",
+                                self.buffer_memory_bytes() as f64 / 1e6
+                            ));
+
+                            // Make it obvious when the plot shows a reduced view of the data
+                            if points_drawn < points_available {
+                                ui.label(format!(
+                                    "Drawing {} / {} pts",
+                                    points_drawn, points_available
+                                ))
+                                .on_hover_text(
+                                    "The display is decimated; short features may not be visible",
+                                );
+                            }
+
+                            if self.stream_sample_rate() > 0.0 {
+                                let text = format!("Gaps: {}", self.gap_count);
+                                if self.gap_count > 0 {
+                                    ui.colored_label(egui::Color32::from_rgb(255, 165, 0), text)
+                                } else {
+                                    ui.label(text)
+                                }
+                                .on_hover_text(format!(
+                                    "Steps between timestamps longer than {}× the nominal sample interval",
+                                    GAP_THRESHOLD
+                                ));
+                            }
+
+                            if let Some(&last_time) = self.timestamp_buffer.back() {
+                                ui.label(format!(
+                                    "Last timestamp: {}",
+                                    self.format_timestamp(last_time)
+                                ))
+                                .on_hover_text(format!(
+                                    "LSL time {:.*} s",
+                                    self.timestamp_decimals, last_time
+                                ));
+                            }
+                            if let (Some(first), Some(last)) =
+                                (self.timestamp_buffer.front(), self.timestamp_buffer.back())
+                            {
+                                ui.label(format!(
+                                    "Buffered duration: {}",
+                                    self.duration_style.format(last - first)
+                                ));
+                            }
+
+                            if ui
+                                .toggle_value(&mut self.frozen, "❄ Freeze")
+                                .on_hover_text(
+                                    "Stop the display to inspect it; pan and zoom are enabled while frozen",
+                                )
+                                .changed()
+                                && !self.frozen
+                            {
+                                self.snap_back_to_live = true;
+                            }
 
-                        if resp_tx.send(LslResponse::Data(data)).is_err() {
-                            panic!("Failed to send data response");
+                            if ui
+                            .button("Hide controls")
+                            .on_hover_text("Only the plot, e.g. for a demo; H or the plot's right-click menu brings the controls back")
+                            .clicked()
+                        {
+                            self.presentation_mode = true;
                         }
+                        let pop_out_label = if self.plot_popped_out {
+                                "Return plot"
+                            } else {
+                                "Pop out plot"
+                            };
+                            if ui
+                                .button(pop_out_label)
+                                .on_hover_text("Show the plot in a window of its own, e.g. on another screen")
+                                .clicked()
+                            {
+                                self.plot_popped_out = !self.plot_popped_out;
+                            }
+                            if ui
+                                .button("Snapshot")
+                                .on_hover_text("Keep an image of the plot in the snapshot gallery")
+                                .clicked()
+                            {
+                                self.snapshot_requested = true;
+                                ctx.send_viewport_cmd(egui::ViewportCommand::Screenshot(
+                                    Default::default(),
+                                ));
+                            }
+                            if self.reference_traces.is_none() {
+                                if ui
+                                    .button("Hold reference")
+                                    .on_hover_text("Keep the traces on screen as a dashed overlay to compare the live data against")
+                                    .clicked()
+                                {
+                                    self.hold_reference_traces();
+                                }
+                            } else if ui.button("Clear reference").clicked() {
+                                self.reference_traces = None;
+                            }
+                            if ui
+                                .button("Save Image")
+                                .on_hover_text("Save the plot with its labels, axes and markers as a PNG")
+                                .clicked()
+                            {
+                                self.image_save_requested = true;
+                                ctx.send_viewport_cmd(egui::ViewportCommand::Screenshot(
+                                    Default::default(),
+                                ));
+                            }
+                            egui::ComboBox::from_id_source("image_scale")
+                                .width(50.0)
+                                .selected_text(format!("{}×", self.image_scale))
+                                .show_ui(ui, |ui| {
+                                    for scale in 1..=4 {
+                                        ui.selectable_value(
+                                            &mut self.image_scale,
+                                            scale,
+                                            format!("{}×", scale),
+                                        );
+                                    }
+                                })
+                                .response
+                                .on_hover_text("Size of the saved image relative to the screen");
+                            if !self.snapshots.is_empty()
+                                && ui
+                                    .button(format!("Gallery ({})", self.snapshots.len()))
+                                    .clicked()
+                            {
+                                self.show_gallery = true;
+                            }
+                        });
                     }
+                } else {
+                    ui.label("No data received yet...");
                 }
-                thread::sleep(Duration::from_millis(25));
-            } else {
-                panic!("Failed to pull data from LSL inlet");
             }
-        } else {
-            thread::sleep(Duration::from_millis(25));
-        }
+        });
     }
 }
 
 impl eframe::App for LslViewer {
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        eframe::set_value(storage, eframe::APP_KEY, &self.settings());
+    }
+
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        if self.applied_theme != Some(self.theme) {
+            ctx.set_theme(self.theme.preference());
+            self.applied_theme = Some(self.theme);
+        }
+        // follows the visuals in use, which for System can change with the OS setting
+        let light = !ctx.style().visuals.dark_mode;
+        if light != self.light_palette {
+            self.light_palette = light;
+            self.assign_channel_colors();
+        }
+
         // Process responses from LSL thread
         self.process_responses();
+        self.poll_export();
+        #[cfg(feature = "audio")]
+        self.poll_audio_errors();
+
+        self.handle_screenshot(ctx);
+        self.handle_shortcuts(ctx);
+        self.gallery_window(ctx);
+        self.stream_xml_window(ctx);
+        self.shortcuts_window(ctx);
+        self.log_window(ctx);
+        self.annotation_window(ctx);
 
         // Auto-refresh UI
         if self.auto_refresh {
             ctx.request_repaint_after(Duration::from_millis(32)); // ~60 FPS
         }
 
-        // left panel for stream selection and controls
+        // right panel for stream selection and controls, hidden in presentation mode
         egui::SidePanel::right("right_panel")
             .default_width(300.0)
-            .show(ctx, |ui| {
+            .show_animated(ctx, !self.presentation_mode, |ui| {
                 ui.vertical(|ui| {
                     // Connection controls
                     // only show the refresh button if not connected
+                    self.playback_ui(ui);
                     if !self.is_connected {
                         ui.horizontal(|ui| {
-                            if ui.button("Refresh Streams").clicked() {
-                                self.send_command(LslCommand::RefreshStreams);
+                            if ui
+                                .button("Refresh Streams")
+                                .on_hover_text("The list also follows streams appearing and disappearing by itself")
+                                .clicked()
+                            {
+                                self.refresh_streams();
+                            }
+                            if ui
+                                .button("Open File…")
+                                .on_hover_text("Play back a CSV recording instead of a live stream")
+                                .clicked()
+                                && let Some(path) = rfd::FileDialog::new()
+                                    .add_filter("CSV recording", &["csv"])
+                                    .pick_file()
+                                {
+                                    self.open_playback(&path);
+                                }
+                        });
+
+                        ui.group(|ui| {
+                            ui.label("Connection Settings");
+                            ui.horizontal(|ui| {
+                                ui.label("Auto-select up to");
+                                ui.add(
+                                    egui::DragValue::new(&mut self.max_auto_selected_channels)
+                                        .range(1..=1024),
+                                );
+                                ui.label("channels");
+                            })
+                            .response
+                            .on_hover_text(
+                                "Only the first channels are ticked on connect; the rest can be enabled by hand",
+                            );
+                            ui.horizontal(|ui| {
+                                ui.label("Auto-select types");
+                                ui.add(
+                                    egui::TextEdit::singleline(&mut self.auto_select_types)
+                                        .hint_text("any, e.g. EEG, EOG")
+                                        .desired_width(120.0),
+                                );
+                            })
+                            .response
+                            .on_hover_text(
+                                "Comma-separated channel types from the stream metadata to tick on connect; if none match, the first channels are ticked",
+                            );
+                            ui.horizontal(|ui| {
+                                ui.label("Metadata timeout");
+                                ui.add(
+                                    egui::DragValue::new(&mut self.connect_options.info_timeout)
+                                        .range(0.1..=60.0)
+                                        .speed(0.1)
+                                        .suffix(" s"),
+                                );
+                                ui.label("retries");
+                                ui.add(
+                                    egui::DragValue::new(&mut self.connect_options.info_retries)
+                                        .range(0..=10),
+                                );
+                            })
+                            .response
+                            .on_hover_text(
+                                "If the stream metadata can't be fetched, the viewer connects with generated channel names",
+                            );
+                            ui.horizontal(|ui| {
+                                ui.label("Connect timeout");
+                                ui.add(
+                                    egui::DragValue::new(&mut self.connect_options.connect_timeout)
+                                        .range(1.0..=300.0)
+                                        .speed(0.5)
+                                        .suffix(" s"),
+                                );
+                            })
+                            .response
+                            .on_hover_text(
+                                "Give up on a connect that takes longer than this in all, from finding the stream to fetching its metadata",
+                            );
+                            ui.horizontal(|ui| {
+                                ui.label("Inlet buffer");
+                                ui.add(
+                                    egui::DragValue::new(&mut self.connect_options.buffer_seconds)
+                                        .range(1..=3600)
+                                        .suffix(" s"),
+                                );
+                                egui::ComboBox::from_id_source("pull_mode")
+                                    .selected_text(self.connect_options.pull_mode.label())
+                                    .show_ui(ui, |ui| {
+                                        for mode in PullMode::ALL {
+                                            ui.selectable_value(
+                                                &mut self.connect_options.pull_mode,
+                                                mode,
+                                                mode.label(),
+                                            );
+                                        }
+                                    });
+                            })
+                            .response
+                            .on_hover_text(
+                                format!("Seconds of data the inlet keeps before dropping the oldest (×100 samples for irregular-rate streams), and how samples are taken out of it. Automatic takes streams up to {} Hz one by one as they arrive, so they move smoothly, and the rest in chunks", BLOCKING_PULL_MAX_RATE),
+                            );
+                            ui.horizontal(|ui| {
+                                ui.label("Idle wait");
+                                ui.add(
+                                    egui::DragValue::new(&mut self.connect_options.idle_wait_ms)
+                                        .range(1..=100)
+                                        .suffix(" ms"),
+                                );
+                            })
+                            .response
+                            .on_hover_text(
+                                "Pause after a pull that returned nothing; while data is flowing the inlet is drained without pausing",
+                            );
+                            ui.checkbox(&mut self.connect_options.recover, "Let LSL recover the inlet")
+                                .on_hover_text("Passed to the inlet; LSL tries to resume an interrupted stream on its own");
+                            if let Some(last_stream) = &self.last_stream {
+                                ui.checkbox(
+                                    &mut self.auto_connect_last_stream,
+                                    format!("Connect to {} on startup", last_stream),
+                                )
+                                .on_hover_text("The most recently connected stream, if it is on the network when the viewer starts");
                             }
+                            ui.checkbox(&mut self.connect_options.auto_reconnect, "Reconnect to lost streams")
+                                .on_hover_text("If the inlet fails, keep looking for the stream by its source_id and resume without clearing the plot");
+                            ui.checkbox(&mut self.connect_options.skip_backlog, "Skip backlog")
+                                .on_hover_text(format!("On resume, or when more than {} s of data has queued up in the inlet, drop it and jump to live data. Never while recording", BACKLOG_FLUSH_SECONDS));
+                            ui.horizontal_wrapped(|ui| {
+                                ui.label("Postprocessing:");
+                                for flag in PostprocessingFlag::ALL {
+                                    let requested = &mut self.connect_options.postprocessing;
+                                    let mut enabled = requested.contains(&flag);
+                                    if ui
+                                        .checkbox(&mut enabled, flag.label())
+                                        .on_hover_text(flag.description())
+                                        .changed()
+                                    {
+                                        if enabled {
+                                            requested.push(flag);
+                                        } else {
+                                            requested.retain(|&f| f != flag);
+                                        }
+                                    }
+                                }
+                            });
+                            egui::Grid::new("resolve_filters").show(ui, |ui| {
+                                ui.label("Only host");
+                                ui.add(
+                                    egui::TextEdit::singleline(&mut self.hostname_filter)
+                                        .hint_text("any")
+                                        .desired_width(120.0),
+                                );
+                                ui.end_row();
+                                ui.label("Only source_id");
+                                ui.add(
+                                    egui::TextEdit::singleline(&mut self.source_id_filter)
+                                        .hint_text("any")
+                                        .desired_width(120.0),
+                                );
+                                ui.end_row();
+                            })
+                            .response
+                            .on_hover_text("Streams from other machines or sources aren't listed; takes effect on the next refresh");
                         });
 
+                        // One click back to the streams of earlier sessions
+                        if !self.is_connected && !self.recent_streams.is_empty() {
+                            ui.horizontal_wrapped(|ui| {
+                                ui.label("Recent:");
+                                let mut clicked = None;
+                                for recent in self.recent_streams.iter() {
+                                    let mut hover = format!("Resolve {} by name and connect", recent.name);
+                                    if !recent.source_id.is_empty() {
+                                        hover.push_str(&format!("\nsource_id: {}", recent.source_id));
+                                    }
+                                    if ui
+                                        .add_enabled(self.connect_stage.is_none(), egui::Button::new(&recent.name))
+                                        .on_hover_text(hover)
+                                        .clicked()
+                                    {
+                                        clicked = Some(recent.name.clone());
+                                    }
+                                }
+                                if let Some(name) = clicked {
+                                    self.connect_by_name(name);
+                                }
+                            });
+                        }
+
                         // Stream selection
                         if !self.available_streams.is_empty() {
+                            ui.horizontal(|ui| {
+                                ui.add(
+                                    egui::TextEdit::singleline(&mut self.stream_name_filter)
+                                        .hint_text("Filter by name")
+                                        .desired_width(120.0),
+                                );
+                                egui::ComboBox::from_id_source("stream_type_filter")
+                                    .selected_text(self.stream_type_filter.label())
+                                    .show_ui(ui, |ui| {
+                                        for filter in StreamTypeFilter::ALL {
+                                            ui.selectable_value(
+                                                &mut self.stream_type_filter,
+                                                filter,
+                                                filter.label(),
+                                            );
+                                        }
+                                    });
+                                egui::ComboBox::from_id_source("stream_sort")
+                                    .selected_text(self.stream_sort.label())
+                                    .show_ui(ui, |ui| {
+                                        for sort in StreamSort::ALL {
+                                            ui.selectable_value(
+                                                &mut self.stream_sort,
+                                                sort,
+                                                sort.label(),
+                                            );
+                                        }
+                                    });
+                            });
                             ui.group(|ui| {
                                 ui.label("Available Streams:");
-                                let streams = self.available_streams.clone();
-                                for (i, stream) in streams.iter().enumerate() {
+                                let name_filter = self.stream_name_filter.to_lowercase();
+                                let type_filter = self.stream_type_filter;
+                                let streams = &self.available_streams;
+                                let mut shown: Vec<&StreamData> = streams
+                                    .iter()
+                                    .filter(|stream| {
+                                        stream.name.to_lowercase().contains(&name_filter)
+                                            && type_filter.matches(&stream.stream_type)
+                                    })
+                                    .collect();
+                                let sort = self.stream_sort;
+                                shown.sort_by(|a, b| sort.compare(a, b));
+                                let mut shown_count = 0;
+                                // acted on after the loop, which borrows the list
+                                let mut clicked = None;
+                                for stream in shown {
+                                    shown_count += 1;
                                     ui.horizontal(|ui| {
-                                        let is_selected = self.selected_stream_index == Some(i);
+                                        let is_selected = self
+                                            .selected_stream
+                                            .as_ref()
+                                            .is_some_and(|s| s.key() == stream.key());
                                         if ui
                                             .selectable_label(
                                                 is_selected,
                                                 format!(
-                                                    "{} - {} channels @ {} Hz",
+                                                    "{} - {} channels @ {}",
                                                     stream.name,
                                                     stream.channel_count,
-                                                    stream.sample_rate
+                                                    stream.rate_label()
                                                 ),
                                             )
+                                            .on_hover_text(format!(
+                                                "source_id: {}",
+                                                stream.source_id
+                                            ))
                                             .clicked()
                                             && !self.is_connected
+                                            && self.connect_stage.is_none()
                                         {
-                                            self.selected_stream_index = Some(i);
-                                            self.send_command(LslCommand::Connect(i));
+                                            clicked = Some(stream.clone());
+                                        }
+                                        if !stream.hostname.is_empty() {
+                                            ui.label(
+                                                egui::RichText::new(&stream.hostname).small().weak(),
+                                            );
+                                        }
+                                        if self.new_streams.get(stream.key()).is_some_and(|seen| {
+                                            seen.elapsed().as_secs_f64() < NEW_STREAM_BADGE_SECONDS
+                                        }) {
+                                            ui.colored_label(
+                                                egui::Color32::from_rgb(144, 238, 144),
+                                                "new",
+                                            );
                                         }
                                     });
                                 }
+                                if shown_count < streams.len() {
+                                    ui.label(
+                                        egui::RichText::new(format!(
+                                            "{} of {} streams hidden by the filter",
+                                            streams.len() - shown_count,
+                                            streams.len()
+                                        ))
+                                        .small()
+                                        .weak(),
+                                    );
+                                }
+                                if let Some(stream) = clicked {
+                                    let key = stream.key().to_string();
+                                    self.selected_stream = Some(stream);
+                                    self.connect_stage = Some(ConnectStage::Resolving);
+                                    self.send_command(LslCommand::Connect(
+                                        key,
+                                        self.connect_options.clone(),
+                                    ));
+                                }
                             });
                         }
                     }
@@ -402,28 +6446,238 @@ impl eframe::App for LslViewer {
 
                     if self.is_connected && self.channel_count > 0 {
                         ui.group(|ui| {
+                            if !self.auto_select_types.trim().is_empty() {
+                                ui.label(
+                                    egui::RichText::new(format!(
+                                        "Channels of type {} were selected on connect.",
+                                        self.auto_select_types.trim()
+                                    ))
+                                    .small()
+                                    .weak(),
+                                );
+                            } else if self.channel_count > self.max_auto_selected_channels {
+                                ui.label(
+                                    egui::RichText::new(format!(
+                                        "Only the first {} channels were selected on connect; {} more are available.",
+                                        self.max_auto_selected_channels,
+                                        self.channel_count - self.max_auto_selected_channels
+                                    ))
+                                    .small()
+                                    .weak(),
+                                );
+                            }
+                            let name_filter = self.channel_name_filter.to_lowercase();
+                            let listed: Vec<bool> = self
+                                .channel_names
+                                .iter()
+                                .map(|name| name.to_lowercase().contains(&name_filter))
+                                .collect();
+                            ui.horizontal(|ui| {
+                                ui.add(
+                                    egui::TextEdit::singleline(&mut self.channel_name_filter)
+                                        .hint_text("Filter channels")
+                                        .desired_width(120.0),
+                                );
+                                let hidden = listed.iter().filter(|&&l| !l).count();
+                                if hidden > 0 {
+                                    ui.label(
+                                        egui::RichText::new(format!("{} hidden", hidden))
+                                            .small()
+                                            .weak(),
+                                    );
+                                }
+                            });
                             ui.horizontal_wrapped(|ui| {
-                                if ui.button("All").clicked() {
-                                    self.selected_channels.fill(true);
+                                let all = ui.button("All").clicked();
+                                let none = ui.button("None").clicked();
+                                let invert = ui.button("Invert").clicked();
+                                // the buttons only touch the channels the filter lists
+                                if all || none || invert {
+                                    for (selected, _) in self
+                                        .selected_channels
+                                        .iter_mut()
+                                        .zip(listed.iter())
+                                        .filter(|(_, l)| **l)
+                                    {
+                                        *selected = all || (!none && !*selected);
+                                    }
+                                }
+                                if !self.color_overrides.is_empty()
+                                    && ui
+                                        .button("Reset colors")
+                                        .on_hover_text("Go back to the default palette")
+                                        .clicked()
+                                {
+                                    self.color_overrides.clear();
+                                    self.assign_channel_colors();
+                                }
+                                if self.channel_offsets.iter().any(|&o| o != 0.0)
+                                    && ui
+                                        .button("Reset offsets")
+                                        .on_hover_text("Put every trace back in the middle of its lane")
+                                        .clicked()
+                                {
+                                    self.channel_offsets.fill(0.0);
+                                }
+                                if self.channel_order.iter().enumerate().any(|(row, &ch)| row != ch)
+                                    && ui
+                                        .button("Reset order")
+                                        .on_hover_text("Go back to the stream's channel order")
+                                        .clicked()
+                                {
+                                    self.channel_order = (0..self.channel_count).collect();
+                                }
+                                if self.solo_channel.is_some() && ui.button("Clear solo").clicked() {
+                                    self.solo_channel = None;
+                                }
+                                if self.muted_channels.iter().any(|&m| m) && ui.button("Unmute all").clicked() {
+                                    self.muted_channels.fill(false);
+                                }
+                                ui.separator();
+
+                                // rows are listed in display order, a row's handle can be dropped onto another's
+                                let mut moved = None;
+                                let mut toggled_bypass = None;
+                                for row in 0..self.channel_order.len() {
+                                    let i = self.channel_order[row];
+                                    if !listed[i] {
+                                        continue;
+                                    }
+                                    let name = &self.channel_names[i];
+                                    let handle = ui
+                                        .dnd_drag_source(egui::Id::new(("channel_row", row)), row, |ui| {
+                                            ui.label("☰");
+                                        })
+                                        .response
+                                        .on_hover_text("Drag onto another channel to move it there");
+                                    if let Some(from) = handle.dnd_release_payload::<usize>() {
+                                        moved = Some((*from, row));
+                                    }
+                                    if ui
+                                        .color_edit_button_srgba(&mut self.channel_colors[i])
+                                        .changed()
+                                    {
+                                        self.color_overrides
+                                            .insert(name.clone(), self.channel_colors[i]);
+                                    }
+                                    let unit = self.channel_unit(i).to_string();
+                                    let mut details = Vec::new();
+                                    if !unit.is_empty() {
+                                        details.push(format!("unit {}", unit));
+                                    }
+                                    if let Some(kind) =
+                                        self.channel_types.get(i).filter(|t| !t.is_empty())
+                                    {
+                                        details.push(format!("type {}", kind));
+                                    }
+                                    let bad = self.bad_channels[i];
+                                    let label = if bad {
+                                        egui::RichText::new(name).weak().strikethrough()
+                                    } else {
+                                        egui::RichText::new(name)
+                                    };
+                                    let response =
+                                        ui.checkbox(&mut self.selected_channels[i], label);
+                                    let bypassed = self.filter_bypass[i];
+                                    response.context_menu(|ui| {
+                                        if ui
+                                            .button(if bad { "Mark as good" } else { "Mark as bad" })
+                                            .clicked()
+                                        {
+                                            self.bad_channels[i] = !bad;
+                                            self.last_stats_update = None;
+                                            ui.close_menu();
+                                        }
+                                        if ui
+                                            .button(if bypassed { "Apply filters" } else { "Bypass filters" })
+                                            .clicked()
+                                        {
+                                            toggled_bypass = Some(i);
+                                            ui.close_menu();
+                                        }
+                                    });
+                                    let soloed = self.solo_channel == Some(i);
+                                    if ui
+                                        .selectable_label(soloed, "S")
+                                        .on_hover_text(format!("Show only {} until solo is cleared", name))
+                                        .clicked()
+                                    {
+                                        self.solo_channel = if soloed { None } else { Some(i) };
+                                    }
+                                    ui.toggle_value(&mut self.muted_channels[i], "M")
+                                        .on_hover_text(format!("Hide {} without unticking it", name));
+                                    if !unit.is_empty() {
+                                        ui.label(egui::RichText::new(&unit).small().weak());
+                                    }
+                                    let badges = self.filters.applied(i).badges();
+                                    if !badges.is_empty() {
+                                        ui.label(
+                                            egui::RichText::new(badges)
+                                                .small()
+                                                .monospace()
+                                                .color(egui::Color32::from_rgb(135, 206, 235)),
+                                        )
+                                        .on_hover_text("Filters applied to this channel: BP band-pass, N notch, AC AC coupling");
+                                    } else if self.filter_bypass[i] {
+                                        ui.label(egui::RichText::new("unfiltered").small().weak())
+                                            .on_hover_text("Filters are bypassed for this channel; right-click its name to apply them");
+                                    }
+                                    if !details.is_empty() {
+                                        response.on_hover_text(details.join(", "));
+                                    }
+                                    if !self.link_scales {
+                                        ui.add(
+                                            egui::DragValue::new(&mut self.channel_scales[i])
+                                                .range(SCALE_RANGE)
+                                                .speed(0.1)
+                                                .max_decimals(1),
+                                        )
+                                        .on_hover_text(format!("Gain of {}", name));
+                                    }
+                                    ui.add(
+                                        egui::DragValue::new(&mut self.channel_offsets[i])
+                                            .speed(0.01)
+                                            .max_decimals(2)
+                                            .prefix("↕"),
+                                    )
+                                    .on_hover_text(format!(
+                                        "Vertical offset of {} in lanes; dragging a trace or double-clicking it works too",
+                                        name
+                                    ));
                                 }
-                                if ui.button("None").clicked() {
-                                    self.selected_channels.fill(false);
+                                if let Some((from, to)) = moved {
+                                    let ch = self.channel_order.remove(from);
+                                    self.channel_order.insert(to, ch);
                                 }
-                                ui.separator();
-
-                                for (i, name) in self.channel_names.iter().enumerate() {
-                                    ui.checkbox(&mut self.selected_channels[i], name);
+                                if let Some(ch) = toggled_bypass {
+                                    self.filter_bypass[ch] = !self.filter_bypass[ch];
+                                    self.rebuild_filters();
                                 }
                             });
                         });
 
                         // Scale control via slider
                         ui.group(|ui| {
-                            ui.label("Scale");
+                            ui.horizontal(|ui| {
+                                ui.label("Scale");
+                                ui.checkbox(&mut self.link_scales, "Link all channels")
+                                    .on_hover_text("Off: each channel gets its own gain next to its checkbox, and scrolling over a lane adjusts only that channel");
+                            });
+                            // no single unit fits channels that declare different ones
+                            let slider_unit = if self.mixed_units() {
+                                String::new()
+                            } else if self.display_unit.is_empty() {
+                                "mV".to_string()
+                            } else {
+                                self.display_unit.clone()
+                            };
                             if ui
-                                .add(
-                                    egui::Slider::new(&mut self.data_scale, 1.0..=100.0)
-                                        .text("mV")
+                                .add_enabled(
+                                    self.link_scales,
+                                    egui::Slider::new(&mut self.data_scale, SCALE_RANGE)
+                                        .text(slider_unit)
+                                        .logarithmic(true)
+                                        .show_value(false)
                                         .clamp_to_range(true),
                                 )
                                 .changed()
@@ -431,34 +6685,427 @@ impl eframe::App for LslViewer {
                                 // Update scale immediately
                                 self.baseline_correct();
                             }
+                            // Exact entry, so a setting can be noted down and reproduced
+                            ui.horizontal(|ui| {
+                                ui.label("Gain:");
+                                ui.add_enabled(
+                                    self.link_scales,
+                                    egui::DragValue::new(&mut self.data_scale)
+                                        .range(SCALE_RANGE)
+                                        .speed(0.1)
+                                        .max_decimals(3),
+                                );
+                                ui.label(format!(
+                                    "(±{:.4} {} per lane)",
+                                    0.5 * self.row_spacing * SCALE_NORMALIZATION / self.data_scale,
+                                    self.display_unit
+                                ));
+                            });
+
+                            ui.horizontal(|ui| {
+                                ui.selectable_value(
+                                    &mut self.lane_scaling,
+                                    LaneScaling::Shared,
+                                    "Shared scale",
+                                )
+                                .on_hover_text("All lanes use the scale above, so amplitudes can be compared");
+                                ui.selectable_value(
+                                    &mut self.lane_scaling,
+                                    LaneScaling::Independent,
+                                    "Independent lanes",
+                                )
+                                .on_hover_text("Each lane is autoscaled so its trace fills the lane");
+                            });
+
+                            // Quick presets: amplitude that fills half a lane in each direction
+                            ui.horizontal_wrapped(|ui| {
+                                for full_scale in AMPLITUDE_PRESETS {
+                                    let preset_scale = scale_for_full_scale(full_scale);
+                                    if ui
+                                        .selectable_label(
+                                            (self.data_scale - preset_scale).abs() < 1e-9,
+                                            format!("±{}{}", full_scale, self.display_unit),
+                                        )
+                                        .on_hover_text(format!(
+                                            "A deflection of ±{} {} fills one channel lane",
+                                            full_scale, self.display_unit
+                                        ))
+                                        .clicked()
+                                    {
+                                        // presets apply to every channel, linked or not
+                                        self.data_scale = preset_scale;
+                                        self.channel_scales.fill(preset_scale);
+                                        self.baseline_correct();
+                                    }
+                                }
+                                if ui
+                                    .button("±auto")
+                                    .on_hover_text("Fit the largest deflection of the selected channels to one lane, per channel when unlinked")
+                                    .clicked()
+                                {
+                                    self.baseline_correct();
+                                    let fit = |full_scale: f64| {
+                                        scale_for_full_scale(full_scale)
+                                            .clamp(*SCALE_RANGE.start(), *SCALE_RANGE.end())
+                                    };
+                                    if self.link_scales {
+                                        if let Some(full_scale) = self.max_deflection() {
+                                            self.data_scale = fit(full_scale);
+                                        }
+                                    } else {
+                                        for ch in 0..self.channel_scales.len() {
+                                            if self.channel_shown(ch)
+                                                && let Some(full_scale) =
+                                                    self.channel_max_deflection(ch)
+                                                {
+                                                    self.channel_scales[ch] = fit(full_scale);
+                                                }
+                                        }
+                                    }
+                                }
+                            });
+                            ui.horizontal(|ui| {
+                                if ui
+                                    .button("Auto Scale")
+                                    .on_hover_text(format!(
+                                        "Fit the {:.0}th percentile amplitude of the selected channels to {:.0}% of a lane, ignoring flat channels",
+                                        AUTO_SCALE_PERCENTILE * 100.0,
+                                        AUTO_SCALE_LANE_FRACTION * 100.0
+                                    ))
+                                    .clicked()
+                                {
+                                    self.auto_scale();
+                                }
+                                ui.checkbox(&mut self.auto_scale_continuous, "Continuous")
+                                    .on_hover_text("Repeat the auto scale every second");
+                            });
+                        });
+
+                        // Unit conversion applied to incoming values
+                        ui.group(|ui| {
+                            ui.label("Units");
+                            if !self.declared_unit.is_empty() {
+                                ui.label(format!("Declared unit: {}", self.declared_unit));
+                            }
+                            let mut conversion = None;
+                            ui.horizontal_wrapped(|ui| {
+                                if ui.button("None").clicked() {
+                                    conversion = Some((1.0, self.declared_unit.clone()));
+                                }
+                                for (label, factor, unit) in UNIT_PRESETS {
+                                    if ui
+                                        .selectable_label(
+                                            self.unit_factor == factor && self.display_unit == unit,
+                                            label,
+                                        )
+                                        .clicked()
+                                    {
+                                        conversion = Some((factor, unit.to_string()));
+                                    }
+                                }
+                            });
+                            ui.horizontal(|ui| {
+                                ui.label("Factor");
+                                let mut factor = self.unit_factor;
+                                if ui
+                                    .add(egui::DragValue::new(&mut factor).speed(0.1))
+                                    .changed()
+                                    && factor != 0.0
+                                {
+                                    conversion = Some((factor, self.display_unit.clone()));
+                                }
+                                ui.label("Unit");
+                                let mut unit = self.display_unit.clone();
+                                if ui
+                                    .add(egui::TextEdit::singleline(&mut unit).desired_width(50.0))
+                                    .changed()
+                                {
+                                    conversion = Some((self.unit_factor, unit));
+                                }
+                            });
+                            if let Some((factor, unit)) = conversion {
+                                self.set_unit_conversion(factor, unit);
+                            }
                         });
 
                         // Time window control via drop-down
                         ui.group(|ui| {
+                            ui.horizontal(|ui| {
+                                ui.selectable_value(&mut self.plot_mode, PlotMode::Time, "Time");
+                                ui.selectable_value(
+                                    &mut self.plot_mode,
+                                    PlotMode::Spectrum,
+                                    "Spectrum",
+                                )
+                                .on_hover_text(format!(
+                                    "Hann-windowed FFT of the newest {} samples of each selected channel",
+                                    spectrum::FFT_SIZE
+                                ));
+                                let previous_mode = self.plot_mode;
+                                ui.selectable_value(
+                                    &mut self.plot_mode,
+                                    PlotMode::Spectrogram,
+                                    "Spectrogram",
+                                )
+                                .on_hover_text("Short-time spectra of one channel over the time window");
+                                ui.selectable_value(&mut self.plot_mode, PlotMode::Erp, "ERP")
+                                    .on_hover_text("Average of the epochs around every event of the marker stream");
+                                ui.selectable_value(&mut self.plot_mode, PlotMode::Digits, "Digits")
+                                    .on_hover_text("The newest value of one channel in large digits, with its min and max");
+                                if self.plot_mode != previous_mode {
+                                    self.reset_spectrogram();
+                                    self.digits_range = None;
+                                }
+                            });
+                            ui.horizontal(|ui| {
+                                ui.selectable_value(
+                                    &mut self.plot_layout,
+                                    PlotLayout::Stacked,
+                                    "Stacked",
+                                )
+                                .on_hover_text("Each channel in its own lane");
+                                ui.selectable_value(
+                                    &mut self.plot_layout,
+                                    PlotLayout::Overlay,
+                                    "Overlay",
+                                )
+                                .on_hover_text("All channels on one axis in their real values");
+                                ui.checkbox(&mut self.reverse_order, "Reverse order")
+                                    .on_hover_text("Stack the first channel at the bottom");
+                                ui.add_enabled(
+                                    self.plot_layout == PlotLayout::Overlay,
+                                    egui::Checkbox::new(&mut self.log_y, "Log Y"),
+                                )
+                                .on_hover_text("Overlay only: plot sign(x)·log10(1 + |x|), so small and large values show together. Magnitudes below 1 are compressed towards zero");
+                                ui.add_enabled(
+                                    self.plot_layout == PlotLayout::Overlay,
+                                    egui::Checkbox::new(&mut self.z_score, "Z-score"),
+                                )
+                                .on_hover_text("Overlay only: scale each channel to zero mean and unit standard deviation over the window, so channels of very different amplitude can be compared by shape");
+                            });
+                            ui.add(
+                                egui::Slider::new(&mut self.row_spacing, ROW_SPACING_RANGE)
+                                    .text("Row spacing")
+                                    .step_by(0.25),
+                            )
+                            .on_hover_text("Distance between stacked lanes; more room per trace at the same gain");
+                            ui.add(
+                                egui::Slider::new(&mut self.line_width, LINE_WIDTH_RANGE)
+                                    .text("Line width")
+                                    .step_by(0.5),
+                            );
+                            ui.horizontal(|ui| {
+                                ui.checkbox(
+                                    &mut self.previous_sweep_in_channel_color,
+                                    "Previous sweep in channel colors",
+                                )
+                                .on_hover_text("Off: the older data right of the sweep line is drawn in the color picked here");
+                                ui.add_enabled_ui(!self.previous_sweep_in_channel_color, |ui| {
+                                    ui.color_edit_button_srgba(&mut self.previous_sweep_color);
+                                });
+                            });
+                            ui.checkbox(&mut self.show_sweep_line, "Sweep line");
+                            ui.horizontal(|ui| {
+                                ui.label("At the right edge");
+                                egui::ComboBox::from_id_source("sweep_boundary")
+                                    .selected_text(self.sweep_boundary.label())
+                                    .show_ui(ui, |ui| {
+                                        for boundary in SweepBoundary::ALL {
+                                            ui.selectable_value(
+                                                &mut self.sweep_boundary,
+                                                boundary,
+                                                boundary.label(),
+                                            );
+                                        }
+                                    });
+                            })
+                            .response
+                            .on_hover_text("Overwrite: the new sweep replaces the old one as it goes. Clear: the old sweep disappears when the sweep wraps");
+                            ui.checkbox(&mut self.baseline_on_wrap, "Recompute baselines on wrap")
+                                .on_hover_text("Off: stacked traces keep their baselines instead of jumping at every wrap");
+                            ui.checkbox(&mut self.lane_grid, "Lane gridlines")
+                                .on_hover_text("Faint gridlines, the zero line and ticks at a round value in every stacked lane");
+                            ui.horizontal(|ui| {
+                                let mut every = !self.auto_x_grid;
+                                ui.checkbox(&mut every, "Time gridlines every")
+                                    .on_hover_text("Off: a spacing that fits the time window. Not used with the recent-emphasis axis");
+                                self.auto_x_grid = !every;
+                                ui.add_enabled(
+                                    every,
+                                    egui::DragValue::new(&mut self.x_grid_spacing)
+                                        .range(X_GRID_SPACING_RANGE)
+                                        .speed(0.01)
+                                        .suffix(" s"),
+                                );
+                            });
+                            ui.horizontal(|ui| {
+                                ui.checkbox(&mut self.grid_in_theme_color, "Gridlines in theme color")
+                                    .on_hover_text("Off: gridlines in the color and opacity picked here");
+                                ui.add_enabled_ui(!self.grid_in_theme_color, |ui| {
+                                    ui.color_edit_button_srgba(&mut self.grid_color);
+                                });
+                            });
+                            ui.checkbox(&mut self.crosshair, "Crosshair readout")
+                                .on_hover_text("A vertical line under the pointer, with the values of all shown channels at that time");
+                            ui.checkbox(&mut self.show_scale_bar, "Scale bar")
+                                .on_hover_text("A calibration bar in the lower right corner, while all stacked traces share one scale");
+                            let previous_window = self.time_window_seconds;
+                            ui.horizontal(|ui| {
                             egui::ComboBox::from_id_source("time_window")
                                 .selected_text(format!("{} seconds", self.time_window_seconds))
                                 .show_ui(ui, |ui| {
-                                    ui.selectable_value(
-                                        &mut self.time_window_seconds,
-                                        1.0,
-                                        "1 second",
-                                    );
-                                    ui.selectable_value(
-                                        &mut self.time_window_seconds,
-                                        2.0,
-                                        "2 seconds",
-                                    );
-                                    ui.selectable_value(
-                                        &mut self.time_window_seconds,
-                                        5.0,
-                                        "5 seconds",
-                                    );
-                                    ui.selectable_value(
-                                        &mut self.time_window_seconds,
-                                        10.0,
-                                        "10 seconds",
-                                    );
+                                    for preset in TIME_WINDOW_PRESETS {
+                                        ui.selectable_value(
+                                            &mut self.time_window_seconds,
+                                            preset,
+                                            if preset == 1.0 {
+                                                "1 second".to_string()
+                                            } else {
+                                                format!("{} seconds", preset)
+                                            },
+                                        );
+                                    }
                                 });
+                            ui.add(
+                                egui::DragValue::new(&mut self.time_window_seconds)
+                                    .range(TIME_WINDOW_RANGE)
+                                    .speed(0.05)
+                                    .max_decimals(2)
+                                    .suffix(" s"),
+                            );
+                            });
+                            ui.checkbox(&mut self.adapt_to_rate, "Adapt to the sample rate")
+                                .on_hover_text("On connect, lengthen the window for slow streams, and shorten and downsample it for fast ones");
+                            if self.time_window_seconds != previous_window {
+                                // the plot keeps its x-range otherwise
+                                self.snap_back_to_live = true;
+                            }
+                            ui.horizontal(|ui| {
+                                ui.selectable_value(
+                                    &mut self.time_axis_scale,
+                                    TimeAxisScale::Linear,
+                                    "Linear",
+                                );
+                                ui.selectable_value(
+                                    &mut self.time_axis_scale,
+                                    TimeAxisScale::RecentEmphasis,
+                                    "Recent emphasis",
+                                )
+                                .on_hover_text(
+                                    "Log-spaced time axis: the newest data is at the right and gets most of the width",
+                                );
+                            });
+                            ui.horizontal(|ui| {
+                                ui.selectable_value(
+                                    &mut self.scroll_mode,
+                                    PlotScrollMode::Sweep,
+                                    "Sweep",
+                                )
+                                .on_hover_text("New data overwrites the previous sweep from the left");
+                                ui.selectable_value(
+                                    &mut self.scroll_mode,
+                                    PlotScrollMode::Scroll,
+                                    "Scroll",
+                                )
+                                .on_hover_text("The newest sample stays at the right edge");
+                            });
+                            ui.checkbox(&mut self.show_wall_clock, "Wall-clock timestamps")
+                                .on_hover_text("Show timestamps as local time of day instead of LSL clock seconds");
+                            ui.horizontal(|ui| {
+                                ui.label("Timestamp decimals:");
+                                ui.add(egui::DragValue::new(&mut self.timestamp_decimals).range(0..=6))
+                                    .on_hover_text("6 shows microseconds");
+                                ui.label("Durations:");
+                                egui::ComboBox::from_id_source("duration_style")
+                                    .selected_text(self.duration_style.label())
+                                    .show_ui(ui, |ui| {
+                                        for style in DurationStyle::ALL {
+                                            ui.selectable_value(
+                                                &mut self.duration_style,
+                                                style,
+                                                style.label(),
+                                            );
+                                        }
+                                    });
+                            });
+                        });
+
+                        // Axis titles
+                        ui.group(|ui| {
+                            ui.horizontal(|ui| {
+                                ui.checkbox(&mut self.show_y_axis_title, "Y-axis title");
+                                ui.add_enabled(
+                                    self.show_y_axis_title,
+                                    egui::TextEdit::singleline(&mut self.y_axis_title)
+                                        .desired_width(100.0),
+                                );
+                            });
+                        });
+
+                        // Filtering of incoming samples
+                        ui.group(|ui| {
+                            if self.irregular {
+                                ui.label(
+                                    egui::RichText::new("Filters need a regular sample rate")
+                                        .small()
+                                        .weak(),
+                                );
+                                ui.disable();
+                            }
+                            let previous = self.filter_settings;
+                            let nyquist = (self.stream_sample_rate() / 2.0).max(1.0);
+                            ui.checkbox(&mut self.filter_settings.bandpass.enabled, "Bandpass filter");
+                            ui.add_enabled(
+                                self.filter_settings.bandpass.enabled,
+                                egui::Slider::new(&mut self.filter_settings.bandpass.low_cut, 0.01..=nyquist)
+                                    .logarithmic(true)
+                                    .text("Low cut (Hz)"),
+                            );
+                            ui.add_enabled(
+                                self.filter_settings.bandpass.enabled,
+                                egui::Slider::new(&mut self.filter_settings.bandpass.high_cut, 0.01..=nyquist)
+                                    .logarithmic(true)
+                                    .text("High cut (Hz)"),
+                            );
+                            if self.filter_settings.bandpass.enabled
+                                && let Some(problem) =
+                                    self.filter_settings.bandpass.problem(self.stream_sample_rate())
+                                {
+                                    ui.colored_label(
+                                        egui::Color32::from_rgb(255, 165, 0),
+                                        format!("{}; filter disabled", problem),
+                                    );
+                                }
+                            ui.horizontal(|ui| {
+                                ui.label("Notch");
+                                egui::ComboBox::from_id_source("notch")
+                                    .selected_text(self.filter_settings.notch.label())
+                                    .show_ui(ui, |ui| {
+                                        for notch in NotchFrequency::ALL {
+                                            ui.selectable_value(
+                                                &mut self.filter_settings.notch,
+                                                notch,
+                                                notch.label(),
+                                            );
+                                        }
+                                    });
+                            });
+                            if let Some(problem) =
+                                self.filter_settings.notch.problem(self.stream_sample_rate())
+                            {
+                                ui.colored_label(
+                                    egui::Color32::from_rgb(255, 165, 0),
+                                    format!("{}; notch disabled", problem),
+                                );
+                            }
+                            ui.label(
+                                egui::RichText::new("Applies to samples as they arrive; the buffer is not refiltered.")
+                                    .small()
+                                    .weak(),
+                            );
+                            if self.filter_settings != previous {
+                                self.rebuild_filters();
+                            }
                         });
 
                         // Ad-hoc baseline correction
@@ -467,261 +7114,1735 @@ impl eframe::App for LslViewer {
                             if ui.button("Correct Now").clicked() {
                                 self.baseline_correct();
                             }
+                            let previous = self.filter_settings.ac_coupling;
+                            ui.horizontal(|ui| {
+                                ui.checkbox(&mut self.filter_settings.ac_coupling.enabled, "AC coupling")
+                                    .on_hover_text("Continuously remove the offset and slow drift of every channel, so it stays centered on its row");
+                                ui.add_enabled(
+                                    self.filter_settings.ac_coupling.enabled,
+                                    egui::DragValue::new(&mut self.filter_settings.ac_coupling.cutoff)
+                                        .range(0.01..=10.0)
+                                        .speed(0.01)
+                                        .suffix(" Hz"),
+                                );
+                            });
+                            if self.filter_settings.ac_coupling.enabled
+                                && self.stream_sample_rate() <= 0.0
+                            {
+                                ui.colored_label(
+                                    egui::Color32::from_rgb(255, 165, 0),
+                                    "Filtering needs a regular sample rate; AC coupling disabled",
+                                );
+                            }
+                            if self.filter_settings.ac_coupling != previous {
+                                // the filter already centers the channels, an old baseline would shift them off
+                                if self.filter_settings.ac_coupling.enabled {
+                                    self.channel_baselines.fill(0.0);
+                                }
+                                self.rebuild_filters();
+                            }
                         });
 
-                        // Allow resampling for plotting using an integer divsior (dropdown)
+                        // Threshold alerts
                         ui.group(|ui| {
-                            egui::ComboBox::from_id_source("resample")
-                                .selected_text(if self.downsample_factor == 1 {
-                                    "No Resampling".to_string()
-                                } else {
-                                    format!("{}x Resampling", self.downsample_factor)
+                            ui.horizontal(|ui| {
+                                ui.checkbox(&mut self.alerts_enabled, "Alert above");
+                                ui.add_enabled(
+                                    self.alerts_enabled,
+                                    egui::DragValue::new(&mut self.alert_threshold)
+                                        .range(0.0..=f64::MAX)
+                                        .speed(1.0)
+                                        .suffix(format!(" {}", self.display_unit)),
+                                )
+                                .on_hover_text("Deflection from the channel's baseline, checked on every sample of every channel");
+                            });
+                            ui.horizontal(|ui| {
+                                ui.checkbox(&mut self.alert_latch, "Latch")
+                                    .on_hover_text("Keep alerts until cleared, so a short excursion isn't missed");
+                                #[cfg(feature = "audio")]
+                                ui.checkbox(&mut self.alert_beep, "Beep");
+                                if ui.button("Clear").clicked() {
+                                    self.channel_alerts.fill(None);
+                                }
+                            });
+                        });
+
+                        // Clipping detection
+                        ui.group(|ui| {
+                            ui.checkbox(&mut self.clip_detection, "Detect clipping")
+                                .on_hover_text("Mark stretches where a channel sits at the amplifier's rails");
+                            ui.add_enabled_ui(self.clip_detection, |ui| {
+                                ui.horizontal(|ui| {
+                                    ui.label("Rails:");
+                                    ui.add(
+                                        egui::DragValue::new(&mut self.clip_min)
+                                            .speed(1.0)
+                                            .suffix(format!(" {}", self.display_unit)),
+                                    );
+                                    ui.label("to");
+                                    ui.add(
+                                        egui::DragValue::new(&mut self.clip_max)
+                                            .speed(1.0)
+                                            .suffix(format!(" {}", self.display_unit)),
+                                    );
+                                });
+                                ui.horizontal(|ui| {
+                                    ui.label("For at least");
+                                    ui.add(egui::DragValue::new(&mut self.clip_run).range(1..=1000));
+                                    ui.label("samples");
+                                });
+                            });
+                            if self.clip_min >= self.clip_max {
+                                ui.colored_label(
+                                    egui::Color32::from_rgb(255, 165, 0),
+                                    "The lower rail must be below the upper one",
+                                );
+                            }
+                        });
+
+                        // Peak detection
+                        ui.group(|ui| {
+                            let previous = (self.peak_channel, self.peak_settings);
+                            ui.horizontal(|ui| {
+                                ui.label("Detect peaks on");
+                                egui::ComboBox::from_id_source("peak_channel")
+                                    .selected_text(
+                                        self.peak_channel
+                                            .and_then(|ch| self.channel_names.get(ch))
+                                            .map_or("None", |name| name.as_str()),
+                                    )
+                                    .show_ui(ui, |ui| {
+                                        ui.selectable_value(&mut self.peak_channel, None, "None");
+                                        for (i, name) in self.channel_names.iter().enumerate() {
+                                            ui.selectable_value(&mut self.peak_channel, Some(i), name);
+                                        }
+                                    });
+                            });
+                            ui.add_enabled_ui(self.peak_channel.is_some(), |ui| {
+                                ui.horizontal(|ui| {
+                                    ui.label("Above");
+                                    ui.add(
+                                        egui::DragValue::new(&mut self.peak_settings.threshold)
+                                            .range(0.0..=f64::MAX)
+                                            .speed(1.0)
+                                            .suffix(format!(" {}", self.display_unit)),
+                                    )
+                                    .on_hover_text("Deflection from the channel's baseline");
+                                });
+                                ui.horizontal(|ui| {
+                                    ui.label("At most one every");
+                                    ui.add(
+                                        egui::DragValue::new(&mut self.peak_settings.refractory)
+                                            .range(0.0..=10.0)
+                                            .speed(0.01)
+                                            .suffix(" s"),
+                                    );
+                                });
+                                ui.checkbox(&mut self.peak_settings.troughs, "Troughs")
+                                    .on_hover_text("Look for dips below minus the threshold instead");
+                            });
+                            if (self.peak_channel, self.peak_settings) != previous {
+                                self.reset_peaks();
+                            }
+                        });
+
+                        // Allow resampling for plotting using an integer divisor
+                        ui.group(|ui| {
+                            if self.irregular {
+                                ui.label(
+                                    egui::RichText::new("Irregular streams are drawn without decimation")
+                                        .small()
+                                        .weak(),
+                                );
+                                ui.disable();
+                            }
+                            ui.horizontal(|ui| {
+                                ui.label("Downsample");
+                                ui.add(
+                                    egui::DragValue::new(&mut self.downsample_factor)
+                                        .range(1..=MAX_DOWN_SAMPLE_FACTOR)
+                                        .prefix("÷"),
+                                )
+                                .on_hover_text("Draw one point per this many samples; 1 draws every sample");
+                                let sample_rate = self.stream_sample_rate();
+                                if sample_rate > 0.0 && self.downsample_factor > 1 {
+                                    ui.label(
+                                        egui::RichText::new(format!(
+                                            "{} ÷ {} = {}",
+                                            format_rate(sample_rate),
+                                            self.downsample_factor,
+                                            format_rate(sample_rate / self.downsample_factor as f64)
+                                        ))
+                                        .small()
+                                        .weak(),
+                                    );
+                                }
+                            });
+                            ui.add_enabled_ui(self.downsample_factor > 1, |ui| {
+                                egui::ComboBox::from_id_source("decimation")
+                                    .selected_text(self.decimation.label())
+                                    .show_ui(ui, |ui| {
+                                        for method in DecimationMethod::ALL {
+                                            ui.selectable_value(
+                                                &mut self.decimation,
+                                                method,
+                                                method.label(),
+                                            );
+                                        }
+                                    })
+                                    .response
+                                    .on_hover_text(
+                                        "How each group of samples becomes plot points; min/max keeps short spikes visible",
+                                    );
+                                ui.add_enabled(
+                                    self.decimation != DecimationMethod::MinMax,
+                                    egui::Checkbox::new(&mut self.anti_alias, "Anti-alias"),
+                                )
+                                .on_hover_text("Low-pass the displayed traces at half the downsampled rate before decimating, so faster activity doesn't fold into slow waves. The buffered data and the band-pass are unaffected");
+                            });
+                            ui.add_enabled(!self.irregular, egui::Checkbox::new(&mut self.smooth_traces, "Smooth sparse traces"))
+                                .on_hover_text("Draw curves through the samples instead of straight segments, e.g. for respiration; the data itself is unchanged");
+                            ui.checkbox(&mut self.pixel_decimation, "Min/max per pixel when dense")
+                                .on_hover_text(format!(
+                                    "With more than {} samples per pixel column, draw only each column's extremes; faster, and looks the same",
+                                    PIXEL_DECIMATION_THRESHOLD
+                                ));
+                        });
+
+                        // Moving RMS envelope, e.g. for EMG
+                        ui.group(|ui| {
+                            if self.irregular {
+                                ui.label(
+                                    egui::RichText::new("Irregular streams have no rate to base an envelope on")
+                                        .small()
+                                        .weak(),
+                                );
+                                ui.disable();
+                            }
+                            ui.horizontal(|ui| {
+                                egui::ComboBox::from_id_source("envelope")
+                                    .selected_text(self.envelope_mode.label())
+                                    .show_ui(ui, |ui| {
+                                        for mode in EnvelopeMode::ALL {
+                                            ui.selectable_value(
+                                                &mut self.envelope_mode,
+                                                mode,
+                                                mode.label(),
+                                            );
+                                        }
+                                    });
+                                ui.add_enabled(
+                                    self.envelope_mode != EnvelopeMode::Off,
+                                    egui::DragValue::new(&mut self.envelope_window_ms)
+                                        .range(1.0..=5000.0)
+                                        .speed(1.0)
+                                        .suffix(" ms"),
+                                )
+                                .on_hover_text("Window of the moving RMS");
+                            });
+                        });
+
+                        // Boxcar moving average over the raw trace, for trends in noisy slow signals
+                        ui.group(|ui| {
+                            ui.horizontal(|ui| {
+                                ui.checkbox(&mut self.moving_average, "Moving average")
+                                    .on_hover_text("A smoothed trace over the raw one; unlike the filters it leaves the data and recordings alone");
+                                ui.add_enabled(
+                                    self.moving_average,
+                                    egui::DragValue::new(&mut self.moving_average_window)
+                                        .range(MOVING_AVERAGE_WINDOW_RANGE)
+                                        .suffix(" samples"),
+                                );
+                            });
+                        });
+
+                        // Re-reference to the common average or to a single channel,
+                        // e.g. linked ears; only one of them at a time
+                        ui.group(|ui| {
+                            ui.horizontal(|ui| {
+                                ui.label("Reference");
+                                egui::ComboBox::from_id_source("re_reference")
+                                    .selected_text(match self.reference_channel {
+                                        _ if self.common_average => "Average",
+                                        Some(ref_idx) => self.channel_names[ref_idx].as_str(),
+                                        None => "None",
+                                    })
+                                    .show_ui(ui, |ui| {
+                                        let unreferenced =
+                                            !self.common_average && self.reference_channel.is_none();
+                                        if ui.selectable_label(unreferenced, "None").clicked() {
+                                            self.common_average = false;
+                                            self.reference_channel = None;
+                                        }
+                                        if ui
+                                            .selectable_label(self.common_average, "Average")
+                                            .on_hover_text("Subtract the mean of the selected good channels from every channel, for samples from now on")
+                                            .clicked()
+                                        {
+                                            self.common_average = true;
+                                            self.reference_channel = None;
+                                        }
+                                        for (i, name) in self.channel_names.iter().enumerate() {
+                                            let selected = !self.common_average
+                                                && self.reference_channel == Some(i);
+                                            if ui.selectable_label(selected, name).clicked() {
+                                                self.common_average = false;
+                                                self.reference_channel = Some(i);
+                                            }
+                                        }
+                                    });
+                            })
+                            .response
+                            .on_hover_text("A channel reference is subtracted from the whole buffer as it is drawn, so it reads flat itself");
+                        });
+
+                        // Virtual channels computed from the stream's channels
+                        ui.group(|ui| {
+                            ui.label("Derived Channels");
+                            let stream_channel_count = self.stream_channel_count();
+                            let stream_names = self.channel_names[..stream_channel_count].to_vec();
+                            self.difference_pick.0 = self.difference_pick.0.min(stream_channel_count.saturating_sub(1));
+                            self.difference_pick.1 = self.difference_pick.1.min(stream_channel_count.saturating_sub(1));
+                            ui.horizontal(|ui| {
+                                for (id, pick) in [
+                                    ("difference_a", &mut self.difference_pick.0),
+                                    ("difference_b", &mut self.difference_pick.1),
+                                ] {
+                                    egui::ComboBox::from_id_source(id)
+                                        .selected_text(stream_names.get(*pick).cloned().unwrap_or_default())
+                                        .show_ui(ui, |ui| {
+                                            for (i, name) in stream_names.iter().enumerate() {
+                                                ui.selectable_value(pick, i, name);
+                                            }
+                                        });
+                                }
+                                let (a, b) = self.difference_pick;
+                                if ui
+                                    .add_enabled(a != b && stream_channel_count > 1, egui::Button::new("Add difference"))
+                                    .on_hover_text("A bipolar derivation: the first channel minus the second")
+                                    .clicked()
+                                {
+                                    self.add_derived_channel(DerivedChannel::Difference(a, b));
+                                }
+                            });
+                            let selected: Vec<usize> = (0..stream_channel_count)
+                                .filter(|&ch| self.selected_channels[ch])
+                                .collect();
+                            if ui
+                                .add_enabled(!selected.is_empty(), egui::Button::new("Add mean of selected"))
+                                .on_hover_text("The average of the channels that are ticked now")
+                                .clicked()
+                            {
+                                self.add_derived_channel(DerivedChannel::Mean(selected));
+                            }
+                            let mut removed = None;
+                            for i in 0..self.derived_channels.len() {
+                                ui.horizontal(|ui| {
+                                    ui.label(&self.channel_names[stream_channel_count + i]);
+                                    if ui.small_button("✖").on_hover_text("Remove").clicked() {
+                                        removed = Some(i);
+                                    }
+                                });
+                            }
+                            if let Some(i) = removed {
+                                self.derived_channels.remove(i);
+                                self.set_derived_channels(stream_channel_count);
+                            }
+                        });
+
+                        // Listen to one channel through the speakers
+                        #[cfg(feature = "audio")]
+                        self.audio_ui(ui);
+
+                        // What to drop when the display can't keep up
+                        ui.group(|ui| {
+                            ui.label("Display Load");
+                            egui::ComboBox::from_id_source("drop_policy")
+                                .selected_text(self.drop_policy.label())
+                                .show_ui(ui, |ui| {
+                                    for policy in SampleDropPolicy::ALL {
+                                        ui.selectable_value(
+                                            &mut self.drop_policy,
+                                            policy,
+                                            policy.label(),
+                                        )
+                                        .on_hover_text(policy.description());
+                                    }
                                 })
+                                .response
+                                .on_hover_text(self.drop_policy.description());
+                            ui.horizontal(|ui| {
+                                ui.label("Max pending samples per frame:");
+                                ui.add(
+                                    egui::DragValue::new(&mut self.max_display_backlog)
+                                        .range(100..=1_000_000),
+                                );
+                            });
+                            ui.horizontal(|ui| {
+                                ui.label("Plot update rate:");
+                                ui.add(
+                                    egui::DragValue::new(&mut self.display_update_hz)
+                                        .range(1.0..=120.0)
+                                        .suffix(" Hz"),
+                                );
+                            })
+                            .response
+                            .on_hover_text(
+                                "How often the traces are rebuilt; the window still repaints smoothly in between",
+                            );
+                            ui.horizontal(|ui| {
+                                ui.checkbox(&mut self.custom_buffer_limit, "Max samples per channel:");
+                                if self.custom_buffer_limit {
+                                    ui.add(
+                                        egui::DragValue::new(&mut self.max_buffer_samples)
+                                            .range(1000..=100_000_000),
+                                    );
+                                } else {
+                                    ui.label(format!("auto ({})", self.buffer_sample_limit()));
+                                }
+                            })
+                            .response
+                            .on_hover_text(format!(
+                                "Oldest samples are dropped beyond this, even inside the time window. Auto is {} time windows at the nominal rate",
+                                AUTO_BUFFER_WINDOWS
+                            ));
+                            ui.horizontal(|ui| {
+                                ui.label("Keep history:");
+                                ui.add(
+                                    egui::DragValue::new(&mut self.history_seconds)
+                                        .range(HISTORY_RANGE)
+                                        .speed(1.0)
+                                        .suffix(" s"),
+                                );
+                            })
+                            .response
+                            .on_hover_text(
+                                "Buffer more than the time window; an overview strip below the plot shows all of it and moves the frozen plot through it. 0 keeps just the window",
+                            );
+                            ui.horizontal(|ui| {
+                                ui.label("Max messages per frame:");
+                                ui.add(
+                                    egui::DragValue::new(&mut self.max_responses_per_frame)
+                                        .range(100..=1_000_000),
+                                );
+                            });
+                            if self.carried_over_responses > 0 {
+                                ui.colored_label(
+                                    egui::Color32::from_rgb(255, 165, 0),
+                                    format!(
+                                        "Frame cap reached, {} messages carried over",
+                                        self.carried_over_responses
+                                    ),
+                                );
+                            }
+                            ui.label(
+                                egui::RichText::new(
+                                    "Only the display is thinned out; acquisition still receives every sample.",
+                                )
+                                .small()
+                                .weak(),
+                            );
+                            if self.dropped_display_samples > 0 {
+                                ui.label(format!(
+                                    "{} samples dropped for display",
+                                    self.dropped_display_samples
+                                ));
+                            }
+                        });
+
+                        // Handling of non-monotonic timestamps
+                        ui.group(|ui| {
+                            ui.label("Timestamp Regressions");
+                            egui::ComboBox::from_id_source("timestamp_regression_policy")
+                                .selected_text(self.timestamp_regression_policy.label())
                                 .show_ui(ui, |ui| {
-                                    ui.selectable_value(&mut self.downsample_factor, 1, "Disable");
-                                    ui.selectable_value(&mut self.downsample_factor, 2, "2x");
-                                    ui.selectable_value(&mut self.downsample_factor, 3, "3x");
-                                    ui.selectable_value(&mut self.downsample_factor, 4, "4x");
-                                    ui.selectable_value(&mut self.downsample_factor, 5, "5x");
-                                    ui.selectable_value(&mut self.downsample_factor, 10, "10x");
+                                    for policy in TimestampRegressionPolicy::ALL {
+                                        ui.selectable_value(
+                                            &mut self.timestamp_regression_policy,
+                                            policy,
+                                            policy.label(),
+                                        );
+                                    }
                                 });
+                            if self.timestamp_regressions > 0 {
+                                ui.colored_label(
+                                    egui::Color32::from_rgb(255, 165, 0),
+                                    format!(
+                                        "{} sample(s) out of order",
+                                        self.timestamp_regressions
+                                    ),
+                                );
+                            }
                         });
 
-                        // Allow re-referencing to a specific channel
+                        // Handling of samples whose width doesn't match the stream
                         ui.group(|ui| {
-                            egui::ComboBox::from_id_source("re_reference")
-                                .selected_text(if let Some(ref_idx) = self.reference_channel {
-                                    format!("Referenced to {}", self.channel_names[ref_idx])
-                                } else {
-                                    "Unreferenced".to_string()
-                                })
+                            ui.label("Channel Count Changes");
+                            egui::ComboBox::from_id_source("channel_count_policy")
+                                .selected_text(self.channel_count_policy.label())
                                 .show_ui(ui, |ui| {
-                                    ui.selectable_value(&mut self.reference_channel, None, "None");
-                                    for (i, name) in self.channel_names.iter().enumerate() {
+                                    for policy in [
+                                        ChannelCountChangePolicy::Resize,
+                                        ChannelCountChangePolicy::Reject,
+                                    ] {
                                         ui.selectable_value(
-                                            &mut self.reference_channel,
-                                            Some(i),
-                                            name,
+                                            &mut self.channel_count_policy,
+                                            policy,
+                                            policy.label(),
                                         );
                                     }
                                 });
+                            if self.channel_count_changes > 0 {
+                                ui.colored_label(
+                                    egui::Color32::from_rgb(255, 165, 0),
+                                    format!(
+                                        "{} sample(s) with an unexpected channel count",
+                                        self.channel_count_changes
+                                    ),
+                                );
+                            }
+                        });
+
+                        // Event markers from a second stream
+                        ui.group(|ui| {
+                            ui.label("Markers");
+                            if let Some(name) = &self.marker_stream {
+                                let mut disconnect = false;
+                                ui.horizontal(|ui| {
+                                    ui.label(format!("{} ({} in view)", name, self.marker_buffer.len()));
+                                    disconnect = ui.small_button("✖").clicked();
+                                });
+                                if disconnect {
+                                    self.send_command(LslCommand::DisconnectMarkers);
+                                }
+                                ui.horizontal(|ui| {
+                                    ui.label("Labels:");
+                                    egui::ComboBox::from_id_source("marker_labels")
+                                        .selected_text(self.marker_labels.label())
+                                        .show_ui(ui, |ui| {
+                                            for labels in MarkerLabels::ALL {
+                                                ui.selectable_value(
+                                                    &mut self.marker_labels,
+                                                    labels,
+                                                    labels.label(),
+                                                );
+                                            }
+                                        });
+                                });
+                                if ui
+                                    .checkbox(&mut self.collapse_markers, "Collapse repeats")
+                                    .on_hover_text("Identical markers close together share one label, e.g. \"stim ×3\"")
+                                    .changed()
+                                {
+                                    self.last_geometry_build = None;
+                                }
+                            } else {
+                                let mut chosen = None;
+                                egui::ComboBox::from_id_source("marker_stream")
+                                    .selected_text("Show markers from…")
+                                    .show_ui(ui, |ui| {
+                                        for stream in self.available_streams.iter() {
+                                            if stream.stream_type.eq_ignore_ascii_case("markers")
+                                                && ui.selectable_label(false, &stream.name).clicked()
+                                            {
+                                                chosen = Some(stream.key().to_string());
+                                            }
+                                        }
+                                    });
+                                if let Some(key) = chosen {
+                                    self.send_command(LslCommand::ConnectMarkers(key));
+                                }
+                            }
+                        });
+
+                        // Notes dropped at the newest sample by hand
+                        ui.group(|ui| {
+                            ui.label("Annotations");
+                            ui.horizontal(|ui| {
+                                let note = ui.add(
+                                    egui::TextEdit::singleline(&mut self.annotation_note)
+                                        .hint_text("Note (optional)")
+                                        .desired_width(120.0),
+                                );
+                                let entered =
+                                    note.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                                let clicked = ui
+                                    .add_enabled(
+                                        !self.timestamp_buffer.is_empty(),
+                                        egui::Button::new("✚ Annotate"),
+                                    )
+                                    .on_hover_text("Mark the newest sample with the note (A)")
+                                    .clicked();
+                                if clicked || (entered && !self.timestamp_buffer.is_empty()) {
+                                    self.add_annotation();
+                                }
+                            });
+                            if !self.annotations.is_empty() {
+                                ui.label(
+                                    egui::RichText::new(format!(
+                                        "{} in view; click one in the plot to edit it",
+                                        self.annotations.len()
+                                    ))
+                                    .small()
+                                    .weak(),
+                                );
+                            }
+                        });
+
+                        // Record everything that arrives to a CSV file
+                        ui.group(|ui| {
+                            ui.label("Recording");
+                            if self.recording.is_some() {
+                                if ui.button("⏹ Stop Recording").clicked() {
+                                    self.send_command(LslCommand::StopRecording);
+                                }
+                            } else {
+                                ui.horizontal(|ui| {
+                                    ui.label("File name:");
+                                    ui.text_edit_singleline(&mut self.recording_template)
+                                        .on_hover_text("{stream}, {date} and {index} are filled in with the stream's name, today's date and the first number not yet used in the folder");
+                                });
+                                if ui
+                                    .button("⏺ Record")
+                                    .on_hover_text("Write every sample of all channels to a CSV file")
+                                    .clicked()
+                                {
+                                    let mut dialog = rfd::FileDialog::new()
+                                        .add_filter("CSV", &["csv"])
+                                        .set_file_name(self.next_recording_file_name());
+                                    if let Some(directory) = &self.recording_directory {
+                                        dialog = dialog.set_directory(directory);
+                                    }
+                                    if let Some(path) = dialog.save_file() {
+                                        self.send_command(LslCommand::StartRecording(path));
+                                    }
+                                }
+                            }
+                        });
+
+                        // Write the buffered data to disk
+                        ui.group(|ui| {
+                            ui.label("Export");
+                            ui.horizontal(|ui| {
+                                ui.label("Downsample");
+                                ui.add(
+                                    egui::DragValue::new(&mut self.export_downsample_factor)
+                                        .range(1..=1000)
+                                        .prefix("÷"),
+                                );
+                                egui::ComboBox::from_id_source("export_aggregation")
+                                    .selected_text(self.export_aggregation.label())
+                                    .show_ui(ui, |ui| {
+                                        for aggregation in Aggregation::ALL {
+                                            ui.selectable_value(
+                                                &mut self.export_aggregation,
+                                                aggregation,
+                                                aggregation.label(),
+                                            );
+                                        }
+                                    });
+                            })
+                            .response
+                            .on_hover_text(
+                                "Each group of samples is written as one row, using the chosen value; independent of the display resampling",
+                            );
+                            if ui
+                                .add_enabled(self.is_connected, egui::Button::new("Export XDF…"))
+                                .on_hover_text("Write the buffered samples of all channels to an XDF file")
+                                .clicked()
+                                && let Some(path) = rfd::FileDialog::new()
+                                    .add_filter("XDF", &["xdf"])
+                                    .set_file_name("buffer.xdf")
+                                    .save_file()
+                                {
+                                    self.set_status(match self.export_xdf(&path) {
+                                        Ok(()) => format!("Exported buffer to {}", path.display()),
+                                        Err(e) => format!("Error: failed to write {}: {}", path.display(), e),
+                                    });
+                                }
+                            if let Some(job) = &self.export_job {
+                                ui.add(
+                                    egui::ProgressBar::new(
+                                        job.written as f32 / job.total.max(1) as f32,
+                                    )
+                                    .text(format!("{} / {} files", job.written, job.total)),
+                                );
+                            } else if ui
+                                .button("Export per-channel…")
+                                .on_hover_text(
+                                    "Write one timestamp,value CSV per selected channel, named by channel label, and the annotations to annotations.csv",
+                                )
+                                .clicked()
+                                && let Some(dir) = rfd::FileDialog::new().pick_folder() {
+                                    self.start_per_channel_export(dir);
+                                }
                         });
 
                         // Stream information
                         ui.group(|ui| {
                             ui.label("Connected Stream Info:");
-                            if let Some(index) = self.selected_stream_index {
-                                if let Some(stream) = self.available_streams.get(index) {
+                            if self.selected_stream.is_some() {
+                                if let Some(stream) = &self.selected_stream {
                                     ui.label(format!("Name: {}", stream.name));
                                     ui.label(format!("Channels: {}", stream.channel_count));
-                                    ui.label(format!("Sample Rate: {:.2} Hz", stream.sample_rate));
+                                    let format = ui.label(format!("Format: {}", stream.channel_format));
+                                    if stream.loses_precision() {
+                                        format.on_hover_text("Shown with f32 precision, about 7 significant digits");
+                                    }
+                                    if stream.sample_rate > 0.0 {
+                                        ui.label(format!("Sample Rate: {:.2} Hz nominal", stream.sample_rate));
+                                    } else {
+                                        ui.label("Sample Rate: irregular");
+                                    }
+                                    if let Some(effective) = self.effective_sample_rate() {
+                                        let text = format!("Effective: {:.2} Hz", effective);
+                                        if stream.sample_rate > 0.0
+                                            && (effective / stream.sample_rate - 1.0).abs()
+                                                > RATE_DEVIATION_WARNING
+                                        {
+                                            ui.colored_label(egui::Color32::from_rgb(255, 165, 0), text)
+                                                .on_hover_text(format!(
+                                                    "More than {:.0}% off the nominal rate",
+                                                    RATE_DEVIATION_WARNING * 100.0
+                                                ));
+                                        } else {
+                                            ui.label(text);
+                                        }
+                                    }
                                 }
-                            } else {
-                                ui.label("No stream selected");
-                            }
-                        });
-                    }
-                });
-            });
-
-        egui::CentralPanel::default().show(ctx, |ui| {
-            ui.vertical(|ui| {
-                if self.is_connected && self.channel_count > 0 {
-                    // Data visualization
-                    if !self.data_buffer.is_empty() && self.data_buffer[0].len() > 0 {
-                        let selected_channel_count =
-                            self.selected_channels.iter().filter(|&&x| x).count();
-                        let selected_channel_labels: Vec<String> = self
-                            .selected_channels
-                            .iter()
-                            .enumerate()
-                            .filter_map(|(i, &selected)| {
-                                if selected {
-                                    Some(self.channel_names[i].clone())
+                                if self.metadata_available {
+                                    ui.horizontal(|ui| {
+                                        ui.label("Metadata: obtained");
+                                        if self.stream_xml.is_some() && ui.small_button("Show…").clicked() {
+                                            self.show_stream_xml = true;
+                                        }
+                                    });
                                 } else {
-                                    None
-                                }
-                            })
-                            .collect();
-
-                        let y_formatter =
-                            |grid_mark: GridMark, _range: &std::ops::RangeInclusive<f64>| {
-                                let index = (-1.0 * grid_mark.value) as usize;
-
-                                if index >= selected_channel_labels.len() {
-                                    return "??".to_string();
+                                    ui.colored_label(
+                                        egui::Color32::from_rgb(255, 165, 0),
+                                        "Metadata: unavailable (generated channel names)",
+                                    );
                                 }
-                                selected_channel_labels[index].to_string()
-                            };
-
-                        let y_grid_spacer = |_grid_input: GridInput| {
-                            (0..selected_channel_count)
-                                .map(|i| GridMark {
-                                    value: -1.0 * (i as f64),
-                                    step_size: 1.0,
-                                })
-                                .collect::<Vec<_>>()
-                        };
-
-                        let plot = Plot::new("lsl_plot")
-                            .default_x_bounds(0.0, self.time_window_seconds)
-                            .default_y_bounds((selected_channel_count as f64 * -1.0) + 0.5, 0.5)
-                            .allow_zoom(false)
-                            .allow_drag(false)
-                            .allow_scroll(false)
-                            .x_axis_label("Time (seconds)")
-                            .y_axis_label("Value")
-                            .y_axis_formatter(y_formatter)
-                            .y_grid_spacer(y_grid_spacer);
-
-                        plot.show(ui, |plot_ui| {
-                            // Find the most recent timestamp to use as reference
-                            let latest_timestamp =
-                                self.timestamp_buffer.back().cloned().unwrap_or(0.0);
-
-                            // decide on the current time window to be shown (always n * TIME_WINDOW_SECONDS, where n is an integer)
-                            let t0 =
-                                latest_timestamp - (latest_timestamp % self.time_window_seconds);
-
-                            // if we're re-referencing, prepare the reference channel
-                            let ref_channel: Option<(Vec<f32>, f64)> =
-                                if let Some(ref_idx) = self.reference_channel {
-                                    if ref_idx < self.data_buffer.len() {
-                                        Some((
-                                            self.data_buffer[ref_idx].iter().cloned().collect(),
-                                            self.channel_baselines[ref_idx],
-                                        ))
-                                    } else {
-                                        None
-                                    }
+                                let applied = if self.applied_postprocessing.is_empty() {
+                                    "none".to_string()
                                 } else {
-                                    None
+                                    self.applied_postprocessing
+                                        .iter()
+                                        .map(|f| f.label())
+                                        .collect::<Vec<_>>()
+                                        .join(", ")
                                 };
-
-                            let mut plot_idx = 0;
-                            let mut t_last = 0.0;
-                            for (ch_idx, channel_data) in self.data_buffer.iter().enumerate() {
-                                if ch_idx < self.selected_channels.len()
-                                    && self.selected_channels[ch_idx]
-                                    && !channel_data.is_empty()
+                                let missing: Vec<&str> = self
+                                    .requested_postprocessing
+                                    .iter()
+                                    .filter(|f| !self.applied_postprocessing.contains(f))
+                                    .map(|f| f.label())
+                                    .collect();
+                                if missing.is_empty() {
+                                    ui.label(format!("Postprocessing: {}", applied));
+                                } else {
+                                    ui.colored_label(
+                                        egui::Color32::from_rgb(255, 165, 0),
+                                        format!("Postprocessing: {}", applied),
+                                    )
+                                    .on_hover_text(format!(
+                                        "Requested but not applied: {}",
+                                        missing.join(", ")
+                                    ));
+                                }
+                                if self
+                                    .applied_postprocessing
+                                    .contains(&PostprocessingFlag::ClockSync)
                                 {
-                                    let mut points_vec_a = Vec::new();
-                                    let mut points_vec_b = Vec::new();
-
-                                    // baseline-correct the data
-                                    let baseline = self.channel_baselines[ch_idx];
-
-                                    let n = self.downsample_factor.max(1);
-
-                                    for (i, (value, timestamp)) in channel_data
+                                    ui.checkbox(&mut self.show_source_clock, "Show source clock timestamps")
+                                        .on_hover_text("Show timestamps as the source stamped them, by taking the latest clock offset back out. \
+                                             Dejitter smoothing can't be undone, and the buffered and recorded data keep the corrected times");
+                                }
+                                if let Some(&offset) = self.time_corrections.back() {
+                                    let (min, max) = self
+                                        .time_corrections
                                         .iter()
-                                        .step_by(n)
-                                        .zip(self.timestamp_buffer.iter().step_by(n))
-                                        .enumerate()
-                                    {
-                                        // We show a rolling window of data, so that new data is drawn from left to right
-                                        let mut t = (timestamp - t0) % self.time_window_seconds;
+                                        .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), &o| {
+                                            (lo.min(o), hi.max(o))
+                                        });
+                                    ui.label(format!(
+                                        "Clock offset: {:+.3} ms (spread {:.3} ms)",
+                                        offset * 1000.0,
+                                        (max - min) * 1000.0
+                                    ))
+                                    .on_hover_text(format!(
+                                        "Added to the source's timestamps to map them to this computer's LSL clock. \
+                                         The spread over the last {} updates bounds the network round-trip jitter.",
+                                        self.time_corrections.len()
+                                    ));
+                                }
+                            } else {
+                                ui.label("No stream selected");
+                            }
+                        });
 
-                                        let v = if let Some(ref ref_data) = ref_channel {
-                                            (*value as f64 - baseline)
-                                                - (ref_data.0[i] as f64 - ref_data.1)
-                                        } else {
-                                            (*value as f64 - baseline)
-                                        };
+                        ui.collapsing("Channel Statistics", |ui| {
+                            self.statistics_ui(ui);
+                        });
+                    }
 
-                                        let v = v * self.data_scale / 10000.0;
+                    ui.collapsing("Diagnostics", |ui| {
+                        self.diagnostics_ui(ui);
+                    });
+                });
+            });
+        if self.auto_scale_continuous
+            && self.is_connected
+            && !self.frozen
+            && self
+                .last_auto_scale
+                .is_none_or(|last| last.elapsed() >= AUTO_SCALE_INTERVAL)
+        {
+            self.auto_scale();
+        }
 
-                                        let val = v + -1.0 * plot_idx as f64;
+        // The master gain drives every channel while linked
+        if self.link_scales {
+            self.channel_scales.fill(self.data_scale);
+        }
 
-                                        if t > 0.0 {
-                                            points_vec_a.push([t, val]);
-                                        } else {
-                                            t += self.time_window_seconds;
-                                            points_vec_b.push([t, val]);
-                                        }
-                                    }
+        if self.plot_popped_out {
+            let mut closed = false;
+            ctx.show_viewport_immediate(
+                egui::ViewportId::from_hash_of("plot_viewport"),
+                egui::ViewportBuilder::default()
+                    .with_title("LSL Data Viewer - Plot")
+                    .with_inner_size([1000.0, 700.0]),
+                |ctx, _class| {
+                    // its input, screenshots included, arrives in this viewport only
+                    self.handle_screenshot(ctx);
+                    self.handle_shortcuts(ctx);
+                    egui::CentralPanel::default().show(ctx, |ui| self.central_ui(ui));
+                    closed = ctx.input(|i| i.viewport().close_requested());
+                },
+            );
+            if closed {
+                self.plot_popped_out = false;
+            }
+            egui::CentralPanel::default().show(ctx, |ui| {
+                ui.label("The plot is in its own window.");
+                if ui.button("Return plot").clicked() {
+                    self.plot_popped_out = false;
+                }
+            });
+        } else {
+            egui::CentralPanel::default().show(ctx, |ui| self.central_ui(ui));
+        }
 
-                                    t_last = points_vec_a.last().map_or(0.0, |p| p[0]);
+        egui::TopBottomPanel::bottom("status_bar").show_animated(ctx, !self.presentation_mode, |ui| {
+            ui.horizontal(|ui| {
+                ui.horizontal(|ui| {
+                    // how recently data arrived, at a glance
+                    if self.is_connected && self.acquisition_paused {
+                        ui.colored_label(egui::Color32::from_rgb(255, 165, 0), "⏸ Acquisition paused");
+                    } else if self.is_connected {
+                        let age = self.last_data_instant.map(|at| at.elapsed());
+                        let (color, text) = match age {
+                            Some(age) if age <= DATA_FRESH_AGE => (
+                                egui::Color32::from_rgb(144, 238, 144),
+                                "Data flowing".to_string(),
+                            ),
+                            Some(age) if age <= DATA_STALE_AGE => (
+                                egui::Color32::from_rgb(255, 215, 0),
+                                format!("Last sample {:.1} s ago", age.as_secs_f64()),
+                            ),
+                            Some(age) => (
+                                egui::Color32::from_rgb(255, 99, 71),
+                                format!("No data for {} s", age.as_secs()),
+                            ),
+                            None => (
+                                egui::Color32::from_rgb(255, 99, 71),
+                                "No data yet".to_string(),
+                            ),
+                        };
+                        ui.colored_label(color, "●").on_hover_text(&text);
+                        if age.is_none_or(|age| age > DATA_STALE_AGE) {
+                            ui.colored_label(color, text);
+                        }
+                    }
+                    if ui
+                        .small_button("🗒")
+                        .on_hover_text("Log of status messages and connection events")
+                        .clicked()
+                    {
+                        self.show_log = !self.show_log;
+                    }
+                    ui.label("Status:");
+                    if let Some(stage) = self.connect_stage {
+                        ui.spinner();
+                        ui.label(stage.label());
+                        if ui.small_button("Cancel").clicked() {
+                            self.send_command(LslCommand::CancelConnect);
+                        }
+                    } else if let Some((name, attempt)) = &self.reconnecting {
+                        ui.spinner();
+                        ui.colored_label(
+                            egui::Color32::from_rgb(255, 165, 0),
+                            format!("Reconnecting to {} (attempt {})…", name, attempt),
+                        );
+                    } else {
+                        ui.label(&self.status_message);
+                    }
 
-                                    let points_a: PlotPoints = points_vec_a.into_iter().collect();
-                                    let points_b: PlotPoints = points_vec_b.into_iter().collect();
+                    let alerted: Vec<&str> = (0..self.channel_count)
+                        .filter(|&ch| self.alert_active(ch))
+                        .filter_map(|ch| self.channel_names.get(ch).map(|n| n.as_str()))
+                        .collect();
+                    if !alerted.is_empty() {
+                        ui.separator();
+                        ui.colored_label(
+                            egui::Color32::from_rgb(255, 99, 71),
+                            format!("⚠ Alert: {}", alerted.join(", ")),
+                        );
+                        if ui.small_button("Clear").clicked() {
+                            self.channel_alerts.fill(None);
+                        }
+                    }
 
-                                    let line_a = Line::new(format!("Channel {}", ch_idx), points_a)
-                                        .stroke(Stroke::new(1.0, self.channel_colors[ch_idx]));
-                                    let line_b = Line::new(format!("Channel {}", ch_idx), points_b)
-                                        .stroke(Stroke::new(1.0, egui::Color32::from_gray(150)));
+                    let clipped: Vec<&str> = self
+                        .clipped_channels()
+                        .into_iter()
+                        .filter_map(|ch| self.channel_names.get(ch).map(|n| n.as_str()))
+                        .collect();
+                    if !clipped.is_empty() {
+                        ui.separator();
+                        ui.colored_label(
+                            egui::Color32::from_rgb(255, 99, 71),
+                            format!("⚠ Clipping on {}", clipped.join(", ")),
+                        )
+                        .on_hover_text("At a rail within the shown window");
+                    }
 
-                                    plot_ui.line(line_a);
-                                    plot_ui.line(line_b);
+                    let nonfinite: Vec<&str> = self
+                        .nonfinite_channels()
+                        .into_iter()
+                        .filter_map(|ch| self.channel_names.get(ch).map(|n| n.as_str()))
+                        .collect();
+                    if !nonfinite.is_empty() {
+                        ui.separator();
+                        ui.colored_label(
+                            egui::Color32::from_rgb(255, 165, 0),
+                            format!("⚠ NaN on {}", nonfinite.join(", ")),
+                        )
+                        .on_hover_text("NaN or infinite values within the shown window, e.g. from a disconnected electrode; they are drawn as gaps and left out of the baseline and statistics");
+                    }
 
-                                    // add a vertical line at t_last
-                                    plot_ui.vline(
-                                        VLine::new("Time Window Start", t_last)
-                                            .stroke(Stroke::new(
-                                                1.0,
-                                                egui::Color32::from_rgb(255, 10, 10),
-                                            ))
-                                            .name("Time Window Start"),
-                                    );
-                                    plot_idx += 1;
-                                }
-                            }
-                            // check if we moved to a new time window
-                            if t_last < self.last_t {
-                                // request baseline correction
-                                self.baseline_correct();
-                            }
-                            self.last_t = t_last;
-                        });
+                    if let Some(recording) = &self.recording {
+                        let elapsed = recording.started.elapsed().as_secs();
+                        ui.separator();
+                        ui.colored_label(
+                            egui::Color32::from_rgb(255, 99, 71),
+                            format!(
+                                "⏺ REC {:02}:{:02} · {} samples",
+                                elapsed / 60,
+                                elapsed % 60,
+                                recording.samples_written
+                            ),
+                        );
+                    }
 
-                        // Display some stats
+                    ui.menu_button("⚙", |ui| {
+                        ui.label("Theme");
+                        for theme in Theme::ALL {
+                            ui.radio_value(&mut self.theme, theme, theme.label());
+                        }
+                        ui.separator();
+                        // egui keeps the zoom factor with its own state, so it persists like the window
+                        let mut ui_scale = ui.ctx().zoom_factor();
                         ui.horizontal(|ui| {
-                            let total_samples: usize =
-                                self.data_buffer.iter().map(|b| b.len()).sum();
-                            ui.label(format!("Total samples buffered: {}", total_samples));
-
-                            if let Some(channel_data) = self.data_buffer.first() {
-                                if let Some(&last_time) = channel_data.back() {
-                                    ui.label(format!("Last timestamp: {:.3}", last_time));
-                                }
+                            ui.label("UI scale");
+                            if ui
+                                .add(
+                                    egui::DragValue::new(&mut ui_scale)
+                                        .range(UI_SCALE_RANGE)
+                                        .speed(0.01)
+                                        .fixed_decimals(2)
+                                        .suffix("×"),
+                                )
+                                .on_hover_text("On top of the display's own scaling; Ctrl+Plus and Ctrl+Minus change it too")
+                                .changed()
+                            {
+                                ui.ctx().set_zoom_factor(ui_scale);
                             }
                         });
-                    } else {
-                        ui.label("No data received yet...");
+                    })
+                    .response
+                    .on_hover_text("Settings");
+                    if ui
+                        .small_button("⌨")
+                        .on_hover_text("Keyboard shortcuts (F1)")
+                        .clicked()
+                    {
+                        self.show_shortcuts = !self.show_shortcuts;
                     }
-                }
-            });
-        });
-
-        // Status bar
-        egui::TopBottomPanel::bottom("status_bar").show(ctx, |ui| {
-            ui.horizontal(|ui| {
-                ui.horizontal(|ui| {
-                    ui.label("Status:");
-                    ui.label(&self.status_message);
 
-                    if self.is_connected {
-                        if ui.button("Disconnect").clicked() {
-                            self.send_command(LslCommand::Disconnect);
+                    if self.is_connected && self.playback.is_none() {
+                        let label = if self.acquisition_paused { "▶ Resume" } else { "⏸ Pause" };
+                        if ui
+                            .button(label)
+                            .on_hover_text("Stop pulling data while staying connected; with Skip backlog, data that queued up meanwhile is dropped on resume. A running recording gets no samples while paused")
+                            .clicked()
+                        {
+                            self.acquisition_paused = !self.acquisition_paused;
+                            self.send_command(if self.acquisition_paused {
+                                LslCommand::Pause
+                            } else {
+                                LslCommand::Resume
+                            });
                         }
                     }
+                    if self.is_connected
+                        && ui.button("Disconnect").clicked() {
+                            self.send_command(LslCommand::Disconnect);
+                        }
                 });
             });
         });
     }
 }
 
+/// Current Unix time minus the LSL clock, to turn LSL timestamps into wall-clock time.
+fn wall_clock_offset() -> Option<f64> {
+    let unix_time = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .ok()?;
+    Some(unix_time.as_secs_f64() - lsl::local_clock())
+}
+
+/// A recording's file name from `template`, with `{stream}`, `{date}` and `{index}`
+/// filled in. Characters that don't belong in a file name are replaced by `_`.
+fn recording_file_name(template: &str, stream: &str, date: &str, index: usize) -> String {
+    let name: String = template
+        .replace("{stream}", stream)
+        .replace("{date}", date)
+        .replace("{index}", &index.to_string())
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || " -_.()".contains(c) {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let name = match name.trim() {
+        "" => "recording",
+        name => name,
+    };
+    if name.to_lowercase().ends_with(".csv") {
+        name.to_string()
+    } else {
+        format!("{}.csv", name)
+    }
+}
+
+/// `HH:MM:SS.mmm` in local time for a Unix time in seconds.
+fn format_time_of_day(unix_time: f64) -> String {
+    let seconds = unix_time.floor();
+    let nanos = ((unix_time - seconds) * 1e9) as u32;
+    chrono::DateTime::from_timestamp(seconds as i64, nanos).map_or_else(
+        || format!("{:.3} s", unix_time),
+        |utc| {
+            utc.with_timezone(&chrono::Local)
+                .format("%H:%M:%S%.3f")
+                .to_string()
+        },
+    )
+}
+
+/// The stream name given with `--connect <name>` or `--connect=<name>`, if any.
+fn stream_to_connect() -> Option<String> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    headless::option_value(&args, "connect")
+}
+
 fn main() -> eframe::Result {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let profile = match headless::option_value(&args, "profile") {
+        Some(path) => match profile::load(std::path::Path::new(&path)) {
+            Ok(profile) => Some(profile),
+            Err(message) => {
+                eprintln!("Error: {}", message);
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+    if let Some(parsed) = headless::parse_args(&args) {
+        let code = match parsed.and_then(headless::run) {
+            Ok(samples_written) => {
+                eprintln!("Recorded {} samples", samples_written);
+                0
+            }
+            Err(message) => {
+                eprintln!("Error: {}", message);
+                1
+            }
+        };
+        std::process::exit(code);
+    }
+
     let options = eframe::NativeOptions {
+        // the size for the first launch; after that the saved size and position are restored
         viewport: egui::ViewportBuilder::default()
-            .with_inner_size([800.0, 600.0])
+            .with_inner_size([1200.0, 800.0])
+            .with_min_inner_size([400.0, 300.0])
             .with_title("LSL Data Viewer"),
+        persist_window: true,
         ..Default::default()
     };
 
     eframe::run_native(
         "LSL Data Viewer",
         options,
-        Box::new(|_cc| Ok(Box::new(LslViewer::new()))),
+        Box::new(|cc| {
+            let settings = cc
+                .storage
+                .and_then(|storage| eframe::get_value(storage, eframe::APP_KEY))
+                .unwrap_or_default();
+            Ok(Box::new(LslViewer::new(
+                settings,
+                stream_to_connect(),
+                profile,
+            )))
+        }),
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_stream(name: &str, source_id: &str, channel_count: usize, rate: f64) -> StreamData {
+        StreamData {
+            name: name.to_string(),
+            stream_type: "EEG".to_string(),
+            source_id: source_id.to_string(),
+            hostname: String::new(),
+            channel_count,
+            sample_rate: rate,
+            channel_format: "float32".to_string(),
+        }
+    }
+
+    fn connected_viewer(channel_count: usize) -> LslViewer {
+        let mut viewer = LslViewer {
+            max_auto_selected_channels: DEFAULT_MAX_AUTO_SELECTED_CHANNELS,
+            time_window_seconds: DEFAULT_TIME_WINDOW_SECONDS,
+            unit_factor: 1.0,
+            ..Default::default()
+        };
+        viewer.handle_response(LslResponse::Connected {
+            stream: test_stream("Test", "", channel_count, 0.0),
+            channel_names: default_channel_names(channel_count),
+            channel_units: vec![String::new(); channel_count],
+            channel_types: vec![String::new(); channel_count],
+            metadata_available: true,
+        });
+        viewer
+    }
+
+    fn sample(timestamp: f64, channel_count: usize) -> LslResponse {
+        LslResponse::Data(DataSample {
+            timestamp,
+            values: vec![1.0; channel_count],
+        })
+    }
+
+    #[test]
+    fn identical_markers_close_together_share_a_label() {
+        let mut viewer = connected_viewer(1);
+        viewer.collapse_markers = true;
+        viewer.handle_response(sample(12.0, 1));
+        viewer.handle_response(sample(13.0, 1));
+        for (timestamp, text) in [
+            (12.1, "a"),
+            (12.11, "a"),
+            (12.12, "a"),
+            (12.5, "a"),
+            (12.51, "b"),
+        ] {
+            viewer
+                .marker_buffer
+                .push_back((timestamp, text.to_string()));
+        }
+        viewer.rebuild_plot_geometry();
+
+        let labels: Vec<(String, usize)> = viewer
+            .plot_geometry
+            .marker_labels
+            .iter()
+            .map(|(_, text, count)| (marker_label(text, *count), *count))
+            .collect();
+        assert_eq!(
+            labels,
+            vec![
+                ("a ×3".to_string(), 3),
+                ("a".to_string(), 1),
+                ("b".to_string(), 1)
+            ]
+        );
+
+        viewer.collapse_markers = false;
+        viewer.rebuild_plot_geometry();
+        assert_eq!(viewer.plot_geometry.marker_labels.len(), 5);
+    }
+
+    #[test]
+    fn nonfinite_values_are_left_out() {
+        let mut viewer = connected_viewer(3);
+        viewer.common_average = true;
+        for i in 0..10 {
+            let bad = match i % 3 {
+                0 => f32::NAN,
+                1 => f32::INFINITY,
+                _ => 4.0,
+            };
+            viewer.handle_response(LslResponse::Data(DataSample {
+                timestamp: i as f64 * 0.01,
+                values: vec![1.0, bad, 3.0],
+            }));
+        }
+        viewer.baseline_correct();
+
+        assert_eq!(viewer.nonfinite_channels(), vec![1]);
+        assert!(viewer.channel_baselines.iter().all(|b| b.is_finite()));
+        // the average reference is taken over the finite values only
+        assert!(viewer.data_buffer[0].iter().all(|v| v.is_finite()));
+        for channel_data in &viewer.data_buffer {
+            let stats = ChannelStats::compute(channel_data.iter().copied()).unwrap();
+            assert!(stats.mean.is_finite() && stats.std.is_finite() && stats.rms.is_finite());
+        }
+        assert_eq!(
+            ChannelStats::compute(viewer.data_buffer[1].iter().copied())
+                .unwrap()
+                .count,
+            3
+        );
+        // the bad samples stay in the buffer as gaps
+        let trace = viewer.data_buffer[1].iter();
+        assert_eq!(trace.filter(|v| v.is_nan()).count(), 7);
+
+        // coalescing averages the finite values of each channel, however many there are
+        let coalesced = coalesce_samples(&[
+            DataSample {
+                timestamp: 0.0,
+                values: vec![1.0, f32::NAN, 2.0],
+            },
+            DataSample {
+                timestamp: 0.1,
+                values: vec![3.0, f32::INFINITY],
+            },
+            DataSample {
+                timestamp: 0.2,
+                values: vec![5.0, f32::NAN, 4.0],
+            },
+        ]);
+        assert_eq!(coalesced.values[0], 3.0);
+        assert!(coalesced.values[1].is_nan());
+        assert_eq!(coalesced.values[2], 3.0);
+
+        // an epoch with a bad sample only leaves that sample out of the average
+        let mut erp = ErpAverager::new(1, 10.0, 0.2, 0.3);
+        let timestamps: VecDeque<f64> = (0..10).map(|i| i as f64 * 0.1).collect();
+        let mut data = vec![VecDeque::from(vec![2.0f32; 10])];
+        data[0][3] = f32::NAN;
+        data[0][5] = 4.0;
+        erp.add_event(0.4);
+        erp.update(&timestamps, &data);
+        assert_eq!(erp.trials, 1);
+        let average = erp.average(0);
+        assert_eq!(average.len(), 4);
+        assert!(average.iter().all(|[_, v]| v.is_finite()));
+        assert_eq!(
+            average.iter().map(|[_, v]| *v).collect::<Vec<_>>(),
+            vec![0.0, 0.0, 2.0, 0.0]
+        );
+    }
+
+    #[cfg(feature = "test-source")]
+    #[test]
+    fn generated_gaps_and_nans_reach_the_buffers() {
+        let config = test_source::TestSourceConfig {
+            channel_count: 4,
+            sample_rate: 250.0,
+            gap: Some((50, 25)),
+            nan_every: Some(10),
+        };
+        let mut viewer = connected_viewer(config.channel_count);
+        viewer.common_average = true;
+        for index in 0..200 {
+            viewer.handle_response(LslResponse::Data(test_source::sample_at(&config, index)));
+        }
+
+        assert_eq!(viewer.timestamp_buffer.len(), 200);
+        let steps: Vec<f64> = viewer
+            .timestamp_buffer
+            .iter()
+            .zip(viewer.timestamp_buffer.iter().skip(1))
+            .map(|(a, b)| b - a)
+            .collect();
+        // each gap skips 25 samples' worth of time after every 50 samples
+        let gaps: Vec<usize> = (0..steps.len())
+            .filter(|&i| steps[i] > 1.5 / config.sample_rate)
+            .collect();
+        assert_eq!(gaps, vec![49, 99, 149]);
+        assert!(
+            gaps.iter()
+                .all(|&i| (steps[i] - 26.0 / config.sample_rate).abs() < 1e-9)
+        );
+
+        // the NaN samples stay gaps on every channel without spreading through the reference
+        assert_eq!(viewer.nonfinite_channels(), vec![0, 1, 2, 3]);
+        for channel_data in &viewer.data_buffer {
+            assert_eq!(channel_data.iter().filter(|v| v.is_nan()).count(), 20);
+            let stats = ChannelStats::compute(channel_data.iter().copied()).unwrap();
+            assert_eq!(stats.count, 180);
+            assert!(stats.mean.is_finite() && stats.std.is_finite());
+        }
+    }
+
+    #[cfg(feature = "test-source")]
+    #[test]
+    fn generator_disconnect_is_handled_promptly() {
+        let (cmd_tx, cmd_rx) = crossbeam_channel::unbounded();
+        let (resp_tx, resp_rx) = crossbeam_channel::unbounded();
+        let handler = thread::spawn(move || {
+            test_source::test_source_thread(
+                cmd_rx,
+                resp_tx,
+                test_source::TestSourceConfig::default(),
+            )
+        });
+        cmd_tx
+            .send(LslCommand::Connect(
+                String::new(),
+                ConnectOptions::default(),
+            ))
+            .unwrap();
+        // disconnect while samples are flowing
+        loop {
+            match resp_rx.recv_timeout(Duration::from_secs(5)) {
+                Ok(LslResponse::Data(_)) => break,
+                Ok(_) => {}
+                Err(_) => panic!("expected generated samples"),
+            }
+        }
+        let sent = Instant::now();
+        cmd_tx.send(LslCommand::Disconnect).unwrap();
+        loop {
+            match resp_rx.recv_timeout(Duration::from_secs(5)) {
+                Ok(LslResponse::Disconnected) => break,
+                Ok(_) => {}
+                Err(_) => panic!("expected a Disconnected response"),
+            }
+        }
+        // one poll interval is the target, the bound leaves room for a loaded machine
+        assert!(sent.elapsed() < Duration::from_secs(1));
+
+        drop(cmd_tx);
+        handler.join().unwrap();
+    }
+
+    #[test]
+    fn profile_values_are_checked_against_the_stream() {
+        let mut viewer = connected_viewer(2);
+        viewer.selected_stream = Some(test_stream("Test", "", 2, 100.0));
+        viewer.profile = Some(Profile {
+            scale: Some(1e6),
+            bandpass: Some([1.0, 70.0]),
+            colors: [("Ch 0", "#e04040"), ("Fp1", "#40e040")]
+                .into_iter()
+                .map(|(label, color)| (label.to_string(), color.to_string()))
+                .collect(),
+            ..Default::default()
+        });
+        viewer.apply_profile("Test");
+
+        assert_eq!(viewer.data_scale, *SCALE_RANGE.end());
+        assert!(
+            viewer
+                .channel_scales
+                .iter()
+                .all(|s| *s == *SCALE_RANGE.end())
+        );
+        assert!(viewer.status_message.starts_with("Warning"));
+        assert!(
+            viewer
+                .status_message
+                .contains("colors for channels not in Test: Fp1")
+        );
+        assert!(viewer.status_message.contains("Nyquist"));
+    }
+
+    #[test]
+    fn headless_output_must_be_csv() {
+        let args = |out: &str| -> Vec<String> {
+            ["--headless", "--connect", "EEG", "--out", out]
+                .iter()
+                .map(|a| a.to_string())
+                .collect()
+        };
+        assert!(headless::parse_args(&args("run.csv")).unwrap().is_ok());
+        assert!(headless::parse_args(&args("RUN.CSV")).unwrap().is_ok());
+        for out in ["run.xdf", "run", "run.csv.gz"] {
+            let error = headless::parse_args(&args(out)).unwrap().err().unwrap();
+            assert!(error.contains(".csv"), "{}", error);
+        }
+    }
+
+    #[test]
+    fn channel_count_increase_resizes_buffers() {
+        let mut viewer = connected_viewer(2);
+        viewer.handle_response(sample(0.0, 2));
+        viewer.handle_response(sample(0.1, 4));
+
+        assert_eq!(viewer.channel_count, 4);
+        assert_eq!(viewer.channel_count_changes, 1);
+        assert_eq!(viewer.data_buffer.len(), 4);
+        assert_eq!(viewer.selected_channels.len(), 4);
+        assert_eq!(viewer.channel_names.len(), 4);
+        assert_eq!(viewer.channel_colors.len(), 4);
+        assert_eq!(viewer.channel_baselines.len(), 4);
+        assert_eq!(viewer.channel_offsets.len(), 4);
+        // the old data can't be lined up with the new layout
+        assert_eq!(viewer.timestamp_buffer.len(), 1);
+        assert!(viewer.data_buffer.iter().all(|b| b.len() == 1));
+    }
+
+    #[test]
+    fn channel_count_decrease_resizes_buffers() {
+        let mut viewer = connected_viewer(4);
+        viewer.reference_channel = Some(3);
+        viewer.handle_response(sample(0.0, 4));
+        viewer.handle_response(sample(0.1, 2));
+
+        assert_eq!(viewer.channel_count, 2);
+        assert_eq!(viewer.data_buffer.len(), 2);
+        assert_eq!(viewer.selected_channels.len(), 2);
+        assert_eq!(viewer.channel_names.len(), 2);
+        assert_eq!(viewer.reference_channel, None);
+    }
+
+    /// A regular stream in sweep mode, with baselines that a wrap would overwrite.
+    fn sweeping_viewer(channel_count: usize) -> LslViewer {
+        let mut viewer = connected_viewer(channel_count);
+        viewer.irregular = false;
+        viewer.channel_baselines.fill(7.0);
+        viewer
+    }
+
+    #[test]
+    fn geometry_without_samples() {
+        let mut viewer = sweeping_viewer(2);
+        viewer.last_t = 3.0;
+        viewer.rebuild_plot_geometry();
+
+        assert!(viewer.plot_geometry.traces.is_empty());
+        assert_eq!(viewer.plot_geometry.t_last, 0.0);
+        assert_eq!(viewer.last_t, 0.0);
+        assert_eq!(viewer.channel_baselines, vec![7.0, 7.0]);
+    }
+
+    #[test]
+    fn geometry_with_one_sample() {
+        let mut viewer = sweeping_viewer(2);
+        viewer.last_t = 3.0;
+        viewer.handle_response(sample(12.5, 2));
+        viewer.rebuild_plot_geometry();
+
+        let traces = &viewer.plot_geometry.traces;
+        assert_eq!(traces.len(), 2);
+        assert!(
+            traces
+                .iter()
+                .all(|t| t.current.len() + t.previous.len() == 1)
+        );
+        // a single sample doesn't define a window, so it can't wrap either
+        assert_eq!(viewer.last_t, 0.0);
+        assert_eq!(viewer.channel_baselines, vec![7.0, 7.0]);
+    }
+
+    #[test]
+    fn geometry_with_two_samples() {
+        let mut viewer = sweeping_viewer(2);
+        viewer.handle_response(sample(12.5, 2));
+        viewer.handle_response(sample(12.6, 2));
+        viewer.rebuild_plot_geometry();
+
+        let traces = &viewer.plot_geometry.traces;
+        assert_eq!(traces.len(), 2);
+        assert!(traces.iter().all(|t| t.current.len() == 2));
+        let position = 12.6f64.rem_euclid(viewer.time_window_seconds);
+        assert!((viewer.plot_geometry.t_last - position).abs() < 1e-9);
+        assert!((viewer.last_t - position).abs() < 1e-9);
+        assert_eq!(viewer.channel_baselines, vec![7.0, 7.0]);
+
+        // the same data again doesn't count as a wrap
+        viewer.rebuild_plot_geometry();
+        assert_eq!(viewer.channel_baselines, vec![7.0, 7.0]);
+    }
+
+    #[test]
+    fn switching_reference_between_equal_baselines() {
+        let mut viewer = sweeping_viewer(3);
+        viewer.channel_scales.fill(DEFAULT_SCALE);
+        for timestamp in [12.5, 12.6, 12.7] {
+            viewer.handle_response(LslResponse::Data(DataSample {
+                timestamp,
+                values: vec![1.0, 2.0, 5.0],
+            }));
+        }
+        let channel_0 = |viewer: &LslViewer| -> Vec<f64> {
+            let trace = viewer.plot_geometry.traces.iter().find(|t| t.channel == 0);
+            trace.unwrap().current.iter().map(|p| p.y).collect()
+        };
+        viewer.reference_channel = Some(1);
+        viewer.rebuild_plot_geometry();
+        let referenced_to_1 = channel_0(&viewer);
+
+        // both references have a baseline of 7, only the channel tells them apart
+        viewer.reference_channel = Some(2);
+        viewer.rebuild_plot_geometry();
+        let referenced_to_2 = channel_0(&viewer);
+        viewer.display_caches.clear();
+        viewer.rebuild_plot_geometry();
+        assert_eq!(referenced_to_2, channel_0(&viewer));
+        assert_ne!(referenced_to_1, referenced_to_2);
+    }
+
+    #[test]
+    fn connected_stream_dropping_out_of_the_listing() {
+        let mut viewer = connected_viewer(2);
+        let stream = test_stream("Test", "amp-1", 2, 250.0);
+        viewer.selected_stream = Some(stream.clone());
+        viewer.handle_response(LslResponse::StreamsFound(vec![stream]));
+        viewer.handle_response(LslResponse::StreamsFound(Vec::new()));
+
+        // the listing mirrors the network, the connection keeps its details
+        assert!(viewer.available_streams.is_empty());
+        assert_eq!(viewer.stream_sample_rate(), 250.0);
+    }
+
+    #[test]
+    fn connected_stream_details_come_with_the_connection() {
+        let mut viewer = connected_viewer(2);
+        viewer.handle_response(LslResponse::Disconnected);
+        // two streams share a name; the connection says which one it was
+        viewer.handle_response(LslResponse::StreamsFound(vec![
+            test_stream("EEG", "amp-1", 2, 250.0),
+            test_stream("EEG", "amp-2", 2, 500.0),
+        ]));
+        viewer.handle_response(LslResponse::Connected {
+            stream: test_stream("EEG", "amp-2", 2, 500.0),
+            channel_names: default_channel_names(2),
+            channel_units: vec![String::new(); 2],
+            channel_types: vec![String::new(); 2],
+            metadata_available: true,
+        });
+        assert_eq!(viewer.selected_stream.as_ref().unwrap().source_id, "amp-2");
+        assert_eq!(viewer.stream_sample_rate(), 500.0);
+        assert!(!viewer.irregular);
+
+        // connecting by name before any listing arrived
+        let mut viewer = connected_viewer(2);
+        viewer.handle_response(LslResponse::Disconnected);
+        viewer.handle_response(LslResponse::Connected {
+            stream: test_stream("EEG", "amp-1", 2, 250.0),
+            channel_names: default_channel_names(2),
+            channel_units: vec![String::new(); 2],
+            channel_types: vec![String::new(); 2],
+            metadata_available: true,
+        });
+        assert_eq!(viewer.stream_sample_rate(), 250.0);
+        assert_eq!(viewer.recent_streams[0].source_id, "amp-1");
+    }
+
+    #[test]
+    fn derived_channel_is_filled_in_from_the_buffer() {
+        let mut viewer = connected_viewer(2);
+        viewer.selected_stream = Some(test_stream("Test", "", 2, 100.0));
+        viewer.filter_settings.bandpass.enabled = true;
+        viewer.rebuild_filters();
+        let sample = |i: usize| {
+            let t = i as f32;
+            LslResponse::Data(DataSample {
+                timestamp: i as f64 * 0.01,
+                values: vec![(t * 0.3).sin() * 10.0 + 5.0, (t * 0.17).cos() * 3.0 - 2.0],
+            })
+        };
+        for i in 0..150 {
+            viewer.handle_response(sample(i));
+        }
+        viewer.clip_buffer.push_back((0, 0.5, 0.6));
+        let buffered = viewer.data_buffer.clone();
+
+        viewer.add_derived_channel(DerivedChannel::Difference(0, 1));
+        assert_eq!(viewer.data_buffer.len(), 3);
+        assert_eq!(viewer.data_buffer[..2], buffered[..]);
+        assert_eq!(viewer.clip_buffer.len(), 1);
+        assert!(viewer.selected_channels[2]);
+
+        // it carries on from the sources' filter state, without a transient
+        for i in 150..250 {
+            viewer.handle_response(sample(i));
+        }
+        let data = &viewer.data_buffer;
+        assert_eq!(data[2].len(), data[0].len());
+        for ((a, b), difference) in data[0].iter().zip(&data[1]).zip(&data[2]) {
+            assert!((a - b - difference).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn reconnect_with_different_channel_count() {
+        let mut viewer = connected_viewer(4);
+        viewer.reference_channel = Some(3);
+        viewer.last_stream = Some("Test".to_string());
+        for i in 0..10 {
+            viewer.handle_response(sample(i as f64 * 0.1, 4));
+        }
+        viewer.rebuild_plot_geometry();
+        viewer.handle_response(LslResponse::Disconnected);
+
+        viewer.handle_response(LslResponse::Connected {
+            stream: test_stream("Test", "", 2, 0.0),
+            channel_names: default_channel_names(2),
+            channel_units: vec![String::new(); 2],
+            channel_types: vec![String::new(); 2],
+            metadata_available: true,
+        });
+        assert!(viewer.status_message.starts_with("Warning"));
+        assert!(viewer.plot_geometry_stale());
+        for i in 0..10 {
+            viewer.handle_response(sample(1.0 + i as f64 * 0.1, 2));
+        }
+        viewer.rebuild_plot_geometry();
+
+        assert!(viewer.ensure_channel_state());
+        assert_eq!(viewer.channel_count, 2);
+        assert_eq!(viewer.channel_colors.len(), 2);
+        assert_eq!(viewer.channel_baselines.len(), 2);
+        assert_eq!(viewer.reference_channel, None);
+        assert!(viewer.plot_geometry.traces.iter().all(|t| t.channel < 2));
+    }
+
+    fn assert_mapped(mapped: (f64, bool), x: f64, current: bool) {
+        assert!(
+            (mapped.0 - x).abs() < 1e-9,
+            "x {} instead of {}",
+            mapped.0,
+            x
+        );
+        assert_eq!(mapped.1, current);
+    }
+
+    #[test]
+    fn map_to_window_splits_sweeps() {
+        // the sweep of the newest sample started at 10 s
+        assert_mapped(map_to_window(12.5, 13.0, 5.0), 2.5, true);
+        assert_mapped(map_to_window(13.0, 13.0, 5.0), 3.0, true);
+        assert_mapped(map_to_window(9.0, 13.0, 5.0), 4.0, false);
+        assert_mapped(map_to_window(8.5, 13.0, 5.0), 3.5, false);
+    }
+
+    #[test]
+    fn map_to_window_boundaries() {
+        // a sample right at the wrap starts the new sweep, also when it is the newest
+        assert_mapped(map_to_window(10.0, 13.0, 5.0), 0.0, true);
+        assert_mapped(map_to_window(10.0, 10.0, 5.0), 0.0, true);
+        assert_mapped(map_to_window(9.99, 10.0, 5.0), 4.99, false);
+        // the first sweep of a clock starting at zero
+        assert_mapped(map_to_window(0.0, 0.0, 5.0), 0.0, true);
+        assert_mapped(map_to_window(4.999, 4.999, 5.0), 4.999, true);
+    }
+
+    #[test]
+    fn map_to_window_negative_timestamps() {
+        // the sweep containing -2 s started at -5 s, not at -2 s
+        assert_eq!(sweep_start(-2.0, 5.0), -5.0);
+        assert_mapped(map_to_window(-3.0, -2.0, 5.0), 2.0, true);
+        assert_mapped(map_to_window(-6.0, -2.0, 5.0), 4.0, false);
+    }
+
+    #[test]
+    fn map_to_window_after_window_change() {
+        let timestamps = [100.2, 101.7, 103.9, 104.0, 104.6];
+        for window in [0.25, 1.0, 2.0, 5.0, 30.0] {
+            let latest = 104.6;
+            let start = sweep_start(latest, window);
+            assert!(start <= latest && latest - start < window);
+            for &timestamp in timestamps.iter() {
+                let (x, current) = map_to_window(timestamp, latest, window);
+                assert!(
+                    (0.0..window).contains(&x),
+                    "x {} outside a {} s window",
+                    x,
+                    window
+                );
+                assert_eq!(current, timestamp >= start);
+            }
+        }
+    }
+
+    #[test]
+    fn channel_count_change_rejected() {
+        let mut viewer = connected_viewer(2);
+        viewer.channel_count_policy = ChannelCountChangePolicy::Reject;
+        viewer.handle_response(sample(0.0, 2));
+        viewer.handle_response(sample(0.1, 3));
+        viewer.handle_response(sample(0.2, 1));
+        viewer.handle_response(sample(0.3, 2));
+
+        assert_eq!(viewer.channel_count, 2);
+        assert_eq!(viewer.channel_count_changes, 2);
+        assert_eq!(viewer.timestamp_buffer.len(), 2);
+        assert!(viewer.data_buffer.iter().all(|b| b.len() == 2));
+    }
+
+    #[test]
+    fn xdf_export_round_trip() {
+        let mut viewer = connected_viewer(3);
+        viewer.time_window_seconds = 20.0;
+        // more than one Samples chunk, with irregular spacing that must survive exactly
+        for i in 0..2500 {
+            viewer.handle_response(sample(1000.0 + i as f64 * 0.004 + (i % 7) as f64 * 1e-6, 3));
+        }
+        let first = *viewer.timestamp_buffer.front().unwrap();
+        let last = *viewer.timestamp_buffer.back().unwrap();
+        let count = viewer.timestamp_buffer.len();
+        // a second, string-valued stream that must not be read as samples
+        viewer.add_annotation();
+
+        let path = std::env::temp_dir().join(format!("lsl-viewer-test-{}.xdf", std::process::id()));
+        viewer.export_xdf(&path).unwrap();
+        let timestamps = xdf::read_timestamps(&path, 3).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(count, 2500);
+        assert_eq!(timestamps.len(), count);
+        assert_eq!(timestamps.first().copied(), Some(first));
+        assert_eq!(timestamps.last().copied(), Some(last));
+    }
+
+    #[test]
+    fn recording_file_name_fills_in_the_template() {
+        assert_eq!(
+            recording_file_name(DEFAULT_RECORDING_TEMPLATE, "EEG", "2024-05-01", 3),
+            "EEG_2024-05-01_3.csv"
+        );
+        assert_eq!(
+            recording_file_name("{stream}.CSV", "a/b:c", "", 1),
+            "a_b_c.CSV"
+        );
+        assert_eq!(
+            recording_file_name("", "EEG", "2024-05-01", 1),
+            "recording.csv"
+        );
+    }
+}
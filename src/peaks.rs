@@ -0,0 +1,71 @@
+// Peak detection on one channel, for periodic signals like a heartbeat or
+// breathing. A peak is the highest sample of a run above the threshold, kept
+// if it comes at least the refractory period after the previous one.
+
+/// Detection settings as chosen in the UI.
+#[derive(Clone, Copy, PartialEq)]
+pub struct PeakSettings {
+    pub threshold: f64,  // Deflection from the baseline, in the display unit
+    pub refractory: f64, // Seconds after a peak in which no other is accepted
+    pub troughs: bool,   // Look for the lowest samples below -threshold instead
+}
+
+impl Default for PeakSettings {
+    fn default() -> Self {
+        Self {
+            threshold: 50.0,
+            refractory: 0.3, // Allows up to 200 peaks per minute
+            troughs: false,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct PeakDetector {
+    candidate: Option<(f64, f32, f64)>, // Timestamp, value and deflection of the extreme of the current run
+    last_peak: Option<f64>,
+}
+
+impl PeakDetector {
+    /// Feed one sample; returns the timestamp and value of the peak that ended
+    /// with it, if any.
+    pub fn push(
+        &mut self,
+        settings: &PeakSettings,
+        timestamp: f64,
+        value: f32,
+        baseline: f64,
+    ) -> Option<(f64, f32)> {
+        let deflection = value as f64 - baseline;
+        let deflection = if settings.troughs {
+            -deflection
+        } else {
+            deflection
+        };
+        if deflection > settings.threshold {
+            if self
+                .candidate
+                .is_none_or(|(_, _, extreme)| deflection > extreme)
+            {
+                self.candidate = Some((timestamp, value, deflection));
+            }
+            return None;
+        }
+        let (peak, value, _) = self.candidate.take()?;
+        if self
+            .last_peak
+            .is_some_and(|last| peak - last < settings.refractory)
+        {
+            return None;
+        }
+        self.last_peak = Some(peak);
+        Some((peak, value))
+    }
+}
+
+/// Peaks per minute from the interval between the last two peak timestamps.
+pub fn rate_per_minute(peaks: impl DoubleEndedIterator<Item = f64>) -> Option<f64> {
+    let mut newest = peaks.rev();
+    let (last, previous) = (newest.next()?, newest.next()?);
+    (last > previous).then(|| 60.0 / (last - previous))
+}
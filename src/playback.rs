@@ -0,0 +1,245 @@
+// Playback of a CSV recording. While a file is open, this thread runs in place of
+// the LSL thread and answers the same commands, so the whole display pipeline is reused.
+use crossbeam_channel::{Receiver, RecvTimeoutError, Sender};
+use lsl_viewer::{COMMAND_POLL_INTERVAL, DataSample, LslCommand, LslResponse, StreamData};
+use std::path::Path;
+use std::time::Instant;
+
+/// Samples of a recording as written by `CsvSink`.
+pub struct Recording {
+    pub name: String, // File name, shown as the stream name
+    pub channel_names: Vec<String>,
+    pub timestamps: Vec<f64>,
+    pub samples: Vec<Vec<f32>>,
+}
+
+impl Recording {
+    fn start(&self) -> f64 {
+        self.timestamps.first().copied().unwrap_or(0.0)
+    }
+
+    fn duration(&self) -> f64 {
+        self.timestamps.last().copied().unwrap_or(0.0) - self.start()
+    }
+
+    /// Average rate over the whole file, 0 if it can't be told.
+    fn sample_rate(&self) -> f64 {
+        let duration = self.duration();
+        if duration > 0.0 {
+            (self.timestamps.len() - 1) as f64 / duration
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Read a CSV file with a `timestamp` column followed by one column per channel.
+pub fn load_csv(path: &Path) -> Result<Recording, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+    let mut lines = text.lines();
+    let header = split_csv_line(lines.next().unwrap_or_default());
+    if header.first().map(|h| h.trim()) != Some("timestamp") || header.len() < 2 {
+        return Err(format!(
+            "{} has no 'timestamp' column followed by channel columns",
+            path.display()
+        ));
+    }
+    let channel_names = header[1..].to_vec();
+
+    let mut timestamps = Vec::new();
+    let mut samples = Vec::new();
+    for (row, line) in lines.enumerate().filter(|(_, l)| !l.trim().is_empty()) {
+        let bad_row = || format!("{}: malformed row {}", path.display(), row + 2);
+        let mut fields = line.split(',');
+        let timestamp: f64 = fields
+            .next()
+            .and_then(|f| f.trim().parse().ok())
+            .ok_or_else(bad_row)?;
+        let values = fields
+            .map(|f| f.trim().parse::<f32>())
+            .collect::<Result<Vec<f32>, _>>()
+            .map_err(|_| bad_row())?;
+        if values.len() != channel_names.len() {
+            return Err(bad_row());
+        }
+        timestamps.push(timestamp);
+        samples.push(values);
+    }
+    if timestamps.is_empty() {
+        return Err(format!("{} contains no samples", path.display()));
+    }
+
+    Ok(Recording {
+        name: path.file_name().map_or_else(
+            || path.display().to_string(),
+            |n| n.to_string_lossy().into_owned(),
+        ),
+        channel_names,
+        timestamps,
+        samples,
+    })
+}
+
+/// Split a header row, undoing the quoting `CsvSink` applies to names.
+fn split_csv_line(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                fields.last_mut().unwrap().push('"');
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(String::new()),
+            _ => fields.last_mut().unwrap().push(c),
+        }
+    }
+    fields
+}
+
+/// Where playback is: the file time that was current at `since`, advancing at
+/// `speed` unless paused.
+struct Clock {
+    position: f64,
+    since: Instant,
+    speed: f64,
+    paused: bool,
+}
+
+impl Clock {
+    fn now(&self) -> f64 {
+        if self.paused {
+            self.position
+        } else {
+            self.position + self.since.elapsed().as_secs_f64() * self.speed
+        }
+    }
+
+    /// Continue from the current position, so speed and pause changes don't jump.
+    fn rebase(&mut self) {
+        self.position = self.now();
+        self.since = Instant::now();
+    }
+}
+
+pub fn playback_thread(
+    recording: Recording,
+    cmd_rx: Receiver<LslCommand>,
+    resp_tx: Sender<LslResponse>,
+) {
+    let stream = StreamData {
+        name: recording.name.clone(),
+        stream_type: "Recording".to_string(),
+        source_id: String::new(),
+        hostname: String::new(),
+        channel_count: recording.channel_names.len(),
+        sample_rate: recording.sample_rate(),
+        channel_format: "float32".to_string(),
+    };
+    let start = recording.start();
+    let duration = recording.duration();
+    let connected = || LslResponse::Connected {
+        stream: stream.clone(),
+        channel_names: recording.channel_names.clone(),
+        channel_units: vec![String::new(); recording.channel_names.len()],
+        channel_types: vec![String::new(); recording.channel_names.len()],
+        metadata_available: false,
+    };
+
+    // listed like a live stream, so it shows up in the stream list
+    let _ = resp_tx.send(LslResponse::StreamsFound(vec![stream.clone()]));
+    let _ = resp_tx.send(connected());
+    let mut clock = Clock {
+        position: 0.0,
+        since: Instant::now(),
+        speed: 1.0,
+        paused: false,
+    };
+    let mut next = 0; // Index of the next sample to send
+
+    loop {
+        match cmd_rx.recv_timeout(COMMAND_POLL_INTERVAL) {
+            Ok(LslCommand::RefreshStreams | LslCommand::RefreshStreamsFiltered(_)) => {
+                let _ = resp_tx.send(LslResponse::StreamsFound(vec![stream.clone()]));
+            }
+            Ok(LslCommand::Connect(..) | LslCommand::ConnectByName(..)) => {
+                let _ = resp_tx.send(connected());
+                clock.position = 0.0;
+                clock.since = Instant::now();
+                next = 0;
+            }
+            Ok(LslCommand::Disconnect) => {
+                let _ = resp_tx.send(LslResponse::Disconnected);
+            }
+            Ok(LslCommand::CancelConnect) => {}
+            Ok(LslCommand::ConnectMarkers(_)) => {
+                let _ = resp_tx.send(LslResponse::Error(
+                    "Markers can't be shown during playback".to_string(),
+                ));
+            }
+            Ok(LslCommand::DisconnectMarkers) => {
+                let _ = resp_tx.send(LslResponse::MarkersDisconnected);
+            }
+            Ok(LslCommand::StartRecording(_)) => {
+                let _ = resp_tx.send(LslResponse::Error(
+                    "The file is already a recording".to_string(),
+                ));
+            }
+            Ok(LslCommand::StopRecording | LslCommand::SkipBacklog) => {}
+            Ok(LslCommand::Pause) => {
+                clock.rebase();
+                clock.paused = true;
+            }
+            Ok(LslCommand::Resume) => {
+                clock.rebase();
+                clock.paused = false;
+            }
+            Ok(LslCommand::SetPlaybackPaused(paused)) => {
+                clock.rebase();
+                clock.paused = paused;
+            }
+            Ok(LslCommand::SetPlaybackSpeed(speed)) => {
+                clock.rebase();
+                clock.speed = speed;
+            }
+            Ok(LslCommand::SeekPlayback(position)) => {
+                clock.position = position.clamp(0.0, duration);
+                clock.since = Instant::now();
+                next = recording
+                    .timestamps
+                    .partition_point(|&t| t - start < clock.position);
+                // everything sent before this belongs to the old position
+                let _ = resp_tx.send(LslResponse::PlaybackSeeked);
+                let _ = resp_tx.send(LslResponse::PlaybackProgress {
+                    position: clock.position,
+                    duration,
+                });
+            }
+            Err(RecvTimeoutError::Disconnected) => break,
+            Err(RecvTimeoutError::Timeout) => {}
+        }
+
+        // Send everything that is due by the stored timestamps
+        let due = clock.now();
+        let sent_before = next;
+        while next < recording.timestamps.len() && recording.timestamps[next] - start <= due {
+            let sample = DataSample {
+                timestamp: recording.timestamps[next],
+                values: recording.samples[next].clone(),
+            };
+            if resp_tx.send(LslResponse::Data(sample)).is_err() {
+                return;
+            }
+            next += 1;
+        }
+        if next != sent_before {
+            let _ = resp_tx.send(LslResponse::PlaybackProgress {
+                position: due.min(duration),
+                duration,
+            });
+        }
+    }
+}
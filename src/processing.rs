@@ -0,0 +1,46 @@
+// Per-sample processing applied on the LSL thread, before samples reach the UI.
+//
+// To add a processor, implement `SampleProcessor` for your type and push a boxed
+// instance in `registered_processor` below. Processors run in registration order.
+// The built-in filters (filter.rs) implement the same trait but run on the UI side,
+// where their settings are changed.
+use crate::DataSample;
+
+pub trait SampleProcessor: Send {
+    /// Called on every (re)connect, before the first sample of the new stream.
+    /// `sample_rate` is 0.0 for irregular streams.
+    fn reset(&mut self, _channel_count: usize, _sample_rate: f64) {}
+
+    /// Modify the sample in place.
+    fn process(&mut self, sample: &mut DataSample);
+}
+
+/// The single registration point for processors. Returns `None` when nothing
+/// is registered, so the LSL thread can skip processing altogether.
+pub fn registered_processor() -> Option<Box<dyn SampleProcessor>> {
+    let processors: Vec<Box<dyn SampleProcessor>> = Vec::new();
+    if processors.is_empty() {
+        None
+    } else {
+        Some(Box::new(ProcessorChain { processors }))
+    }
+}
+
+/// Runs a list of processors one after the other.
+pub struct ProcessorChain {
+    processors: Vec<Box<dyn SampleProcessor>>,
+}
+
+impl SampleProcessor for ProcessorChain {
+    fn reset(&mut self, channel_count: usize, sample_rate: f64) {
+        for processor in self.processors.iter_mut() {
+            processor.reset(channel_count, sample_rate);
+        }
+    }
+
+    fn process(&mut self, sample: &mut DataSample) {
+        for processor in self.processors.iter_mut() {
+            processor.process(sample);
+        }
+    }
+}
@@ -0,0 +1,106 @@
+// A viewer profile given with `--profile FILE`: which stream to open and how to
+// show it once connected. JSON, every field optional, e.g.
+// {"stream": "EEG", "channels": ["Cz", "Pz"], "colors": {"Cz": "#e04040"},
+//  "scale": 50.0, "window_seconds": 10.0, "bandpass": [1.0, 40.0], "notch": 50}
+use crate::filter::{FilterSettings, NotchFrequency};
+use std::collections::HashMap;
+use std::path::Path;
+
+#[derive(Default, serde::Deserialize)]
+#[serde(default)]
+pub struct Profile {
+    pub stream: Option<String>, // Connected to on launch unless --connect names another
+    pub channels: Vec<String>,  // Labels of the channels to show, all if empty
+    pub colors: HashMap<String, String>, // Channel label to "#rrggbb"
+    pub scale: Option<f64>,
+    pub window_seconds: Option<f64>,
+    pub bandpass: Option<[f64; 2]>, // Low and high cut in Hz
+    pub notch: Option<u32>,         // 50 or 60 Hz, 0 for off
+    pub ac_coupling: Option<f64>,   // Cutoff in Hz
+}
+
+pub fn load(path: &Path) -> Result<Profile, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read profile {}: {}", path.display(), e))?;
+    let profile: Profile = serde_json::from_str(&text)
+        .map_err(|e| format!("Invalid profile {}: {}", path.display(), e))?;
+    if let Some(hz) = profile.notch
+        && !matches!(hz, 0 | 50 | 60)
+    {
+        return Err(format!("Profile notch must be 0, 50 or 60, not {}", hz));
+    }
+    if let Some([low_cut, high_cut]) = profile.bandpass
+        && !(low_cut > 0.0 && low_cut < high_cut && high_cut.is_finite())
+    {
+        return Err(format!(
+            "Profile bandpass must be [low, high] with 0 < low < high, not [{}, {}]",
+            low_cut, high_cut
+        ));
+    }
+    if let Some((label, color)) = profile
+        .colors
+        .iter()
+        .find(|(_, color)| egui::Color32::from_hex(color).is_err())
+    {
+        return Err(format!(
+            "Profile color '{}' for {} isn't #rrggbb",
+            color, label
+        ));
+    }
+    Ok(profile)
+}
+
+impl Profile {
+    /// Whether the profile is meant for the stream called `name`.
+    pub fn applies_to(&self, name: &str) -> bool {
+        self.stream.as_deref().is_none_or(|stream| stream == name)
+    }
+
+    /// Colors by channel label; `load` has already rejected unparsable ones.
+    pub fn parsed_colors(&self) -> impl Iterator<Item = (String, egui::Color32)> + '_ {
+        self.colors.iter().filter_map(|(label, color)| {
+            egui::Color32::from_hex(color)
+                .ok()
+                .map(|color| (label.clone(), color))
+        })
+    }
+
+    /// The profile's labels that aren't among `channel_names`.
+    pub fn missing_channels(&self, channel_names: &[String]) -> Vec<String> {
+        self.channels
+            .iter()
+            .filter(|label| !channel_names.contains(label))
+            .cloned()
+            .collect()
+    }
+
+    /// The labels given a color that aren't among `channel_names`, sorted.
+    pub fn missing_colors(&self, channel_names: &[String]) -> Vec<String> {
+        let mut missing: Vec<String> = self
+            .colors
+            .keys()
+            .filter(|label| !channel_names.contains(label))
+            .cloned()
+            .collect();
+        missing.sort();
+        missing
+    }
+
+    pub fn apply_filters(&self, settings: &mut FilterSettings) {
+        if let Some([low_cut, high_cut]) = self.bandpass {
+            settings.bandpass.enabled = true;
+            settings.bandpass.low_cut = low_cut;
+            settings.bandpass.high_cut = high_cut;
+        }
+        match self.notch {
+            Some(50) => settings.notch = NotchFrequency::Hz50,
+            Some(60) => settings.notch = NotchFrequency::Hz60,
+            Some(_) => settings.notch = NotchFrequency::Off,
+            None => {}
+        }
+        if let Some(cutoff) = self.ac_coupling {
+            settings.ac_coupling.enabled = true;
+            settings.ac_coupling.cutoff = cutoff;
+        }
+    }
+}
@@ -0,0 +1,176 @@
+// Recording of incoming samples, driven from the LSL thread. The file format is
+// behind `SampleSink`, so the thread only deals with a `Recorder`.
+use crate::DataSample;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1); // At most this much data is lost on a crash
+
+/// A recording format. Buffered data must also reach the output when the sink is dropped.
+pub trait SampleSink: Send {
+    fn write_header(&mut self, channels: &[String]) -> std::io::Result<()>;
+    fn write_sample(&mut self, timestamp: f64, values: &[f32]) -> std::io::Result<()>;
+    fn flush(&mut self) -> std::io::Result<()>;
+}
+
+/// One row per sample: the timestamp, then every channel.
+pub struct CsvSink<W: Write> {
+    writer: BufWriter<W>, // Flushes itself when dropped
+}
+
+impl CsvSink<File> {
+    pub fn create(path: &Path) -> std::io::Result<Self> {
+        Ok(Self::new(File::create(path)?))
+    }
+}
+
+impl<W: Write> CsvSink<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer: BufWriter::new(writer),
+        }
+    }
+}
+
+impl<W: Write + Send> SampleSink for CsvSink<W> {
+    fn write_header(&mut self, channels: &[String]) -> std::io::Result<()> {
+        write!(self.writer, "timestamp")?;
+        for name in channels {
+            write!(self.writer, ",{}", csv_field(name))?;
+        }
+        writeln!(self.writer)
+    }
+
+    fn write_sample(&mut self, timestamp: f64, values: &[f32]) -> std::io::Result<()> {
+        write!(self.writer, "{}", timestamp)?;
+        for value in values.iter() {
+            write!(self.writer, ",{}", value)?;
+        }
+        writeln!(self.writer)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+pub struct Recorder {
+    sink: Box<dyn SampleSink>,
+    pub samples_written: usize,
+    last_flush: Instant,
+}
+
+impl Recorder {
+    /// Create a CSV file at `path` and write the header row.
+    pub fn create(path: &Path, channel_names: &[String]) -> std::io::Result<Self> {
+        Self::new(Box::new(CsvSink::create(path)?), channel_names)
+    }
+
+    /// Record into `sink`, starting with the header.
+    pub fn new(mut sink: Box<dyn SampleSink>, channel_names: &[String]) -> std::io::Result<Self> {
+        sink.write_header(channel_names)?;
+        Ok(Self {
+            sink,
+            samples_written: 0,
+            last_flush: Instant::now(),
+        })
+    }
+
+    pub fn write(&mut self, sample: &DataSample) -> std::io::Result<()> {
+        self.sink.write_sample(sample.timestamp, &sample.values)?;
+        self.samples_written += 1;
+        Ok(())
+    }
+
+    /// Flush to disk if the flush interval has passed; returns whether it did.
+    pub fn flush_if_due(&mut self) -> std::io::Result<bool> {
+        if self.last_flush.elapsed() < FLUSH_INTERVAL {
+            return Ok(false);
+        }
+        self.sink.flush()?;
+        self.last_flush = Instant::now();
+        Ok(true)
+    }
+
+    /// Flush the remaining data and return the number of samples written.
+    pub fn finish(mut self) -> std::io::Result<usize> {
+        self.sink.flush()?;
+        Ok(self.samples_written)
+    }
+}
+
+/// Quote a header field if it contains characters that would break the row.
+pub fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    /// An in-memory output that stays readable after the recorder took ownership.
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn csv_sink_output() {
+        let buffer = SharedBuffer::default();
+        let mut recorder = Recorder::new(
+            Box::new(CsvSink::new(buffer.clone())),
+            &["Fp1".to_string(), "A, \"B\"".to_string()],
+        )
+        .unwrap();
+        recorder
+            .write(&DataSample {
+                timestamp: 1.5,
+                values: vec![0.25, -2.0],
+            })
+            .unwrap();
+        recorder
+            .write(&DataSample {
+                timestamp: 2.0,
+                values: vec![1.0, f32::NAN],
+            })
+            .unwrap();
+        assert_eq!(recorder.finish().unwrap(), 2);
+
+        let written = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        assert_eq!(
+            written,
+            "timestamp,Fp1,\"A, \"\"B\"\"\"\n1.5,0.25,-2\n2,1,NaN\n"
+        );
+    }
+
+    #[test]
+    fn csv_sink_flushes_on_drop() {
+        let mut output = Vec::new();
+        {
+            let mut sink = CsvSink::new(&mut output);
+            sink.write_header(&["Cz".to_string()]).unwrap();
+            for i in 0..1000 {
+                sink.write_sample(i as f64, &[i as f32]).unwrap();
+            }
+        }
+        let written = String::from_utf8(output).unwrap();
+
+        assert_eq!(written.lines().count(), 1001);
+        assert_eq!(written.lines().last(), Some("999,999"));
+    }
+}
@@ -0,0 +1,94 @@
+// User settings that are kept between launches, stored through eframe's storage.
+use crate::filter::FilterSettings;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default)] // Settings saved by an older version still load, missing fields get defaults
+pub struct Settings {
+    pub data_scale: f64,
+    pub time_window_seconds: f64,
+    pub downsample_factor: usize,
+    pub history_seconds: f64, // Buffered beyond the time window for the overview strip, 0 for none
+    pub auto_refresh: bool,
+    pub color_overrides: HashMap<String, egui::Color32>,
+    pub last_stream: Option<String>, // Name of the most recently connected stream
+    pub auto_connect_last_stream: bool,
+    pub adapt_to_rate: bool, // Pick the time window and downsampling from the sample rate on connect
+    pub theme: Theme,
+    pub auto_select_types: String, // Comma-separated channel types ticked on connect, empty for any
+    pub recent_streams: Vec<RecentStream>, // Most recently used first
+    pub stream_profiles: HashMap<String, StreamProfile>, // By stream name
+    pub recording_directory: Option<PathBuf>, // Where the last recording went
+    pub recording_template: String, // File name of new recordings, see `recording_file_name`
+}
+
+/// How a stream was last displayed, restored when a stream of that name connects.
+#[derive(Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct StreamProfile {
+    pub selected_channels: Vec<String>, // Labels of the ticked channels
+    pub colors: HashMap<String, egui::Color32>, // Picked colors of its channels, by label
+    pub data_scale: f64,
+    pub time_window_seconds: f64,
+    pub filters: FilterSettings,
+}
+
+/// A stream connected to in an earlier session, offered for quick reconnecting.
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct RecentStream {
+    pub name: String,
+    pub source_id: String,
+    pub last_used: u64, // Unix time in seconds
+}
+
+/// Light or dark visuals, or whatever the operating system uses.
+#[derive(Clone, Copy, PartialEq, Default, serde::Serialize, serde::Deserialize)]
+pub enum Theme {
+    #[default]
+    System,
+    Light,
+    Dark,
+}
+
+impl Theme {
+    pub const ALL: [Theme; 3] = [Theme::System, Theme::Light, Theme::Dark];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Theme::System => "System",
+            Theme::Light => "Light",
+            Theme::Dark => "Dark",
+        }
+    }
+
+    pub fn preference(self) -> egui::ThemePreference {
+        match self {
+            Theme::System => egui::ThemePreference::System,
+            Theme::Light => egui::ThemePreference::Light,
+            Theme::Dark => egui::ThemePreference::Dark,
+        }
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            data_scale: crate::DEFAULT_SCALE,
+            time_window_seconds: crate::DEFAULT_TIME_WINDOW_SECONDS,
+            downsample_factor: crate::DEFAULT_DOWN_SAMPLE_FACTOR,
+            history_seconds: 0.0,
+            auto_refresh: true,
+            color_overrides: HashMap::new(),
+            last_stream: None,
+            auto_connect_last_stream: false,
+            adapt_to_rate: true,
+            theme: Theme::System,
+            auto_select_types: String::new(),
+            recent_streams: Vec::new(),
+            stream_profiles: HashMap::new(),
+            recording_directory: None,
+            recording_template: crate::DEFAULT_RECORDING_TEMPLATE.to_string(),
+        }
+    }
+}
@@ -0,0 +1,70 @@
+// In-session gallery of plot snapshots.
+use eframe::egui;
+use std::path::Path;
+use std::sync::Arc;
+
+pub struct Snapshot {
+    pub image: Arc<egui::ColorImage>,
+    pub texture: egui::TextureHandle,
+    pub stream_time: f64, // Newest LSL timestamp on screen when captured
+    pub unix_time: u64,   // Wall-clock capture time, used for file names
+    pub settings: String, // Human-readable display settings at capture
+}
+
+impl Snapshot {
+    /// Crop a full-window screenshot to `plot_rect` and upload it as a texture.
+    pub fn from_screenshot(
+        ctx: &egui::Context,
+        screenshot: &egui::ColorImage,
+        plot_rect: egui::Rect,
+        stream_time: f64,
+        settings: String,
+    ) -> Self {
+        let image = Arc::new(screenshot.region(&plot_rect, Some(ctx.pixels_per_point())));
+        let unix_time = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        let texture = ctx.load_texture(
+            format!("snapshot_{}_{}", unix_time, stream_time),
+            image.clone(),
+            egui::TextureOptions::LINEAR,
+        );
+        Self {
+            image,
+            texture,
+            stream_time,
+            unix_time,
+            settings,
+        }
+    }
+}
+
+/// Write every snapshot to `dir` as a PNG, returning how many were written.
+pub fn export_snapshots(dir: &Path, snapshots: &[Snapshot]) -> Result<usize, String> {
+    for (i, snapshot) in snapshots.iter().enumerate() {
+        let path = dir.join(format!("snapshot_{}_{:03}.png", snapshot.unix_time, i + 1));
+        save_png(&path, &snapshot.image, 1)?;
+    }
+    Ok(snapshots.len())
+}
+
+/// Write `image` to `path` as a PNG, enlarged `scale` times.
+pub fn save_png(path: &Path, image: &egui::ColorImage, scale: u32) -> Result<(), String> {
+    let [width, height] = image.size;
+    let (width, height) = (width as u32, height as u32);
+    let buffer = image::RgbaImage::from_raw(width, height, image.as_raw().to_vec())
+        .ok_or_else(|| "screenshot has an unexpected size".to_string())?;
+    let buffer = if scale > 1 {
+        image::imageops::resize(
+            &buffer,
+            width * scale,
+            height * scale,
+            image::imageops::FilterType::CatmullRom,
+        )
+    } else {
+        buffer
+    };
+    buffer
+        .save_with_format(path, image::ImageFormat::Png)
+        .map_err(|e| format!("failed to write {}: {}", path.display(), e))
+}
@@ -0,0 +1,213 @@
+// Running spectrogram of one channel: a short-time spectrum every `hop` samples,
+// kept for the time window and drawn as an image that scrolls right to left.
+use crate::spectrum;
+use egui::{Color32, ColorImage, TextureHandle, TextureId, TextureOptions};
+use rustfft::FftPlanner;
+use std::collections::VecDeque;
+
+pub const FFT_SIZES: [usize; 5] = [128, 256, 512, 1024, 2048];
+const DYNAMIC_RANGE_DB: f64 = 60.0; // Bins this far below the strongest one are drawn darkest
+const MAX_COLUMNS: usize = 2048; // Texture width limit, reached with a small hop and a long window
+
+/// Spectrogram settings as chosen in the UI.
+#[derive(Clone, Copy, PartialEq)]
+pub struct SpectrogramSettings {
+    pub channel: usize,
+    pub fft_size: usize,
+    pub hop: usize,         // Samples from the start of one spectrum to the next
+    pub min_frequency: f64, // Hz
+    pub max_frequency: f64, // Hz, capped at Nyquist
+}
+
+impl Default for SpectrogramSettings {
+    fn default() -> Self {
+        Self {
+            channel: 0,
+            fft_size: 256,
+            hop: 32,
+            min_frequency: 0.0,
+            max_frequency: 60.0,
+        }
+    }
+}
+
+pub struct Spectrogram {
+    settings: SpectrogramSettings,
+    sample_rate: f64,
+    planner: FftPlanner<f64>,
+    samples: VecDeque<f32>,      // The newest fft_size samples
+    since_column: usize,         // Samples since the last spectrum
+    columns: VecDeque<Vec<f64>>, // dB per bin of the frequency range, lowest bin first, oldest column first
+    max_columns: usize,
+    texture: Option<TextureHandle>,
+    changed: bool, // Columns were added since the texture was last updated
+}
+
+impl Spectrogram {
+    pub fn new(settings: SpectrogramSettings, sample_rate: f64) -> Self {
+        Self {
+            settings,
+            sample_rate,
+            planner: FftPlanner::new(),
+            samples: VecDeque::with_capacity(settings.fft_size + 1),
+            since_column: 0,
+            columns: VecDeque::new(),
+            max_columns: MAX_COLUMNS,
+            texture: None,
+            changed: false,
+        }
+    }
+
+    pub fn settings(&self) -> &SpectrogramSettings {
+        &self.settings
+    }
+
+    /// Feed one sample; a spectrum is added every `hop` samples once a full
+    /// FFT length has arrived.
+    pub fn push(&mut self, value: f32) {
+        let fft_size = self.settings.fft_size;
+        self.samples.push_back(value);
+        if self.samples.len() > fft_size {
+            self.samples.pop_front();
+        }
+        self.since_column += 1;
+        if self.since_column < self.settings.hop || self.samples.len() < fft_size {
+            return;
+        }
+        self.since_column = 0;
+
+        let bins = self.bins();
+        let spectrum = spectrum::magnitude_spectrum(
+            &mut self.planner,
+            self.samples.make_contiguous(),
+            self.sample_rate,
+            fft_size,
+        );
+        let column = spectrum
+            .get(bins.0..=bins.1)
+            .map_or_else(Vec::new, |bins| bins.iter().map(|&[_, db]| db).collect());
+        self.columns.push_back(column);
+        while self.columns.len() > self.max_columns {
+            self.columns.pop_front();
+        }
+        self.changed = true;
+    }
+
+    /// Keep as many spectra as cover `seconds`.
+    pub fn set_window(&mut self, seconds: f64) {
+        let columns = (seconds / self.column_seconds()).ceil() as usize;
+        let columns = columns.clamp(1, MAX_COLUMNS);
+        if columns != self.max_columns {
+            self.max_columns = columns;
+            while self.columns.len() > columns {
+                self.columns.pop_front();
+            }
+            self.changed = true;
+        }
+    }
+
+    /// Seconds from one spectrum to the next.
+    pub fn column_seconds(&self) -> f64 {
+        self.settings.hop as f64 / self.sample_rate
+    }
+
+    /// Seconds covered by the image, newest spectrum on the right.
+    pub fn width_seconds(&self) -> f64 {
+        self.max_columns as f64 * self.column_seconds()
+    }
+
+    /// Hz from one bin to the next.
+    pub fn bin_width(&self) -> f64 {
+        self.sample_rate / self.settings.fft_size as f64
+    }
+
+    /// First and last FFT bin inside the frequency range.
+    fn bins(&self) -> (usize, usize) {
+        let nyquist_bin = self.settings.fft_size / 2;
+        let first = (self.settings.min_frequency / self.bin_width()).ceil() as usize;
+        let last = (self.settings.max_frequency / self.bin_width()).floor() as usize;
+        let last = last.min(nyquist_bin);
+        (first.min(last), last)
+    }
+
+    /// Lower and upper edge in Hz of the rows of the image.
+    pub fn frequency_edges(&self) -> (f64, f64) {
+        let (first, last) = self.bins();
+        let bin_width = self.bin_width();
+        (
+            (first as f64 - 0.5) * bin_width,
+            (last as f64 + 0.5) * bin_width,
+        )
+    }
+
+    /// The image, updated with the spectra added since the last call; `None`
+    /// until the first spectrum is in.
+    pub fn texture(&mut self, ctx: &egui::Context) -> Option<TextureId> {
+        if self.columns.is_empty() {
+            return None;
+        }
+        if self.changed || self.texture.is_none() {
+            let image = self.image();
+            match &mut self.texture {
+                Some(texture) => texture.set(image, TextureOptions::NEAREST),
+                None => {
+                    self.texture =
+                        Some(ctx.load_texture("spectrogram", image, TextureOptions::NEAREST))
+                }
+            }
+            self.changed = false;
+        }
+        self.texture.as_ref().map(|texture| texture.id())
+    }
+
+    /// One column per spectrum, right-aligned, highest frequency in the top row.
+    /// Brightness is relative to the strongest bin on screen.
+    fn image(&self) -> ColorImage {
+        let (first, last) = self.bins();
+        let (width, height) = (self.max_columns, last - first + 1);
+        let top = self
+            .columns
+            .iter()
+            .flatten()
+            .copied()
+            .fold(f64::NEG_INFINITY, f64::max);
+        let floor = top - DYNAMIC_RANGE_DB;
+        let mut rgba = vec![0; width * height * 4];
+        let offset = width - self.columns.len();
+        for (x, column) in self.columns.iter().enumerate() {
+            for (bin, &db) in column.iter().enumerate() {
+                let color = colormap(((db - floor) / DYNAMIC_RANGE_DB) as f32);
+                let y = height - 1 - bin;
+                let i = (y * width + offset + x) * 4;
+                rgba[i..i + 4].copy_from_slice(&color.to_array());
+            }
+        }
+        // columns not filled yet keep the darkest color rather than showing through
+        let background = colormap(0.0).to_array();
+        for y in 0..height {
+            for x in 0..offset {
+                let i = (y * width + x) * 4;
+                rgba[i..i + 4].copy_from_slice(&background);
+            }
+        }
+        ColorImage::from_rgba_unmultiplied([width, height], &rgba)
+    }
+}
+
+/// Viridis-like colormap from dark blue at 0 to yellow at 1.
+pub fn colormap(t: f32) -> Color32 {
+    const STOPS: [[f32; 3]; 5] = [
+        [68.0, 1.0, 84.0],
+        [59.0, 82.0, 139.0],
+        [33.0, 145.0, 140.0],
+        [94.0, 201.0, 98.0],
+        [253.0, 231.0, 37.0],
+    ];
+    let t = if t.is_nan() { 0.0 } else { t.clamp(0.0, 1.0) };
+    let position = t * (STOPS.len() - 1) as f32;
+    let i = (position as usize).min(STOPS.len() - 2);
+    let f = position - i as f32;
+    let [r, g, b] =
+        std::array::from_fn(|c| (STOPS[i][c] + (STOPS[i + 1][c] - STOPS[i][c]) * f).round() as u8);
+    Color32::from_rgb(r, g, b)
+}
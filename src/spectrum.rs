@@ -0,0 +1,53 @@
+// Magnitude spectrum of the most recent samples of a channel.
+use rustfft::FftPlanner;
+use rustfft::num_complex::Complex;
+use std::f64::consts::PI;
+
+pub const FFT_SIZE: usize = 1024; // Longer buffers are truncated to the newest samples, shorter ones zero-padded
+
+/// Hann-windowed magnitude spectrum in dB, as `[frequency, dB]` points from 0 Hz
+/// up to Nyquist. Empty if there is nothing to analyze.
+pub fn magnitude_spectrum(
+    planner: &mut FftPlanner<f64>,
+    samples: &[f32],
+    sample_rate: f64,
+    fft_size: usize,
+) -> Vec<[f64; 2]> {
+    let samples = &samples[samples.len().saturating_sub(fft_size)..];
+    let n = samples.len();
+    if n < 2 || sample_rate <= 0.0 {
+        return Vec::new();
+    }
+
+    // remove the DC offset first, it would otherwise leak into the low bins
+    let finite = samples.iter().filter(|v| v.is_finite());
+    let finite_count = finite.clone().count();
+    if finite_count == 0 {
+        return Vec::new();
+    }
+    let mean = finite.map(|&v| v as f64).sum::<f64>() / finite_count as f64;
+    // a non-finite sample would spread over every bin, it counts as the mean instead
+    let mut buffer: Vec<Complex<f64>> = samples
+        .iter()
+        .enumerate()
+        .map(|(i, &v)| {
+            let window = 0.5 - 0.5 * (2.0 * PI * i as f64 / (n - 1) as f64).cos();
+            let v = if v.is_finite() { v as f64 - mean } else { 0.0 };
+            Complex::new(v * window, 0.0)
+        })
+        .collect();
+    buffer.resize(fft_size, Complex::new(0.0, 0.0));
+    planner.plan_fft_forward(fft_size).process(&mut buffer);
+
+    // amplitude-normalized for the Hann window's coherent gain of 0.5
+    let normalization = 2.0 / (0.5 * n as f64);
+    buffer[..=fft_size / 2]
+        .iter()
+        .enumerate()
+        .map(|(k, bin)| {
+            let frequency = k as f64 * sample_rate / fft_size as f64;
+            let magnitude = bin.norm() * normalization;
+            [frequency, 20.0 * (magnitude + 1e-12).log10()]
+        })
+        .collect()
+}
@@ -0,0 +1,86 @@
+// Summary statistics of the buffered samples of a channel.
+use lsl_viewer::recording::csv_field;
+use std::io::Write;
+use std::path::Path;
+
+#[derive(Clone, Copy)]
+pub struct ChannelStats {
+    pub count: usize, // Finite samples the statistics are over
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    pub std: f64,
+    pub rms: f64,
+}
+
+impl ChannelStats {
+    /// All statistics in a single pass over `values`. Non-finite values are
+    /// skipped; `None` if nothing is left.
+    pub fn compute(values: impl IntoIterator<Item = f32>) -> Option<Self> {
+        let mut count = 0usize;
+        let mut min = f64::INFINITY;
+        let mut max = f64::NEG_INFINITY;
+        let mut sum = 0.0;
+        let mut sum_of_squares = 0.0;
+        for value in values {
+            let value = value as f64;
+            if !value.is_finite() {
+                continue;
+            }
+            count += 1;
+            min = min.min(value);
+            max = max.max(value);
+            sum += value;
+            sum_of_squares += value * value;
+        }
+        if count == 0 {
+            return None;
+        }
+
+        let n = count as f64;
+        let mean = sum / n;
+        // rounding can push the variance of a flat channel slightly below zero
+        let variance = (sum_of_squares / n - mean * mean).max(0.0);
+        Some(Self {
+            count,
+            min,
+            max,
+            mean,
+            std: variance.sqrt(),
+            rms: (sum_of_squares / n).sqrt(),
+        })
+    }
+
+    pub fn peak_to_peak(&self) -> f64 {
+        self.max - self.min
+    }
+}
+
+/// Write one row per channel, with the span the statistics were taken over.
+pub fn write_csv(
+    path: &Path,
+    span: (f64, f64),
+    rows: &[(&str, ChannelStats)],
+) -> std::io::Result<()> {
+    let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+    writeln!(
+        file,
+        "channel,start,end,samples,mean,std,min,max,peak_to_peak"
+    )?;
+    for (name, s) in rows {
+        writeln!(
+            file,
+            "{},{},{},{},{},{},{},{},{}",
+            csv_field(name),
+            span.0,
+            span.1,
+            s.count,
+            s.mean,
+            s.std,
+            s.min,
+            s.max,
+            s.peak_to_peak()
+        )?;
+    }
+    file.flush()
+}
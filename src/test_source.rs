@@ -0,0 +1,183 @@
+// Deterministic synthetic data source. With the `test-source` feature enabled and
+// LSL_VIEWER_TEST_SOURCE set, it runs in place of the LSL thread, answering the same
+// commands with the same responses, so the UI and processing run without LSL.
+use crossbeam_channel::{Receiver, RecvTimeoutError, Sender};
+use lsl_viewer::recording::Recorder;
+use lsl_viewer::{COMMAND_POLL_INTERVAL, DataSample, LslCommand, LslResponse, StreamData};
+use std::f64::consts::TAU;
+use std::time::Instant;
+
+const STREAM_NAME: &str = "Test Source";
+const AMPLITUDE: f64 = 50.0; // Peak amplitude of the generated waveforms, in µV
+
+#[derive(Clone)]
+pub struct TestSourceConfig {
+    pub channel_count: usize,
+    pub sample_rate: f64,
+    /// After every `n` samples, skip `length` samples' worth of time.
+    pub gap: Option<(usize, usize)>,
+    /// Every `n`th sample has NaN on all channels.
+    pub nan_every: Option<usize>,
+}
+
+impl Default for TestSourceConfig {
+    fn default() -> Self {
+        Self {
+            channel_count: 8,
+            sample_rate: 250.0,
+            gap: None,
+            nan_every: None,
+        }
+    }
+}
+
+/// The `index`th generated sample. Channel `i` is a sine at `i + 1` Hz, except
+/// the last channel which is a 1 Hz square wave, so sharp edges can be checked too.
+pub fn sample_at(config: &TestSourceConfig, index: usize) -> DataSample {
+    let skipped = config
+        .gap
+        .filter(|&(every, _)| every > 0)
+        .map_or(0, |(every, length)| (index / every) * length);
+    let timestamp = (index + skipped) as f64 / config.sample_rate;
+
+    let is_nan = config
+        .nan_every
+        .is_some_and(|every| every > 0 && index % every == every - 1);
+    let values = (0..config.channel_count)
+        .map(|ch| {
+            if is_nan {
+                f32::NAN
+            } else if ch + 1 == config.channel_count && ch > 0 {
+                (AMPLITUDE * (TAU * timestamp).sin().signum()) as f32
+            } else {
+                (AMPLITUDE * (TAU * (ch + 1) as f64 * timestamp).sin()) as f32
+            }
+        })
+        .collect();
+    DataSample { timestamp, values }
+}
+
+pub fn test_source_thread(
+    cmd_rx: Receiver<LslCommand>,
+    resp_tx: Sender<LslResponse>,
+    config: TestSourceConfig,
+) {
+    // Set while connected: when the stream started and how many samples went out
+    let mut running: Option<(Instant, usize)> = None;
+    let mut recorder: Option<Recorder> = None;
+    let channel_names = lsl_viewer::default_channel_names(config.channel_count);
+    let stream = StreamData {
+        name: STREAM_NAME.to_string(),
+        stream_type: "EEG".to_string(),
+        source_id: "test-source".to_string(),
+        hostname: "localhost".to_string(),
+        channel_count: config.channel_count,
+        sample_rate: config.sample_rate,
+        channel_format: "float32".to_string(),
+    };
+    let mut paused = false;
+
+    loop {
+        match cmd_rx.recv_timeout(COMMAND_POLL_INTERVAL) {
+            Ok(LslCommand::RefreshStreams | LslCommand::RefreshStreamsFiltered(_)) => {
+                let _ = resp_tx.send(LslResponse::StreamsFound(vec![stream.clone()]));
+            }
+            Ok(LslCommand::ConnectByName(name, _)) if name != STREAM_NAME => {
+                let _ = resp_tx.send(LslResponse::Error(format!(
+                    "No stream named '{}' found",
+                    name
+                )));
+            }
+            Ok(LslCommand::Connect(..) | LslCommand::ConnectByName(..)) => {
+                running = Some((Instant::now(), 0));
+                paused = false;
+                let _ = resp_tx.send(LslResponse::Connected {
+                    stream: stream.clone(),
+                    channel_names: channel_names.clone(),
+                    channel_units: vec!["µV".to_string(); config.channel_count],
+                    channel_types: vec!["EEG".to_string(); config.channel_count],
+                    metadata_available: true,
+                });
+            }
+            Ok(LslCommand::Disconnect) => {
+                lsl_viewer::stop_recording(&mut recorder, &resp_tx);
+                running = None;
+                let _ = resp_tx.send(LslResponse::Disconnected);
+            }
+            Ok(LslCommand::ConnectMarkers(_)) => {
+                let _ = resp_tx.send(LslResponse::Error(
+                    "The test source has no marker streams".to_string(),
+                ));
+            }
+            Ok(LslCommand::DisconnectMarkers) => {
+                let _ = resp_tx.send(LslResponse::MarkersDisconnected);
+            }
+            Ok(LslCommand::StartRecording(path)) => {
+                lsl_viewer::stop_recording(&mut recorder, &resp_tx);
+                match Recorder::create(&path, &channel_names) {
+                    Ok(new_recorder) => {
+                        recorder = Some(new_recorder);
+                        let _ = resp_tx.send(LslResponse::RecordingStarted(path));
+                    }
+                    Err(e) => {
+                        let _ = resp_tx.send(LslResponse::Error(format!(
+                            "Failed to start recording: {}",
+                            e
+                        )));
+                    }
+                }
+            }
+            Ok(LslCommand::StopRecording) => lsl_viewer::stop_recording(&mut recorder, &resp_tx),
+            Ok(LslCommand::Pause) => paused = true,
+            Ok(LslCommand::Resume) => {
+                paused = false;
+                // like flushing an inlet: what came due while paused is skipped
+                if let Some((started, sent)) = running.as_mut() {
+                    *sent = (started.elapsed().as_secs_f64() * config.sample_rate) as usize;
+                }
+            }
+            // connecting is instant and samples are generated as they come due
+            Ok(
+                LslCommand::CancelConnect
+                | LslCommand::SkipBacklog
+                | LslCommand::SetPlaybackPaused(_)
+                | LslCommand::SetPlaybackSpeed(_)
+                | LslCommand::SeekPlayback(_),
+            ) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+            Err(RecvTimeoutError::Timeout) => {}
+        }
+
+        // Emit whatever is due in real time; the content only depends on the index
+        if let Some((started, sent)) = running.as_mut().filter(|_| !paused) {
+            let due = (started.elapsed().as_secs_f64() * config.sample_rate) as usize;
+            while *sent < due {
+                let sample = sample_at(&config, *sent);
+                if let Some(active) = recorder.as_mut()
+                    && let Err(e) = active.write(&sample)
+                {
+                    let _ = resp_tx.send(LslResponse::Error(format!("Recording failed: {}", e)));
+                    lsl_viewer::stop_recording(&mut recorder, &resp_tx);
+                }
+                if resp_tx.send(LslResponse::Data(sample)).is_err() {
+                    return;
+                }
+                *sent += 1;
+            }
+            if let Some(active) = recorder.as_mut() {
+                match active.flush_if_due() {
+                    Ok(true) => {
+                        let _ =
+                            resp_tx.send(LslResponse::RecordingProgress(active.samples_written));
+                    }
+                    Ok(false) => {}
+                    Err(e) => {
+                        let _ =
+                            resp_tx.send(LslResponse::Error(format!("Recording failed: {}", e)));
+                        lsl_viewer::stop_recording(&mut recorder, &resp_tx);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,189 @@
+// Minimal XDF writer: one float32 stream with a header, samples and footer, and
+// the annotations as a second, string-valued stream.
+// See https://github.com/sccn/xdf/wiki/Specifications for the format.
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+const TAG_FILE_HEADER: u16 = 1;
+const TAG_STREAM_HEADER: u16 = 2;
+const TAG_SAMPLES: u16 = 3;
+const TAG_STREAM_FOOTER: u16 = 6;
+const STREAM_ID: u32 = 1;
+const ANNOTATION_STREAM_ID: u32 = 2;
+const SAMPLES_PER_CHUNK: usize = 1024;
+
+pub struct XdfStream<'a> {
+    pub name: &'a str,
+    pub channel_names: &'a [String],
+    pub unit: &'a str,
+    pub sample_rate: f64,
+    pub timestamps: &'a [f64],
+    /// One entry per channel, each as long as `timestamps`.
+    pub channels: &'a [Vec<f32>],
+    /// Timestamp and text of each annotation; no annotation stream is written without any.
+    pub annotations: &'a [(f64, String)],
+}
+
+pub fn write_xdf(path: &Path, stream: &XdfStream) -> std::io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writer.write_all(b"XDF:")?;
+    write_chunk(
+        &mut writer,
+        TAG_FILE_HEADER,
+        b"<?xml version=\"1.0\"?><info><version>1.0</version></info>",
+    )?;
+
+    let mut header = String::from("<?xml version=\"1.0\"?><info>");
+    header += &format!("<name>{}</name>", escape_xml(stream.name));
+    header += &format!("<channel_count>{}</channel_count>", stream.channels.len());
+    header += &format!("<nominal_srate>{}</nominal_srate>", stream.sample_rate);
+    header += "<channel_format>float32</channel_format><desc><channels>";
+    for name in stream.channel_names {
+        header += &format!(
+            "<channel><label>{}</label><unit>{}</unit></channel>",
+            escape_xml(name),
+            escape_xml(stream.unit)
+        );
+    }
+    header += "</channels></desc></info>";
+    write_chunk(
+        &mut writer,
+        TAG_STREAM_HEADER,
+        &with_stream_id(STREAM_ID, header.as_bytes()),
+    )?;
+    if !stream.annotations.is_empty() {
+        let header = format!(
+            "<?xml version=\"1.0\"?><info><name>{} Annotations</name><type>Markers</type><channel_count>1</channel_count><nominal_srate>0</nominal_srate><channel_format>string</channel_format></info>",
+            escape_xml(stream.name)
+        );
+        write_chunk(
+            &mut writer,
+            TAG_STREAM_HEADER,
+            &with_stream_id(ANNOTATION_STREAM_ID, header.as_bytes()),
+        )?;
+    }
+
+    let sample_count = stream.timestamps.len();
+    let mut start = 0;
+    while start < sample_count {
+        let end = (start + SAMPLES_PER_CHUNK).min(sample_count);
+        let mut content = STREAM_ID.to_le_bytes().to_vec();
+        // sample count as a variable-length integer, always 4 bytes here
+        content.push(4);
+        content.extend_from_slice(&((end - start) as u32).to_le_bytes());
+        for i in start..end {
+            // every sample carries its own timestamp, so they are preserved exactly
+            content.push(8);
+            content.extend_from_slice(&stream.timestamps[i].to_le_bytes());
+            for channel in stream.channels {
+                content.extend_from_slice(&channel[i].to_le_bytes());
+            }
+        }
+        write_chunk(&mut writer, TAG_SAMPLES, &content)?;
+        start = end;
+    }
+    if !stream.annotations.is_empty() {
+        let mut content = ANNOTATION_STREAM_ID.to_le_bytes().to_vec();
+        content.push(4);
+        content.extend_from_slice(&(stream.annotations.len() as u32).to_le_bytes());
+        for (timestamp, text) in stream.annotations {
+            content.push(8);
+            content.extend_from_slice(&timestamp.to_le_bytes());
+            // a string value is its byte length, again as a variable-length integer, then the bytes
+            content.push(4);
+            content.extend_from_slice(&(text.len() as u32).to_le_bytes());
+            content.extend_from_slice(text.as_bytes());
+        }
+        write_chunk(&mut writer, TAG_SAMPLES, &content)?;
+    }
+
+    write_footer(&mut writer, STREAM_ID, stream.timestamps)?;
+    if !stream.annotations.is_empty() {
+        let timestamps: Vec<f64> = stream.annotations.iter().map(|(t, _)| *t).collect();
+        write_footer(&mut writer, ANNOTATION_STREAM_ID, &timestamps)?;
+    }
+    writer.flush()
+}
+
+fn write_footer(
+    writer: &mut impl Write,
+    stream_id: u32,
+    timestamps: &[f64],
+) -> std::io::Result<()> {
+    let footer = format!(
+        "<?xml version=\"1.0\"?><info><first_timestamp>{}</first_timestamp><last_timestamp>{}</last_timestamp><sample_count>{}</sample_count></info>",
+        timestamps.first().copied().unwrap_or(0.0),
+        timestamps.last().copied().unwrap_or(0.0),
+        timestamps.len()
+    );
+    write_chunk(
+        writer,
+        TAG_STREAM_FOOTER,
+        &with_stream_id(stream_id, footer.as_bytes()),
+    )
+}
+
+fn with_stream_id(stream_id: u32, content: &[u8]) -> Vec<u8> {
+    let mut bytes = stream_id.to_le_bytes().to_vec();
+    bytes.extend_from_slice(content);
+    bytes
+}
+
+/// Write one chunk: a variable-length byte count, the tag and the content.
+fn write_chunk(writer: &mut impl Write, tag: u16, content: &[u8]) -> std::io::Result<()> {
+    let length = (content.len() + 2) as u64;
+    if length <= u8::MAX as u64 {
+        writer.write_all(&[1, length as u8])?;
+    } else if length <= u32::MAX as u64 {
+        writer.write_all(&[4])?;
+        writer.write_all(&(length as u32).to_le_bytes())?;
+    } else {
+        writer.write_all(&[8])?;
+        writer.write_all(&length.to_le_bytes())?;
+    }
+    writer.write_all(&tag.to_le_bytes())?;
+    writer.write_all(content)
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Read back the sample timestamps of the data stream of a file written by `write_xdf`.
+#[cfg(test)]
+pub fn read_timestamps(path: &Path, channel_count: usize) -> std::io::Result<Vec<f64>> {
+    let bytes = std::fs::read(path)?;
+    assert_eq!(&bytes[..4], b"XDF:");
+    let read_varlen = |pos: &mut usize| -> u64 {
+        let n = bytes[*pos] as usize;
+        let mut buf = [0u8; 8];
+        buf[..n].copy_from_slice(&bytes[*pos + 1..*pos + 1 + n]);
+        *pos += 1 + n;
+        u64::from_le_bytes(buf)
+    };
+
+    let mut timestamps = Vec::new();
+    let mut pos = 4;
+    while pos < bytes.len() {
+        let length = read_varlen(&mut pos) as usize;
+        let tag = u16::from_le_bytes([bytes[pos], bytes[pos + 1]]);
+        let end = pos + length;
+        if tag == TAG_SAMPLES && bytes[pos + 2..pos + 6] == STREAM_ID.to_le_bytes() {
+            let mut p = pos + 2 + 4;
+            let count = read_varlen(&mut p);
+            for _ in 0..count {
+                assert_eq!(bytes[p], 8);
+                let mut buf = [0u8; 8];
+                buf.copy_from_slice(&bytes[p + 1..p + 9]);
+                timestamps.push(f64::from_le_bytes(buf));
+                p += 9 + 4 * channel_count;
+            }
+            assert_eq!(p, end);
+        }
+        pos = end;
+    }
+    Ok(timestamps)
+}
@@ -0,0 +1,66 @@
+// Just enough XML parsing to show stream metadata as a tree. Attributes, comments
+// and processing instructions are skipped; LSL doesn't use them for metadata.
+
+pub struct XmlNode {
+    pub name: String,
+    pub text: String,
+    pub children: Vec<XmlNode>,
+}
+
+/// Parse the root element of `xml`, or `None` if it isn't well-formed enough.
+pub fn parse(xml: &str) -> Option<XmlNode> {
+    let mut rest = xml;
+    loop {
+        rest = &rest[rest.find('<')?..];
+        if rest.starts_with("<?") || rest.starts_with("<!") {
+            rest = &rest[rest.find('>')? + 1..];
+        } else {
+            return parse_element(&mut rest);
+        }
+    }
+}
+
+/// Parse the element at the start of `rest` and advance past it.
+fn parse_element(rest: &mut &str) -> Option<XmlNode> {
+    let tag_end = rest.find('>')?;
+    let tag = &rest[1..tag_end];
+    let self_closing = tag.ends_with('/');
+    let name = tag
+        .trim_end_matches('/')
+        .split_whitespace()
+        .next()?
+        .to_string();
+    *rest = &rest[tag_end + 1..];
+
+    let mut node = XmlNode {
+        name,
+        text: String::new(),
+        children: Vec::new(),
+    };
+    if self_closing {
+        return Some(node);
+    }
+
+    loop {
+        let next = rest.find('<')?;
+        node.text.push_str(&rest[..next]);
+        *rest = &rest[next..];
+        if rest.starts_with("</") {
+            *rest = &rest[rest.find('>')? + 1..];
+            node.text = decode_entities(node.text.trim());
+            return Some(node);
+        } else if rest.starts_with("<!") || rest.starts_with("<?") {
+            *rest = &rest[rest.find('>')? + 1..];
+        } else {
+            node.children.push(parse_element(rest)?);
+        }
+    }
+}
+
+fn decode_entities(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}